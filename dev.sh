@@ -1,8 +1,5 @@
 #!/bin/bash
 
-# Set up environment
-export DATABASE_URL=sqlite:data_dash.db
-
 # Reset DB
 ./scripts/reset_db.sh
 
@@ -0,0 +1,248 @@
+-- Schema shared by SQLite and PostgreSQL. On PostgreSQL, INTEGER columns
+-- become BIGINT and AUTOINCREMENT keys BIGSERIAL.
+
+CREATE TABLE IF NOT EXISTS check_results (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    check_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    message TEXT,
+    details TEXT,
+    executed_at TIMESTAMPTZ NOT NULL,
+    environment TEXT
+);
+
+CREATE TABLE IF NOT EXISTS connection_profiles (
+    name TEXT PRIMARY KEY,
+    driver TEXT NOT NULL,
+    connection_string_template TEXT NOT NULL,
+    connection_type TEXT DEFAULT 'database',
+    secret_ref TEXT
+);
+
+CREATE TABLE IF NOT EXISTS secrets (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS data_sources (
+    name TEXT PRIMARY KEY,
+    connection_name TEXT NOT NULL,
+    secret_key TEXT NOT NULL,
+    is_valid BOOLEAN DEFAULT TRUE,
+    FOREIGN KEY (connection_name) REFERENCES connection_profiles(name),
+    FOREIGN KEY (secret_key) REFERENCES secrets(key)
+);
+
+CREATE TABLE IF NOT EXISTS unix_groups (
+    group_name TEXT PRIMARY KEY,
+    file_path TEXT NOT NULL,
+    permissions TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS runs (
+    id TEXT PRIMARY KEY,
+    check_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    params TEXT,
+    result TEXT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS check_attempts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    result_id INTEGER NOT NULL,
+    attempt INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    message TEXT,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY (result_id) REFERENCES check_results(id)
+);
+
+CREATE TABLE IF NOT EXISTS alert_state (
+    check_id TEXT PRIMARY KEY,
+    state TEXT NOT NULL,
+    consecutive_warnings INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS alerts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    check_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL,
+    message TEXT,
+    result_id INTEGER,
+    deliveries TEXT,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS webhooks (
+    name TEXT PRIMARY KEY,
+    url TEXT NOT NULL,
+    headers TEXT,
+    payload_template TEXT,
+    signing_secret_ref TEXT,
+    max_attempts INTEGER NOT NULL DEFAULT 3,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    webhook TEXT NOT NULL,
+    check_id TEXT NOT NULL,
+    alert_kind TEXT NOT NULL,
+    attempt INTEGER NOT NULL,
+    status_code INTEGER,
+    error TEXT,
+    delivered BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS email_channels (
+    name TEXT PRIMARY KEY,
+    host TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    security TEXT NOT NULL,
+    username TEXT,
+    password_secret_ref TEXT,
+    from_address TEXT NOT NULL,
+    recipients TEXT NOT NULL,
+    send_digest BOOLEAN NOT NULL DEFAULT FALSE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE TABLE IF NOT EXISTS maintenance_windows (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    reason TEXT,
+    schedule TEXT NOT NULL,
+    scope TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS silences (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    check_id TEXT NOT NULL,
+    silenced_until TIMESTAMPTZ NOT NULL,
+    reason TEXT NOT NULL,
+    author TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS acknowledgements (
+    check_id TEXT PRIMARY KEY,
+    author TEXT NOT NULL,
+    comment TEXT,
+    acknowledged_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS result_annotations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    result_id INTEGER NOT NULL,
+    author TEXT NOT NULL,
+    text TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY (result_id) REFERENCES check_results(id)
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    username TEXT PRIMARY KEY,
+    password_hash TEXT NOT NULL,
+    disabled BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    token_hash TEXT PRIMARY KEY,
+    username TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    username TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT NOT NULL,
+    expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL,
+    last_used_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS roles (
+    name TEXT PRIMARY KEY,
+    description TEXT,
+    permissions TEXT NOT NULL
+);
+
+INSERT INTO roles (name, description, permissions) VALUES
+    ('viewer', 'See checks and results', '["view"]'),
+    ('analyst', 'Run checks and handle their results', '["view","run"]'),
+    ('platform-engineer', 'Run checks and manage connections', '["view","run","manage-connections"]'),
+    ('secrets-manager', 'Manage secrets', '["manage-secrets"]'),
+    ('admin', 'Full access', '["admin"]')
+ON CONFLICT (name) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    username TEXT NOT NULL,
+    role TEXT NOT NULL,
+    scope TEXT NOT NULL DEFAULT '{}',
+    PRIMARY KEY (username, role),
+    FOREIGN KEY (role) REFERENCES roles(name)
+);
+
+-- Append-only, see 0002_append_only_audit_log.
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    resource_type TEXT NOT NULL,
+    resource_id TEXT NOT NULL,
+    ip TEXT,
+    before TEXT,
+    after TEXT
+);
+CREATE TABLE IF NOT EXISTS environments (
+    name TEXT PRIMARY KEY,
+    description TEXT
+);
+
+CREATE TABLE IF NOT EXISTS connection_profile_overrides (
+    profile_name TEXT NOT NULL,
+    environment TEXT NOT NULL,
+    driver TEXT,
+    connection_string_template TEXT,
+    secret_ref TEXT,
+    PRIMARY KEY (profile_name, environment),
+    FOREIGN KEY (profile_name) REFERENCES connection_profiles(name) ON DELETE CASCADE,
+    FOREIGN KEY (environment) REFERENCES environments(name) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS data_source_overrides (
+    data_source TEXT NOT NULL,
+    environment TEXT NOT NULL,
+    connection_name TEXT,
+    secret_key TEXT,
+    PRIMARY KEY (data_source, environment),
+    FOREIGN KEY (data_source) REFERENCES data_sources(name) ON DELETE CASCADE,
+    FOREIGN KEY (environment) REFERENCES environments(name) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS schedules (
+    name TEXT PRIMARY KEY,
+    cron TEXT NOT NULL,
+    checks TEXT NOT NULL,
+    check_group TEXT,
+    environment TEXT,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE TABLE IF NOT EXISTS check_metadata (
+    check_id TEXT PRIMARY KEY,
+    metadata TEXT NOT NULL
+);
@@ -0,0 +1,3 @@
+-- How HTTP requests to api connection profiles authenticate with their
+-- secret, see `HttpAuth`.
+ALTER TABLE connection_profiles ADD COLUMN auth TEXT;
@@ -16,7 +16,8 @@ if ! command -v sqlite3 &> /dev/null; then
 fi
 
 echo "Creating schema..."
-sqlite3 "$DB_FILE" < scripts/schema.sql
+touch "$DB_FILE"
+cargo run --quiet -- --database-url "sqlite:$DB_FILE" migrate
 
 echo "Seeding data..."
 sqlite3 "$DB_FILE" < scripts/seed_data.sql
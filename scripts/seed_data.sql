@@ -31,14 +31,14 @@ INSERT INTO connection_profiles (name, driver, connection_string_template, conne
 VALUES ('Oracle DB', 'oracle', 'DSN=OracleDB;Driver=/opt/oracle/instantclient_21_1/libsqora.so.21.1;Server=db.example.com;Port=1521;ServiceName=ORCL;UID=system;PWD={{PASSWORD}}', 'database', 'oracle_password');
 
 -- API Connection Profiles
-INSERT INTO connection_profiles (name, driver, connection_string_template, connection_type, secret_ref) 
-VALUES ('Petstore API', 'openapi', 'https://petstore3.swagger.io/api/v3/openapi.json', 'api', 'example_api_key');
+INSERT INTO connection_profiles (name, driver, connection_string_template, connection_type, secret_ref, auth) 
+VALUES ('Petstore API', 'openapi', 'https://petstore3.swagger.io/api/v3', 'api', 'example_api_key', 'header:api_key');
 
 INSERT INTO connection_profiles (name, driver, connection_string_template, connection_type, secret_ref) 
 VALUES ('JSONPlaceholder API', 'openapi', 'https://jsonplaceholder.typicode.com/', 'api', NULL);
 
 INSERT INTO connection_profiles (name, driver, connection_string_template, connection_type, secret_ref) 
-VALUES ('GitHub API', 'openapi', 'https://raw.githubusercontent.com/github/rest-api-description/main/descriptions/api.github.com/api.github.com.json', 'api', NULL);
+VALUES ('GitHub API', 'openapi', 'https://api.github.com', 'api', NULL);
 
 -- File Connection Profiles
 INSERT INTO connection_profiles (name, driver, connection_string_template, connection_type, secret_ref) 
@@ -0,0 +1,603 @@
+//! Email notifications over SMTP.
+//!
+//! Each alert is sent as a multipart email with plain-text and HTML bodies.
+//! Channels can also opt into a daily digest listing the latest status of
+//! every check. The SMTP password is referenced by secret key and resolved
+//! through the `SecretStore` when a message is sent.
+
+use super::{Alert, AlertKind, Notifier, NotifyError};
+use crate::db::{Db, EmailChannelRecord};
+use crate::secrets::SecretStore;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration as ChronoDuration, NaiveTime, Utc};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SmtpSecurity {
+    /// Unencrypted connection. Only for local relays.
+    Plain,
+    /// Upgrade the connection with STARTTLS; fails if the server does not offer it.
+    StartTls,
+    /// TLS from the first byte (SMTPS).
+    Tls,
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Plain" => Ok(SmtpSecurity::Plain),
+            "StartTls" => Ok(SmtpSecurity::StartTls),
+            "Tls" => Ok(SmtpSecurity::Tls),
+            other => Err(format!("Unknown SMTP security mode: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    /// Secret holding the SMTP password.
+    pub password_secret_ref: Option<String>,
+    pub from: String,
+    pub recipients: Vec<String>,
+    /// Also send the daily status digest to this channel.
+    #[serde(default)]
+    pub send_digest: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl TryFrom<EmailChannelRecord> for EmailConfig {
+    type Error = String;
+
+    fn try_from(r: EmailChannelRecord) -> Result<Self, Self::Error> {
+        Ok(EmailConfig {
+            name: r.name,
+            host: r.host,
+            port: u16::try_from(r.port).map_err(|e| e.to_string())?,
+            security: r.security.parse()?,
+            username: r.username,
+            password_secret_ref: r.password_secret_ref,
+            from: r.from_address,
+            recipients: serde_json::from_value(r.recipients).map_err(|e| e.to_string())?,
+            send_digest: r.send_digest,
+            enabled: r.enabled,
+        })
+    }
+}
+
+impl From<&EmailConfig> for EmailChannelRecord {
+    fn from(c: &EmailConfig) -> Self {
+        EmailChannelRecord {
+            name: c.name.clone(),
+            host: c.host.clone(),
+            port: c.port as i64,
+            security: format!("{:?}", c.security),
+            username: c.username.clone(),
+            password_secret_ref: c.password_secret_ref.clone(),
+            from_address: c.from.clone(),
+            recipients: serde_json::to_value(&c.recipients).unwrap_or_default(),
+            send_digest: c.send_digest,
+            enabled: c.enabled,
+        }
+    }
+}
+
+/// A rendered email.
+#[derive(Debug, Clone)]
+pub struct EmailBody {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// Latest status of one check, as listed in the digest.
+#[derive(Debug, Clone)]
+pub struct DigestEntry {
+    pub check_id: String,
+    pub status: Option<String>,
+    pub executed_at: Option<DateTime<Utc>>,
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn render_alert(alert: &Alert) -> EmailBody {
+    let headline = match alert.kind {
+        AlertKind::Failure => format!("[FAILURE] {} is failing", alert.check_id),
+        AlertKind::Recovery => format!("[RECOVERED] {} is passing again", alert.check_id),
+        AlertKind::RepeatedWarning => {
+            format!("[WARNING] {} keeps returning warnings", alert.check_id)
+        }
+    };
+
+    let metadata = &alert.metadata;
+    let mut fields = vec![
+        ("Check", alert.check_id.clone()),
+        ("Status", format!("{:?}", alert.status)),
+        ("Message", alert.message.clone()),
+        ("Raised at", alert.raised_at.to_rfc3339()),
+    ];
+    if let Some(owner) = &metadata.owner {
+        fields.push(("Owner", owner.clone()));
+    }
+    if let Some(contact) = &metadata.contact {
+        fields.push(("Contact", contact.clone()));
+    }
+    if let Some(severity) = metadata.severity {
+        fields.push(("Severity", format!("{:?}", severity)));
+    }
+    if let Some(runbook) = &metadata.runbook_url {
+        fields.push(("Runbook", runbook.clone()));
+    }
+
+    let text = fields
+        .iter()
+        .map(|(k, v)| format!("{}: {}", k, v))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let rows = fields
+        .iter()
+        .map(|(k, v)| {
+            format!(
+                "<tr><th align=\"left\">{}</th><td>{}</td></tr>",
+                k,
+                escape_html(v)
+            )
+        })
+        .collect::<String>();
+    let html = format!(
+        "<html><body><h2>{}</h2><table>{}</table></body></html>",
+        escape_html(&headline),
+        rows
+    );
+
+    EmailBody {
+        subject: headline,
+        text,
+        html,
+    }
+}
+
+pub fn render_digest(entries: &[DigestEntry], date: DateTime<Utc>) -> EmailBody {
+    let failing = entries
+        .iter()
+        .filter(|e| {
+            !matches!(
+                e.status.as_deref(),
+                Some("Success") | Some("Warning") | Some("Suppressed")
+            )
+        })
+        .count();
+    let subject = format!(
+        "Data check digest {}: {} of {} checks need attention",
+        date.format("%Y-%m-%d"),
+        failing,
+        entries.len()
+    );
+
+    let describe = |e: &DigestEntry| {
+        (
+            e.status.clone().unwrap_or_else(|| "Not Run".to_string()),
+            e.executed_at
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_else(|| "never".to_string()),
+        )
+    };
+    let text = entries
+        .iter()
+        .map(|e| {
+            let (status, at) = describe(e);
+            format!("{:<40} {:<10} {}", e.check_id, status, at)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let rows = entries
+        .iter()
+        .map(|e| {
+            let (status, at) = describe(e);
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(&e.check_id),
+                escape_html(&status),
+                at
+            )
+        })
+        .collect::<String>();
+    let html = format!(
+        "<html><body><h2>{}</h2><table><tr><th>Check</th><th>Status</th><th>Last run</th></tr>{}</table></body></html>",
+        escape_html(&subject),
+        rows
+    );
+
+    EmailBody {
+        subject,
+        text,
+        html,
+    }
+}
+
+pub struct EmailNotifier {
+    config: EmailConfig,
+    secrets: Arc<dyn SecretStore>,
+}
+
+impl EmailNotifier {
+    pub fn new(config: EmailConfig, secrets: Arc<dyn SecretStore>) -> Self {
+        Self { config, secrets }
+    }
+
+    async fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, NotifyError> {
+        let host = self.config.host.as_str();
+        let builder = match self.config.security {
+            SmtpSecurity::Plain => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| NotifyError::Config(e.to_string()))?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .map_err(|e| NotifyError::Config(e.to_string()))?,
+        };
+        let mut builder = builder.port(self.config.port);
+
+        if let Some(username) = &self.config.username {
+            let password = match &self.config.password_secret_ref {
+                Some(secret_ref) => self
+                    .secrets
+                    .get_secret(secret_ref)
+                    .await
+                    .map_err(|e| NotifyError::Config(e.to_string()))?,
+                None => String::new(),
+            };
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(builder.build())
+    }
+
+    pub async fn send(&self, body: EmailBody) -> Result<(), NotifyError> {
+        let config_err = |e: String| NotifyError::Config(e);
+        let from: Mailbox = self
+            .config
+            .from
+            .parse()
+            .map_err(|e| config_err(format!("Invalid sender: {}", e)))?;
+
+        let mut message = Message::builder().from(from).subject(body.subject);
+        for recipient in &self.config.recipients {
+            let to: Mailbox = recipient
+                .parse()
+                .map_err(|e| config_err(format!("Invalid recipient {}: {}", recipient, e)))?;
+            message = message.to(to);
+        }
+        let message = message
+            .multipart(MultiPart::alternative_plain_html(body.text, body.html))
+            .map_err(|e| config_err(e.to_string()))?;
+
+        self.transport()
+            .await?
+            .send(message)
+            .await
+            .map_err(|e| NotifyError::Delivery(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        self.send(render_alert(alert)).await
+    }
+}
+
+/// Delivers alerts to every enabled email channel stored in the database.
+pub struct EmailChannels {
+    db: Db,
+    secrets: Arc<dyn SecretStore>,
+}
+
+impl EmailChannels {
+    pub fn new(db: Db, secrets: Arc<dyn SecretStore>) -> Self {
+        Self { db, secrets }
+    }
+
+    async fn enabled(&self) -> Result<Vec<EmailConfig>, NotifyError> {
+        let records = self
+            .db
+            .get_email_channels()
+            .await
+            .map_err(|e| NotifyError::Config(e.to_string()))?;
+        Ok(records
+            .into_iter()
+            .filter_map(|r| {
+                let name = r.name.clone();
+                EmailConfig::try_from(r)
+                    .map_err(|e| tracing::error!("Invalid email channel {}: {}", name, e))
+                    .ok()
+            })
+            .filter(|c| c.enabled)
+            .collect())
+    }
+
+    /// Send the status digest for `check_ids` to every channel that opted in.
+    pub async fn send_digest(&self, check_ids: &[String]) -> Result<(), NotifyError> {
+        let latest = self
+            .db
+            .get_latest_check_statuses()
+            .await
+            .map_err(|e| NotifyError::Delivery(e.to_string()))?;
+        let entries: Vec<DigestEntry> = check_ids
+            .iter()
+            .map(|id| DigestEntry {
+                check_id: id.clone(),
+                status: latest.get(id).map(|(s, _)| s.clone()),
+                executed_at: latest.get(id).map(|(_, t)| *t),
+            })
+            .collect();
+        let body = render_digest(&entries, Utc::now());
+
+        for config in self.enabled().await? {
+            if !config.send_digest {
+                continue;
+            }
+            let name = config.name.clone();
+            if let Err(e) = EmailNotifier::new(config, self.secrets.clone())
+                .send(body.clone())
+                .await
+            {
+                tracing::error!("Failed to send digest to {}: {}", name, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the digest every day at `at` (UTC). Runs until the task is dropped.
+    pub async fn run_daily_digest(self: Arc<Self>, check_ids: Vec<String>, at: NaiveTime) {
+        loop {
+            let now = Utc::now();
+            let wait = (next_occurrence(now, at) - now)
+                .to_std()
+                .unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            if let Err(e) = self.send_digest(&check_ids).await {
+                tracing::error!("Failed to send daily digest: {}", e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailChannels {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let body = render_alert(alert);
+        let mut failed = Vec::new();
+        for config in self.enabled().await? {
+            let name = config.name.clone();
+            if let Err(e) = EmailNotifier::new(config, self.secrets.clone())
+                .send(body.clone())
+                .await
+            {
+                failed.push(format!("{}: {}", name, e));
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(NotifyError::Delivery(failed.join("; ")))
+        }
+    }
+}
+
+/// The first time after `now` at which the clock reads `at`.
+fn next_occurrence(now: DateTime<Utc>, at: NaiveTime) -> DateTime<Utc> {
+    let today = now.date_naive().and_time(at).and_utc();
+    if today > now {
+        today
+    } else {
+        today + ChronoDuration::days(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::{CheckMetadata, CheckStatus};
+    use crate::secrets::MemorySecretStore;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// A minimal SMTP server that accepts every message and keeps the raw
+    /// DATA sections and AUTH lines it received.
+    #[derive(Default)]
+    struct SmtpSink {
+        messages: Mutex<Vec<String>>,
+        auth: Mutex<Vec<String>>,
+    }
+
+    async fn start_sink() -> (u16, Arc<SmtpSink>) {
+        let sink = Arc::new(SmtpSink::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = sink.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let sink = state.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut lines = BufReader::new(read).lines();
+                    write.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let command = line.to_uppercase();
+                        let reply: &[u8] = if command.starts_with("EHLO") {
+                            b"250-sink\r\n250 AUTH PLAIN LOGIN\r\n"
+                        } else if command.starts_with("AUTH") {
+                            sink.auth.lock().unwrap().push(line.clone());
+                            b"235 OK\r\n"
+                        } else if command.starts_with("DATA") {
+                            write.write_all(b"354 go ahead\r\n").await.unwrap();
+                            let mut data = Vec::new();
+                            while let Ok(Some(line)) = lines.next_line().await {
+                                if line == "." {
+                                    break;
+                                }
+                                data.push(line);
+                            }
+                            sink.messages.lock().unwrap().push(data.join("\n"));
+                            b"250 queued\r\n"
+                        } else if command.starts_with("QUIT") {
+                            write.write_all(b"221 bye\r\n").await.unwrap();
+                            break;
+                        } else {
+                            b"250 OK\r\n"
+                        };
+                        write.write_all(reply).await.unwrap();
+                    }
+                });
+            }
+        });
+        (port, sink)
+    }
+
+    fn config(port: u16) -> EmailConfig {
+        EmailConfig {
+            name: "oncall".to_string(),
+            host: "127.0.0.1".to_string(),
+            port,
+            security: SmtpSecurity::Plain,
+            username: Some("alerts".to_string()),
+            password_secret_ref: Some("smtp_password".to_string()),
+            from: "Data Dash <dash@example.com>".to_string(),
+            recipients: vec!["oncall@example.com".to_string()],
+            send_digest: true,
+            enabled: true,
+        }
+    }
+
+    fn secrets() -> Arc<dyn SecretStore> {
+        Arc::new(MemorySecretStore::new(HashMap::from([(
+            "smtp_password".to_string(),
+            "hunter2".to_string(),
+        )])))
+    }
+
+    #[tokio::test]
+    async fn test_alert_email_is_delivered() {
+        let (port, sink) = start_sink().await;
+        let notifier = EmailNotifier::new(config(port), secrets());
+        let alert = Alert {
+            check_id: "orders_daily".to_string(),
+            kind: AlertKind::Failure,
+            status: CheckStatus::Failure,
+            message: "0 rows < expected".to_string(),
+            details: None,
+            metadata: CheckMetadata::default(),
+            result_id: None,
+            raised_at: Utc::now(),
+        };
+
+        notifier.notify(&alert).await.unwrap();
+
+        let messages = sink.messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        let message = &messages[0];
+        assert!(message.contains("Subject: [FAILURE] orders_daily is failing"));
+        assert!(message.contains("Content-Type: text/plain"));
+        assert!(message.contains("Content-Type: text/html"));
+        assert_eq!(sink.auth.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_digest_goes_to_opted_in_channels() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let (port, sink) = start_sink().await;
+        db.save_email_channel(&(&config(port)).into())
+            .await
+            .unwrap();
+        let mut quiet = config(port);
+        quiet.name = "quiet".to_string();
+        quiet.send_digest = false;
+        db.save_email_channel(&(&quiet).into()).await.unwrap();
+        db.save_result("orders_daily", "Failure", "boom", None, None)
+            .await
+            .unwrap();
+
+        let channels = EmailChannels::new(db, secrets());
+        channels
+            .send_digest(&["orders_daily".to_string(), "never_run".to_string()])
+            .await
+            .unwrap();
+
+        let messages = sink.messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("2 of 2 checks need attention"));
+    }
+
+    #[test]
+    fn test_render_alert_escapes_html() {
+        let alert = Alert {
+            check_id: "orders_daily".to_string(),
+            kind: AlertKind::Recovery,
+            status: CheckStatus::Success,
+            message: "rows <ok>".to_string(),
+            details: None,
+            metadata: CheckMetadata {
+                owner: Some("data-eng".to_string()),
+                ..Default::default()
+            },
+            result_id: None,
+            raised_at: Utc::now(),
+        };
+
+        let body = render_alert(&alert);
+        assert_eq!(body.subject, "[RECOVERED] orders_daily is passing again");
+        assert!(body.text.contains("Message: rows <ok>"));
+        assert!(body.text.contains("Owner: data-eng"));
+        assert!(body.html.contains("rows &lt;ok&gt;"));
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let at = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        let morning = DateTime::parse_from_rfc3339("2024-05-01T06:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let evening = DateTime::parse_from_rfc3339("2024-05-01T20:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            next_occurrence(morning, at).to_rfc3339(),
+            "2024-05-01T08:00:00+00:00"
+        );
+        assert_eq!(
+            next_occurrence(evening, at).to_rfc3339(),
+            "2024-05-02T08:00:00+00:00"
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! Alerting on check status transitions.
+//!
+//! Every recorded result is compared with the alert state of its check. An
+//! alert is raised only when the state changes — a check that keeps failing
+//! alerts once, not on every run — and is delivered to every registered
+//! `Notifier`. Raised alerts are kept in the `alerts` table. A silenced
+//! check keeps its alert state, so its alerts are dropped until the silence
+//! ends (see `crate::maintenance`).
+
+pub mod email;
+pub mod webhook;
+
+use crate::checks::{CheckMetadata, CheckResult, CheckStatus, DataCheck};
+use crate::db::Db;
+use crate::maintenance::Maintenance;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+#[derive(Error, Debug)]
+pub enum NotifyError {
+    #[error("Delivery failed: {0}")]
+    Delivery(String),
+    #[error("Configuration error: {0}")]
+    Config(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertKind {
+    /// The check started failing or timing out.
+    Failure,
+    /// The check succeeded again after an alert.
+    Recovery,
+    /// The check returned `Warning` several runs in a row.
+    RepeatedWarning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub check_id: String,
+    pub kind: AlertKind,
+    pub status: CheckStatus,
+    pub message: String,
+    pub details: Option<Value>,
+    pub metadata: CheckMetadata,
+    pub result_id: Option<i64>,
+    pub raised_at: DateTime<Utc>,
+}
+
+/// A channel alerts are delivered to.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &str;
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError>;
+}
+
+/// Writes alerts to the application log.
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        tracing::warn!(
+            "Alert {:?} for {}: {:?} - {}",
+            alert.kind,
+            alert.check_id,
+            alert.status,
+            alert.message
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertRules {
+    /// Number of consecutive `Warning` results that raise an alert.
+    pub consecutive_warnings: u32,
+}
+
+impl Default for AlertRules {
+    fn default() -> Self {
+        Self {
+            consecutive_warnings: 3,
+        }
+    }
+}
+
+/// Alert state of a check, persisted in the `alert_state` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertState {
+    /// No open alert.
+    Ok,
+    /// A failure alert is open.
+    Failing,
+    /// A repeated-warning alert is open.
+    Warning,
+}
+
+impl FromStr for AlertState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Ok" => Ok(AlertState::Ok),
+            "Failing" => Ok(AlertState::Failing),
+            "Warning" => Ok(AlertState::Warning),
+            other => Err(format!("Unknown alert state: {}", other)),
+        }
+    }
+}
+
+/// Decide the next alert state and whether to raise an alert.
+///
+/// `warnings` is the number of consecutive warnings including `status`.
+pub fn evaluate(
+    state: AlertState,
+    status: &CheckStatus,
+    warnings: u32,
+    rules: &AlertRules,
+) -> (AlertState, Option<AlertKind>) {
+    match status {
+        CheckStatus::Failure | CheckStatus::TimedOut => match state {
+            AlertState::Failing => (AlertState::Failing, None),
+            _ => (AlertState::Failing, Some(AlertKind::Failure)),
+        },
+        CheckStatus::Success => match state {
+            AlertState::Ok => (AlertState::Ok, None),
+            _ => (AlertState::Ok, Some(AlertKind::Recovery)),
+        },
+        CheckStatus::Warning => match state {
+            AlertState::Ok if warnings >= rules.consecutive_warnings.max(1) => {
+                (AlertState::Warning, Some(AlertKind::RepeatedWarning))
+            }
+            _ => (state, None),
+        },
+        CheckStatus::Skipped | CheckStatus::Suppressed => (state, None),
+    }
+}
+
+pub struct AlertManager {
+    db: Db,
+    rules: AlertRules,
+    maintenance: Maintenance,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    /// Serializes state transitions so concurrent results for the same check
+    /// cannot both raise an alert.
+    transitions: Mutex<()>,
+}
+
+impl AlertManager {
+    pub fn new(db: Db, rules: AlertRules) -> Self {
+        Self {
+            maintenance: Maintenance::new(db.clone()),
+            db,
+            rules,
+            notifiers: Vec::new(),
+            transitions: Mutex::new(()),
+        }
+    }
+
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifiers.push(notifier);
+        self
+    }
+
+    /// Evaluate a freshly recorded result. An alert it raises is stored
+    /// right away and delivered in the background, so slow notifiers do not
+    /// hold up check execution.
+    pub async fn process(
+        self: &Arc<Self>,
+        check: &dyn DataCheck,
+        result: &CheckResult,
+        result_id: Option<i64>,
+    ) -> anyhow::Result<Option<Alert>> {
+        let kind = {
+            let _guard = self.transitions.lock().await;
+            let (state, warnings) = match self.db.get_alert_state(check.id()).await? {
+                Some((state, warnings)) => (state.parse().map_err(anyhow::Error::msg)?, warnings),
+                None => (AlertState::Ok, 0),
+            };
+            let warnings = match result.status {
+                CheckStatus::Warning => warnings + 1,
+                CheckStatus::Skipped | CheckStatus::Suppressed => warnings,
+                _ => 0,
+            };
+            let (mut next, mut kind) =
+                evaluate(state, &result.status, warnings as u32, &self.rules);
+            // A silenced transition is not taken, so that it alerts once the
+            // silence ends if the check is still in the new state.
+            if kind.is_some() {
+                if let Some(silence) = self
+                    .maintenance
+                    .active_silence(check.id(), Utc::now())
+                    .await?
+                {
+                    tracing::info!(
+                        "Alert for {} muted by silence {} ({}, until {})",
+                        check.id(),
+                        silence.id,
+                        silence.reason,
+                        silence.until
+                    );
+                    (next, kind) = (state, None);
+                }
+            }
+            self.db
+                .save_alert_state(check.id(), &format!("{:?}", next), warnings)
+                .await?;
+            kind
+        };
+
+        let Some(kind) = kind else {
+            return Ok(None);
+        };
+
+        let alert = Alert {
+            check_id: check.id().to_string(),
+            kind,
+            status: result.status.clone(),
+            message: result.message.clone(),
+            details: result.details.clone(),
+            metadata: check.metadata(),
+            result_id,
+            raised_at: Utc::now(),
+        };
+        let alert_id = self
+            .db
+            .save_alert(
+                &alert.check_id,
+                &format!("{:?}", alert.kind),
+                &format!("{:?}", alert.status),
+                &alert.message,
+                alert.result_id,
+            )
+            .await?;
+
+        let manager = self.clone();
+        let delivered = alert.clone();
+        tokio::spawn(async move {
+            let deliveries = manager.deliver(&delivered).await;
+            let deliveries = serde_json::to_value(&deliveries).unwrap_or_default();
+            if let Err(e) = manager
+                .db
+                .save_alert_deliveries(alert_id, &deliveries)
+                .await
+            {
+                tracing::error!("Failed to store deliveries of alert {}: {}", alert_id, e);
+            }
+        });
+
+        Ok(Some(alert))
+    }
+
+    /// Send an alert to every notifier. Returns the outcome per notifier.
+    async fn deliver(&self, alert: &Alert) -> BTreeMap<String, String> {
+        let mut outcomes = BTreeMap::new();
+        for notifier in &self.notifiers {
+            let outcome = match notifier.notify(alert).await {
+                Ok(()) => "delivered".to_string(),
+                Err(e) => {
+                    tracing::error!(
+                        "Notifier {} failed for {}: {}",
+                        notifier.name(),
+                        alert.check_id,
+                        e
+                    );
+                    e.to_string()
+                }
+            };
+            outcomes.insert(notifier.name().to_string(), outcome);
+        }
+        outcomes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::testing::{Behavior, ScriptedCheck};
+    use std::sync::Mutex as StdMutex;
+
+    #[derive(Default)]
+    struct RecordingNotifier {
+        alerts: StdMutex<Vec<AlertKind>>,
+    }
+
+    #[async_trait]
+    impl Notifier for RecordingNotifier {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+            self.alerts.lock().unwrap().push(alert.kind);
+            Ok(())
+        }
+    }
+
+    fn result(status: CheckStatus) -> CheckResult {
+        CheckResult {
+            status,
+            message: String::new(),
+            details: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_failures_are_deduplicated() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let notifier = Arc::new(RecordingNotifier::default());
+        let manager = Arc::new(
+            AlertManager::new(db.clone(), AlertRules::default()).with_notifier(notifier.clone()),
+        );
+        let check = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Success));
+
+        for status in [
+            CheckStatus::Failure,
+            CheckStatus::Failure,
+            CheckStatus::Failure,
+            CheckStatus::Success,
+        ] {
+            manager
+                .process(&check, &result(status), None)
+                .await
+                .unwrap();
+        }
+
+        let alerts = db.get_alerts(Some("orders"), 10).await.unwrap();
+        let kinds: Vec<_> = alerts.iter().rev().map(|a| a.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["Failure", "Recovery"]);
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(
+            *notifier.alerts.lock().unwrap(),
+            vec![AlertKind::Failure, AlertKind::Recovery]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_silenced_check_does_not_alert() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let manager = Arc::new(AlertManager::new(db.clone(), AlertRules::default()));
+        let check = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Success));
+        db.create_silence(
+            "orders",
+            Utc::now() + chrono::Duration::hours(1),
+            "vendor outage",
+            "alice",
+        )
+        .await
+        .unwrap();
+
+        let alert = manager
+            .process(&check, &result(CheckStatus::Failure), None)
+            .await
+            .unwrap();
+        assert!(alert.is_none());
+        assert!(db.get_alerts(Some("orders"), 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failure_during_silence_alerts_after_expiry() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let manager = Arc::new(AlertManager::new(db.clone(), AlertRules::default()));
+        let check = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Success));
+        db.create_silence(
+            "orders",
+            Utc::now() + chrono::Duration::milliseconds(200),
+            "vendor outage",
+            "alice",
+        )
+        .await
+        .unwrap();
+
+        let alert = manager
+            .process(&check, &result(CheckStatus::Failure), None)
+            .await
+            .unwrap();
+        assert!(alert.is_none());
+
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        let alert = manager
+            .process(&check, &result(CheckStatus::Failure), None)
+            .await
+            .unwrap();
+        assert_eq!(alert.map(|a| a.kind), Some(AlertKind::Failure));
+    }
+
+    #[test]
+    fn test_failure_alerts_once() {
+        let rules = AlertRules::default();
+        let (state, kind) = evaluate(AlertState::Ok, &CheckStatus::Failure, 0, &rules);
+        assert_eq!(
+            (state, kind),
+            (AlertState::Failing, Some(AlertKind::Failure))
+        );
+
+        let (state, kind) = evaluate(state, &CheckStatus::TimedOut, 0, &rules);
+        assert_eq!((state, kind), (AlertState::Failing, None));
+
+        let (state, kind) = evaluate(state, &CheckStatus::Success, 0, &rules);
+        assert_eq!((state, kind), (AlertState::Ok, Some(AlertKind::Recovery)));
+    }
+
+    #[test]
+    fn test_consecutive_warnings() {
+        let rules = AlertRules {
+            consecutive_warnings: 2,
+        };
+        let (state, kind) = evaluate(AlertState::Ok, &CheckStatus::Warning, 1, &rules);
+        assert_eq!((state, kind), (AlertState::Ok, None));
+
+        let (state, kind) = evaluate(state, &CheckStatus::Warning, 2, &rules);
+        assert_eq!(
+            (state, kind),
+            (AlertState::Warning, Some(AlertKind::RepeatedWarning))
+        );
+
+        let (state, kind) = evaluate(state, &CheckStatus::Warning, 3, &rules);
+        assert_eq!((state, kind), (AlertState::Warning, None));
+
+        let (_, kind) = evaluate(state, &CheckStatus::Success, 0, &rules);
+        assert_eq!(kind, Some(AlertKind::Recovery));
+    }
+
+    #[test]
+    fn test_skipped_leaves_state_alone() {
+        let rules = AlertRules::default();
+        let (state, kind) = evaluate(AlertState::Failing, &CheckStatus::Skipped, 0, &rules);
+        assert_eq!((state, kind), (AlertState::Failing, None));
+    }
+}
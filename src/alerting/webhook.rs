@@ -0,0 +1,569 @@
+//! Outgoing webhook notifier.
+//!
+//! Alerts are POSTed as a payload rendered from a template. Header values may
+//! reference secrets as `{{secret:KEY}}`, resolved through the `SecretStore`
+//! at delivery time, and the body can be signed with HMAC-SHA256. Every
+//! delivery attempt is written to the `webhook_deliveries` log.
+
+use super::{Alert, Notifier, NotifyError};
+use crate::db::{Db, WebhookRecord};
+use crate::secrets::SecretStore;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Header carrying the body signature, formatted as `sha256=<hex digest>`.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most delivery attempts a webhook may be configured with.
+pub const MAX_ATTEMPTS: u32 = 10;
+
+/// Longest delay between two delivery attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub name: String,
+    pub url: String,
+    /// Header name to value. Values may contain `{{secret:KEY}}` references.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Body template, see `render_template`. The alert serialized as JSON is
+    /// sent when unset.
+    pub payload_template: Option<String>,
+    /// Secret used to sign the body with HMAC-SHA256.
+    pub signing_secret_ref: Option<String>,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl WebhookConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_ATTEMPTS).contains(&self.max_attempts) {
+            return Err(format!(
+                "Webhook max_attempts must be between 1 and {}",
+                MAX_ATTEMPTS
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl From<WebhookRecord> for WebhookConfig {
+    fn from(r: WebhookRecord) -> Self {
+        WebhookConfig {
+            name: r.name,
+            url: r.url,
+            headers: r
+                .headers
+                .and_then(|h| serde_json::from_value(h).ok())
+                .unwrap_or_default(),
+            payload_template: r.payload_template,
+            signing_secret_ref: r.signing_secret_ref,
+            max_attempts: u32::try_from(r.max_attempts.max(1))
+                .map_or(MAX_ATTEMPTS, |n| n.min(MAX_ATTEMPTS)),
+            enabled: r.enabled,
+        }
+    }
+}
+
+impl From<&WebhookConfig> for WebhookRecord {
+    fn from(c: &WebhookConfig) -> Self {
+        WebhookRecord {
+            name: c.name.clone(),
+            url: c.url.clone(),
+            headers: serde_json::to_value(&c.headers).ok(),
+            payload_template: c.payload_template.clone(),
+            signing_secret_ref: c.signing_secret_ref.clone(),
+            max_attempts: c.max_attempts as i64,
+            enabled: c.enabled,
+        }
+    }
+}
+
+/// Render a payload template for an alert.
+///
+/// Supported placeholders: `{{check_id}}`, `{{kind}}`, `{{status}}`,
+/// `{{message}}`, `{{raised_at}}`, `{{owner}}`, `{{contact}}`, `{{severity}}`,
+/// `{{dataset}}`, `{{runbook_url}}`, `{{result_id}}` and `{{details}}`.
+/// Values are JSON-escaped, so they can be placed inside quoted strings of a
+/// JSON template; `{{details}}` is inserted as raw JSON.
+pub fn render_template(template: &str, alert: &Alert) -> String {
+    let text = |value: &str| {
+        let quoted = serde_json::to_string(value).unwrap_or_default();
+        quoted[1..quoted.len() - 1].to_string()
+    };
+    let optional = |value: &Option<String>| text(value.as_deref().unwrap_or(""));
+    let metadata = &alert.metadata;
+
+    let values = [
+        ("check_id", text(&alert.check_id)),
+        ("kind", text(&format!("{:?}", alert.kind))),
+        ("status", text(&format!("{:?}", alert.status))),
+        ("message", text(&alert.message)),
+        ("raised_at", text(&alert.raised_at.to_rfc3339())),
+        ("owner", optional(&metadata.owner)),
+        ("contact", optional(&metadata.contact)),
+        (
+            "severity",
+            text(
+                &metadata
+                    .severity
+                    .map(|s| format!("{:?}", s))
+                    .unwrap_or_default(),
+            ),
+        ),
+        ("dataset", optional(&metadata.dataset)),
+        ("runbook_url", optional(&metadata.runbook_url)),
+        (
+            "result_id",
+            alert.result_id.map(|id| id.to_string()).unwrap_or_default(),
+        ),
+        (
+            "details",
+            alert
+                .details
+                .as_ref()
+                .map(|d| d.to_string())
+                .unwrap_or_else(|| "null".to_string()),
+        ),
+    ];
+
+    // One pass over the template: substituted values are copied verbatim and
+    // never scanned again, so a message containing `{{details}}` stays as is.
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let value = placeholder.find("}}").and_then(|end| {
+            let name = &placeholder[2..end];
+            values
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, value)| (value, end + 2))
+        });
+        match value {
+            Some((value, len)) => {
+                rendered.push_str(value);
+                rest = &placeholder[len..];
+            }
+            None => {
+                rendered.push_str("{{");
+                rest = &placeholder[2..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Hex-encoded HMAC-SHA256 of `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Replace `{{secret:KEY}}` references in a header value.
+async fn resolve_secrets(value: &str, secrets: &dyn SecretStore) -> Result<String, NotifyError> {
+    let mut resolved = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("{{secret:") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let key = &rest[start + "{{secret:".len()..start + end];
+        let secret = secrets
+            .get_secret(key)
+            .await
+            .map_err(|e| NotifyError::Config(e.to_string()))?;
+        resolved.push_str(&rest[..start]);
+        resolved.push_str(&secret);
+        rest = &rest[start + end + 2..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+pub struct WebhookNotifier {
+    config: WebhookConfig,
+    secrets: Arc<dyn SecretStore>,
+    db: Db,
+    client: reqwest::Client,
+    initial_backoff: Duration,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig, secrets: Arc<dyn SecretStore>, db: Db) -> Self {
+        Self {
+            config,
+            secrets,
+            db,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("default HTTP client"),
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+
+    /// Delay before the second attempt; doubled for every further attempt,
+    /// up to a minute.
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    async fn build_request(&self, alert: &Alert) -> Result<reqwest::RequestBuilder, NotifyError> {
+        let body = match &self.config.payload_template {
+            Some(template) => render_template(template, alert),
+            None => serde_json::to_string(alert).map_err(|e| NotifyError::Config(e.to_string()))?,
+        };
+
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        for (name, value) in &self.config.headers {
+            request = request.header(name, resolve_secrets(value, self.secrets.as_ref()).await?);
+        }
+        if let Some(secret_ref) = &self.config.signing_secret_ref {
+            let secret = self
+                .secrets
+                .get_secret(secret_ref)
+                .await
+                .map_err(|e| NotifyError::Config(e.to_string()))?;
+            request = request.header(
+                SIGNATURE_HEADER,
+                format!("sha256={}", sign(&secret, body.as_bytes())),
+            );
+        }
+        Ok(request.body(body))
+    }
+
+    async fn log_attempt(
+        &self,
+        alert: &Alert,
+        attempt: u32,
+        status_code: Option<u16>,
+        error: Option<&str>,
+    ) {
+        let delivered = error.is_none();
+        if let Err(e) = self
+            .db
+            .save_webhook_delivery(
+                &self.config.name,
+                &alert.check_id,
+                &format!("{:?}", alert.kind),
+                attempt as i64,
+                status_code.map(i64::from),
+                error,
+                delivered,
+            )
+            .await
+        {
+            tracing::error!(
+                "Failed to log delivery for webhook {}: {}",
+                self.config.name,
+                e
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let max_attempts = self.config.max_attempts.clamp(1, MAX_ATTEMPTS);
+        let mut backoff = self.initial_backoff;
+        let mut last_error = String::new();
+
+        for attempt in 1..=max_attempts {
+            let request = self.build_request(alert).await?;
+            let (status_code, error, retryable) = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    (Some(response.status().as_u16()), None, false)
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    // Client errors other than rate limiting will not go away
+                    // by sending the same request again.
+                    let retryable = status.is_server_error()
+                        || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                    (
+                        Some(status.as_u16()),
+                        Some(format!("HTTP {}", status)),
+                        retryable,
+                    )
+                }
+                Err(e) => (None, Some(e.to_string()), true),
+            };
+
+            self.log_attempt(alert, attempt, status_code, error.as_deref())
+                .await;
+            let Some(error) = error else {
+                return Ok(());
+            };
+            last_error = error;
+            if !retryable {
+                break;
+            }
+            if attempt < max_attempts {
+                tokio::time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(2).min(MAX_BACKOFF);
+            }
+        }
+
+        Err(NotifyError::Delivery(last_error))
+    }
+}
+
+/// Delivers alerts to every enabled webhook stored in the database.
+pub struct WebhookChannels {
+    db: Db,
+    secrets: Arc<dyn SecretStore>,
+}
+
+impl WebhookChannels {
+    pub fn new(db: Db, secrets: Arc<dyn SecretStore>) -> Self {
+        Self { db, secrets }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookChannels {
+    fn name(&self) -> &str {
+        "webhooks"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let webhooks = self
+            .db
+            .get_webhooks()
+            .await
+            .map_err(|e| NotifyError::Config(e.to_string()))?;
+
+        let mut failed = Vec::new();
+        for config in webhooks.into_iter().map(WebhookConfig::from) {
+            if !config.enabled {
+                continue;
+            }
+            let name = config.name.clone();
+            let notifier = WebhookNotifier::new(config, self.secrets.clone(), self.db.clone());
+            if let Err(e) = notifier.notify(alert).await {
+                failed.push(format!("{}: {}", name, e));
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(NotifyError::Delivery(failed.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerting::AlertKind;
+    use crate::checks::{CheckMetadata, CheckStatus};
+    use crate::secrets::MemorySecretStore;
+    use axum::{extract::State, http::HeaderMap, http::StatusCode, routing::post, Router};
+    use chrono::Utc;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Received {
+        requests: Mutex<Vec<(HeaderMap, String)>>,
+        failures_left: Mutex<u32>,
+    }
+
+    async fn receive(
+        State(received): State<Arc<Received>>,
+        headers: HeaderMap,
+        body: String,
+    ) -> StatusCode {
+        received.requests.lock().unwrap().push((headers, body));
+        let mut failures_left = received.failures_left.lock().unwrap();
+        if *failures_left > 0 {
+            *failures_left -= 1;
+            StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    /// Start a local HTTP endpoint that records requests and answers the
+    /// first `failures` of them with 503.
+    async fn stand_in(failures: u32) -> (String, Arc<Received>) {
+        let received = Arc::new(Received::default());
+        *received.failures_left.lock().unwrap() = failures;
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, received)
+    }
+
+    fn alert() -> Alert {
+        Alert {
+            check_id: "orders_daily".to_string(),
+            kind: AlertKind::Failure,
+            status: CheckStatus::Failure,
+            message: "No rows for \"2024-01-01\"".to_string(),
+            details: Some(serde_json::json!({ "rows": 0 })),
+            metadata: CheckMetadata {
+                owner: Some("sales-data".to_string()),
+                ..Default::default()
+            },
+            result_id: Some(7),
+            raised_at: Utc::now(),
+        }
+    }
+
+    fn secrets() -> Arc<dyn SecretStore> {
+        Arc::new(MemorySecretStore::new(HashMap::from([
+            ("hook_token".to_string(), "t0ken".to_string()),
+            ("hook_signing".to_string(), "signing-key".to_string()),
+        ])))
+    }
+
+    #[test]
+    fn test_render_template_escapes_values() {
+        let rendered = render_template(
+            r#"{"text": "{{check_id}} {{status}}: {{message}}", "owner": "{{owner}}", "details": {{details}}}"#,
+            &alert(),
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(
+            parsed["text"],
+            "orders_daily Failure: No rows for \"2024-01-01\""
+        );
+        assert_eq!(parsed["owner"], "sales-data");
+        assert_eq!(parsed["details"]["rows"], 0);
+    }
+
+    #[test]
+    fn test_render_template_does_not_expand_values() {
+        let mut alert = alert();
+        alert.message = "see {{details}} and {{owner}}".to_string();
+        let rendered = render_template(
+            r#"{"text": "{{message}}", "owner": "{{owner}}", "details": {{details}}, "raw": "{{unknown}}"}"#,
+            &alert,
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(parsed["text"], "see {{details}} and {{owner}}");
+        assert_eq!(parsed["owner"], "sales-data");
+        assert_eq!(parsed["details"]["rows"], 0);
+        assert_eq!(parsed["raw"], "{{unknown}}");
+    }
+
+    #[test]
+    fn test_max_attempts_is_bounded() {
+        let record = |max_attempts| WebhookRecord {
+            name: "ops".to_string(),
+            url: "http://localhost/hook".to_string(),
+            headers: None,
+            payload_template: None,
+            signing_secret_ref: None,
+            max_attempts,
+            enabled: true,
+        };
+        for (stored, expected) in [(-5, 1), (0, 1), (4, 4), (1 << 32, MAX_ATTEMPTS)] {
+            let config = WebhookConfig::from(record(stored));
+            assert_eq!(config.max_attempts, expected);
+            assert!(config.validate().is_ok());
+        }
+
+        let mut config = WebhookConfig::from(record(3));
+        config.max_attempts = 0;
+        assert!(config.validate().is_err());
+        config.max_attempts = MAX_ATTEMPTS + 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_is_signed_and_retried() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let (url, received) = stand_in(1).await;
+        let config = WebhookConfig {
+            name: "ops".to_string(),
+            url,
+            headers: BTreeMap::from([(
+                "Authorization".to_string(),
+                "Bearer {{secret:hook_token}}".to_string(),
+            )]),
+            payload_template: Some(r#"{"check": "{{check_id}}"}"#.to_string()),
+            signing_secret_ref: Some("hook_signing".to_string()),
+            max_attempts: 3,
+            enabled: true,
+        };
+        let notifier = WebhookNotifier::new(config, secrets(), db.clone())
+            .with_initial_backoff(Duration::from_millis(1));
+
+        notifier.notify(&alert()).await.unwrap();
+
+        let requests = received.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        let (headers, body) = &requests[1];
+        assert_eq!(body, r#"{"check": "orders_daily"}"#);
+        assert_eq!(headers["authorization"], "Bearer t0ken");
+        assert_eq!(
+            headers[SIGNATURE_HEADER.to_lowercase().as_str()],
+            format!("sha256={}", sign("signing-key", body.as_bytes())).as_str()
+        );
+
+        let log = db.get_webhook_deliveries("ops", 10).await.unwrap();
+        let outcomes: Vec<_> = log.iter().rev().map(|d| (d.attempt, d.delivered)).collect();
+        assert_eq!(outcomes, vec![(1, false), (2, true)]);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_gives_up_after_max_attempts() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let (url, received) = stand_in(10).await;
+        let config = WebhookConfig {
+            name: "flaky".to_string(),
+            url,
+            headers: BTreeMap::new(),
+            payload_template: None,
+            signing_secret_ref: None,
+            max_attempts: 2,
+            enabled: true,
+        };
+        let notifier = WebhookNotifier::new(config, secrets(), db)
+            .with_initial_backoff(Duration::from_millis(1));
+
+        assert!(matches!(
+            notifier.notify(&alert()).await,
+            Err(NotifyError::Delivery(_))
+        ));
+        assert_eq!(received.requests.lock().unwrap().len(), 2);
+    }
+}
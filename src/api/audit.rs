@@ -0,0 +1,110 @@
+//! Recording API changes in the audit log, and the `/api/audit` endpoint.
+
+use super::AppState;
+use crate::audit::{Actor, AuditEntry, AuditEvent, AuditFilter, AuditLog};
+use crate::auth::Identity;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Query, State},
+    http::request::Parts,
+    Json,
+};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// The caller of a request: the authenticated user, or `anonymous` when
+/// authentication is disabled, and the peer address of the connection.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let username = parts
+            .extensions
+            .get::<Identity>()
+            .map(|identity| identity.username.clone())
+            .unwrap_or_else(|| Identity::anonymous().username);
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        Ok(Actor { username, ip })
+    }
+}
+
+/// Appends an event to the audit log. The change it describes has already
+/// been made, so a failure to record it is logged rather than returned.
+pub(super) async fn record(state: &AppState, actor: &Actor, event: AuditEvent) {
+    if let Err(e) = AuditLog::new(state.db.clone()).record(actor, event).await {
+        tracing::error!("Failed to write audit log entry: {}", e);
+    }
+}
+
+pub async fn list_audit(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<AuditFilter>,
+) -> Json<Vec<AuditEntry>> {
+    match AuditLog::new(state.db.clone()).query(&filter).await {
+        Ok(entries) => Json(entries),
+        Err(_) => Json(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::{app_router, AppState};
+    use crate::checks::registry::CheckRegistry;
+    use crate::db::Db;
+    use crate::runner::runs::RunManager;
+    use crate::runner::testing::MockContext;
+    use crate::runner::CheckRunner;
+    use serde_json::{json, Value};
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_secret_changes_are_audited_without_values() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let runner = Arc::new(CheckRunner::new(db.clone(), Arc::new(MockContext)));
+        let state = Arc::new(AppState {
+            checks: CheckRegistry::new(),
+            runs: Arc::new(RunManager::new(db.clone(), runner.clone())),
+            runner,
+            db,
+            auth: None,
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = app_router(state).into_make_service_with_connect_info::<SocketAddr>();
+            axum::serve(listener, app).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+
+        for value in ["hunter2", "correct-horse"] {
+            client
+                .post(format!("{}/api/secrets", url))
+                .json(&json!({ "key": "db_password", "value": value }))
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let response = client
+            .get(format!("{}/api/audit?resource_type=Secret", url))
+            .send()
+            .await
+            .unwrap();
+        let body = response.text().await.unwrap();
+        assert!(!body.contains("hunter2") && !body.contains("correct-horse"));
+
+        let entries: Vec<Value> = serde_json::from_str(&body).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["action"], "Update");
+        assert_eq!(entries[1]["action"], "Create");
+        assert_eq!(entries[0]["actor"], "anonymous");
+        assert_eq!(entries[0]["ip"], "127.0.0.1");
+    }
+}
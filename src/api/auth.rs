@@ -0,0 +1,799 @@
+//! Authentication and authorization middleware and the `/api/auth`
+//! endpoints.
+
+use super::AppState;
+use crate::auth::rbac::{self, Permission, Role, RoleAssignment, Target};
+use crate::auth::{
+    hash_password, ApiToken, AuthError, Authenticator, Identity, Scope, SESSION_COOKIE,
+};
+use crate::checks::registry::CheckRegistry;
+use crate::checks::DataCheck;
+use crate::connections::DataSourceBindings;
+use axum::{
+    extract::{MatchedPath, Path, Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(Err::<(), String>(message.into()))).into_response()
+}
+
+pub(super) fn internal_error(message: impl Into<String>) -> Response {
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, message)
+}
+
+pub(super) fn forbidden(permission: Permission, what: &str) -> Response {
+    error_response(
+        StatusCode::FORBIDDEN,
+        format!("Missing {} permission for {}", permission, what),
+    )
+}
+
+/// Permission checks on individual checks and data sources, for handlers
+/// of routes that accept scoped grants.
+pub(super) struct Access {
+    identity: Identity,
+    bindings: DataSourceBindings,
+}
+
+impl Access {
+    pub(super) async fn load(state: &AppState, identity: Identity) -> Self {
+        let bindings = DataSourceBindings::load(&state.db)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to load data sources for access checks: {}", e);
+                DataSourceBindings::default()
+            });
+        Self { identity, bindings }
+    }
+
+    pub(super) fn allows(&self, permission: Permission, check: &dyn DataCheck) -> bool {
+        let tags = check.metadata().tags;
+        let data_sources = self.bindings.data_sources_of(check);
+        self.identity.can(
+            permission,
+            &Target::Check {
+                tags: &tags,
+                data_sources: &data_sources,
+            },
+        )
+    }
+
+    /// Like `allows`, by check id. Results of checks that are no longer
+    /// registered are only covered by global grants.
+    pub(super) fn allows_id(
+        &self,
+        checks: &CheckRegistry,
+        permission: Permission,
+        check_id: &str,
+    ) -> bool {
+        match checks.get(check_id) {
+            Some(check) => self.allows(permission, check.as_ref()),
+            None => self.identity.can(permission, &Target::Global),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub(super) fn require_id(
+        &self,
+        checks: &CheckRegistry,
+        permission: Permission,
+        check_id: &str,
+    ) -> Result<(), Response> {
+        if self.allows_id(checks, permission, check_id) {
+            Ok(())
+        } else {
+            Err(forbidden(permission, &format!("check {}", check_id)))
+        }
+    }
+
+    pub(super) fn allows_data_source(&self, permission: Permission, name: &str) -> bool {
+        self.identity.can(permission, &Target::DataSource(name))
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+fn session_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|c| c.trim().strip_prefix(SESSION_COOKIE)?.strip_prefix('='))
+}
+
+async fn identify(
+    auth: &Authenticator,
+    headers: &HeaderMap,
+) -> Result<Option<Identity>, AuthError> {
+    if let Some(token) = bearer_token(headers) {
+        return auth.token_identity(token).await;
+    }
+    match session_token(headers) {
+        Some(token) => auth.session_identity(token).await,
+        None => Ok(None),
+    }
+}
+
+/// Resolve the caller of every API request and store its `Identity` in the
+/// request extensions. Read-only tokens may only make `GET` requests, and
+/// the caller must hold the permission `rbac::route_requirement` lists for
+/// the route.
+pub async fn require_auth(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let identity = match &state.auth {
+        None => Identity::anonymous(),
+        Some(auth) => match identify(auth, request.headers()).await {
+            Ok(Some(identity)) => identity,
+            Ok(None) => return error_response(StatusCode::UNAUTHORIZED, "Authentication required"),
+            Err(e) => {
+                tracing::error!("Failed to authenticate request: {}", e);
+                return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+            }
+        },
+    };
+
+    let required = match *request.method() {
+        Method::GET | Method::HEAD => Scope::Read,
+        _ => Scope::Write,
+    };
+    if !identity.has_scope(required) {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            format!("Token does not have the {:?} scope", required),
+        );
+    }
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+    if let Some(requirement) = rbac::route_requirement(request.method().as_str(), &route) {
+        let allowed = if requirement.scoped {
+            identity.holds(requirement.permission)
+        } else {
+            identity.can(requirement.permission, &Target::Global)
+        };
+        if !allowed {
+            return forbidden(requirement.permission, &route);
+        }
+    }
+
+    request.extensions_mut().insert(identity);
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+/// `Set-Cookie` value for the session cookie; an empty token with no
+/// lifetime clears it.
+fn session_cookie(auth: Option<&Authenticator>, token: &str, max_age: i64) -> String {
+    let secure = if auth.is_some_and(Authenticator::secure_cookie) {
+        "; Secure"
+    } else {
+        ""
+    };
+    format!(
+        "{}={}; HttpOnly; SameSite=Strict; Path=/; Max-Age={}{}",
+        SESSION_COOKIE, token, max_age, secure
+    )
+}
+
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<LoginRequest>,
+) -> Response {
+    let Some(auth) = &state.auth else {
+        return error_response(StatusCode::BAD_REQUEST, "Authentication is disabled");
+    };
+    match auth.login(&request.username, &request.password).await {
+        Ok(session) => {
+            let max_age = (session.expires_at - Utc::now()).num_seconds().max(0);
+            let cookie = session_cookie(Some(auth), &session.token, max_age);
+            (
+                [(header::SET_COOKIE, cookie)],
+                Json(Ok::<_, String>(session)),
+            )
+                .into_response()
+        }
+        Err(AuthError::InvalidCredentials) => error_response(
+            StatusCode::UNAUTHORIZED,
+            AuthError::InvalidCredentials.to_string(),
+        ),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub async fn logout(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if let (Some(auth), Some(token)) = (&state.auth, session_token(&headers)) {
+        if let Err(e) = auth.logout(token).await {
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        }
+    }
+    let cookie = session_cookie(state.auth.as_deref(), "", 0);
+    ([(header::SET_COOKIE, cookie)], Json(Ok::<(), String>(()))).into_response()
+}
+
+pub async fn me(Extension(identity): Extension<Identity>) -> Json<Identity> {
+    Json(identity)
+}
+
+#[derive(Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<Scope>,
+    /// Lifetime of the token; tokens without one do not expire.
+    #[serde(default)]
+    pub expires_in_days: Option<u32>,
+}
+
+fn default_scopes() -> Vec<Scope> {
+    vec![Scope::Read]
+}
+
+#[derive(Serialize)]
+pub struct CreatedToken {
+    /// The token itself. It is not stored and cannot be shown again.
+    pub token: String,
+    #[serde(flatten)]
+    pub info: ApiToken,
+}
+
+pub async fn create_token(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(request): Json<CreateTokenRequest>,
+) -> Json<Result<CreatedToken, String>> {
+    let Some(auth) = &state.auth else {
+        return Json(Err("Authentication is disabled".to_string()));
+    };
+    // A token cannot grant more than the credentials used to create it.
+    if let Some(scope) = request.scopes.iter().find(|s| !identity.has_scope(**s)) {
+        return Json(Err(format!("Cannot grant the {:?} scope", scope)));
+    }
+    let expires_at = request
+        .expires_in_days
+        .map(|days| Utc::now() + ChronoDuration::days(days as i64));
+
+    match auth
+        .create_token(
+            &identity.username,
+            &request.name,
+            request.scopes,
+            expires_at,
+        )
+        .await
+    {
+        Ok((token, info)) => Json(Ok(CreatedToken { token, info })),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+pub async fn list_tokens(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+) -> Json<Vec<ApiToken>> {
+    match state.db.get_api_tokens(&identity.username).await {
+        Ok(tokens) => Json(tokens.into_iter().map(ApiToken::from).collect()),
+        Err(_) => Json(vec![]),
+    }
+}
+
+pub async fn revoke_token(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+) -> Json<Result<(), String>> {
+    match state.db.delete_api_token(&id, &identity.username).await {
+        Ok(true) => Json(Ok(())),
+        Ok(false) => Json(Err(format!("Token not found: {}", id))),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+#[derive(Serialize)]
+pub struct UserInfo {
+    pub username: String,
+    pub disabled: bool,
+    pub created_at: String,
+    pub roles: Vec<RoleAssignment>,
+}
+
+#[derive(Deserialize)]
+pub struct SaveUserRequest {
+    pub username: String,
+    /// Sets the password of a new user or resets that of an existing one.
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetUserDisabledRequest {
+    pub disabled: bool,
+}
+
+fn parse_assignments(rows: Vec<(String, serde_json::Value)>) -> Vec<RoleAssignment> {
+    rows.into_iter()
+        .map(|(role, scope)| RoleAssignment {
+            role,
+            scope: serde_json::from_value(scope).unwrap_or_default(),
+        })
+        .collect()
+}
+
+pub async fn list_users(State(state): State<Arc<AppState>>) -> Json<Vec<UserInfo>> {
+    let Ok(users) = state.db.get_users().await else {
+        return Json(vec![]);
+    };
+    let mut result = Vec::with_capacity(users.len());
+    for (username, disabled, created_at) in users {
+        let roles = state
+            .db
+            .get_user_roles(&username)
+            .await
+            .map(parse_assignments)
+            .unwrap_or_default();
+        result.push(UserInfo {
+            username,
+            disabled,
+            created_at: created_at.to_rfc3339(),
+            roles,
+        });
+    }
+    Json(result)
+}
+
+pub async fn save_user(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SaveUserRequest>,
+) -> Json<Result<(), String>> {
+    if request.username.trim().is_empty() || request.password.is_empty() {
+        return Json(Err("Username and password are required".to_string()));
+    }
+    let hash = match hash_password(&request.password) {
+        Ok(hash) => hash,
+        Err(e) => return Json(Err(e.to_string())),
+    };
+    match state.db.save_user(&request.username, &hash).await {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+/// Disabling a user also ends their sessions and revokes their API tokens.
+pub async fn set_user_disabled(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    Json(request): Json<SetUserDisabledRequest>,
+) -> Json<Result<(), String>> {
+    match state
+        .db
+        .set_user_disabled(&username, request.disabled)
+        .await
+    {
+        Ok(true) => Json(Ok(())),
+        Ok(false) => Json(Err(format!("User not found: {}", username))),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+pub async fn set_user_roles(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    Json(assignments): Json<Vec<RoleAssignment>>,
+) -> Json<Result<(), String>> {
+    match state.db.get_user(&username).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Json(Err(format!("User not found: {}", username))),
+        Err(e) => return Json(Err(e.to_string())),
+    }
+    let known: Vec<String> = match state.db.get_roles().await {
+        Ok(roles) => roles.into_iter().map(|(name, _, _)| name).collect(),
+        Err(e) => return Json(Err(e.to_string())),
+    };
+    if let Some(unknown) = assignments.iter().find(|a| !known.contains(&a.role)) {
+        return Json(Err(format!("Unknown role: {}", unknown.role)));
+    }
+
+    let rows: Vec<(String, serde_json::Value)> = assignments
+        .into_iter()
+        .map(|a| (a.role, serde_json::json!(a.scope)))
+        .collect();
+    match state.db.set_user_roles(&username, &rows).await {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+pub async fn list_roles(State(state): State<Arc<AppState>>) -> Json<Vec<Role>> {
+    match state.db.get_roles().await {
+        Ok(roles) => Json(
+            roles
+                .into_iter()
+                .map(|(name, description, permissions)| Role {
+                    name,
+                    description,
+                    permissions: serde_json::from_value(permissions).unwrap_or_default(),
+                })
+                .collect(),
+        ),
+        Err(_) => Json(vec![]),
+    }
+}
+
+pub async fn save_role(
+    State(state): State<Arc<AppState>>,
+    Json(role): Json<Role>,
+) -> Json<Result<(), String>> {
+    match state
+        .db
+        .save_role(
+            &role.name,
+            role.description.as_deref(),
+            &serde_json::json!(role.permissions),
+        )
+        .await
+    {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+pub async fn delete_role(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Json<Result<(), String>> {
+    match state.db.delete_role(&name).await {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::app_router;
+    use crate::auth::LocalIdentityBackend;
+    use crate::checks::registry::CheckRegistry;
+    use crate::checks::CheckStatus;
+    use crate::db::Db;
+    use crate::runner::runs::RunManager;
+    use crate::runner::testing::{Behavior, MockContext, ScriptedCheck};
+    use crate::runner::CheckRunner;
+    use reqwest::StatusCode as HttpStatus;
+    use tokio::net::TcpListener;
+
+    async fn serve(auth_enabled: bool) -> (String, Db) {
+        serve_checks(auth_enabled, CheckRegistry::new()).await
+    }
+
+    async fn serve_checks(auth_enabled: bool, checks: CheckRegistry) -> (String, Db) {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        db.save_user("alice", &hash_password("secret").unwrap())
+            .await
+            .unwrap();
+        db.set_user_roles("alice", &[("analyst".to_string(), serde_json::json!({}))])
+            .await
+            .unwrap();
+        let runner = Arc::new(CheckRunner::new(db.clone(), Arc::new(MockContext)));
+        let auth = auth_enabled.then(|| {
+            let backend = Arc::new(LocalIdentityBackend::new(db.clone()));
+            Arc::new(Authenticator::new(db.clone(), backend))
+        });
+        let state = Arc::new(AppState {
+            checks,
+            runs: Arc::new(RunManager::new(db.clone(), runner.clone())),
+            runner,
+            db: db.clone(),
+            auth,
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app_router(state)).await.unwrap();
+        });
+        (url, db)
+    }
+
+    #[tokio::test]
+    async fn test_session_login() {
+        let (url, _) = serve(true).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/api/checks", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::UNAUTHORIZED);
+
+        let response = client
+            .post(format!("{}/api/auth/login", url))
+            .json(&serde_json::json!({ "username": "alice", "password": "nope" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::UNAUTHORIZED);
+
+        let response = client
+            .post(format!("{}/api/auth/login", url))
+            .json(&serde_json::json!({ "username": "alice", "password": "secret" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::OK);
+        let cookie = response.headers()[header::SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .split(';')
+            .next()
+            .unwrap()
+            .to_string();
+
+        let me: serde_json::Value = client
+            .get(format!("{}/api/auth/me", url))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(me["username"], "alice");
+        assert_eq!(me["method"], "Session");
+    }
+
+    #[tokio::test]
+    async fn test_session_cookie_is_secure_off_loopback() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let auth = Authenticator::new(db.clone(), Arc::new(LocalIdentityBackend::new(db)));
+        assert!(!session_cookie(Some(&auth), "dds_1", 60).contains("Secure"));
+
+        let auth = auth.with_secure_cookie(true);
+        assert_eq!(
+            session_cookie(Some(&auth), "dds_1", 60),
+            "dd_session=dds_1; HttpOnly; SameSite=Strict; Path=/; Max-Age=60; Secure"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_only_token_cannot_write() {
+        let (url, db) = serve(true).await;
+        let auth = Authenticator::new(db.clone(), Arc::new(LocalIdentityBackend::new(db)));
+        let (token, _) = auth
+            .create_token("alice", "dashboard", vec![Scope::Read], None)
+            .await
+            .unwrap();
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/api/checks", url))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::OK);
+
+        let response = client
+            .post(format!("{}/api/checks/run-all", url))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_analyst_cannot_manage_secrets() {
+        let (url, db) = serve(true).await;
+        let auth = Authenticator::new(db.clone(), Arc::new(LocalIdentityBackend::new(db)));
+        let (token, _) = auth
+            .create_token("alice", "cli", vec![Scope::Read, Scope::Write], None)
+            .await
+            .unwrap();
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{}/api/checks/run-all", url))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), HttpStatus::OK);
+
+        for path in ["/api/secrets", "/api/roles"] {
+            let response = client
+                .get(format!("{}{}", url, path))
+                .bearer_auth(&token)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), HttpStatus::FORBIDDEN, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_annotation_author_is_caller() {
+        let (url, db) = serve(true).await;
+        let auth = Authenticator::new(db.clone(), Arc::new(LocalIdentityBackend::new(db.clone())));
+        let (token, _) = auth
+            .create_token("alice", "cli", vec![Scope::Read, Scope::Write], None)
+            .await
+            .unwrap();
+        let result_id = db
+            .save_result("orders", "Failure", "late", None, None)
+            .await
+            .unwrap();
+
+        let response: serde_json::Value = reqwest::Client::new()
+            .post(format!("{}/api/results/{}/annotations", url, result_id))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "author": "mallory", "text": "expected" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(response["Ok"].is_i64(), "{}", response);
+        assert_eq!(
+            db.get_annotations(result_id).await.unwrap()[0].author,
+            "alice"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history_is_filled_with_visible_results() {
+        let mut checks = CheckRegistry::new();
+        for (id, tag) in [("orders", "finance"), ("clicks", "marketing")] {
+            let check =
+                ScriptedCheck::new(id, Behavior::Return(CheckStatus::Success)).with_tags(&[tag]);
+            checks.register(Arc::new(check)).unwrap();
+        }
+        let (url, db) = serve_checks(true, checks).await;
+        db.set_user_roles(
+            "alice",
+            &[(
+                "analyst".to_string(),
+                serde_json::json!({ "tags": ["finance"] }),
+            )],
+        )
+        .await
+        .unwrap();
+        let auth = Authenticator::new(db.clone(), Arc::new(LocalIdentityBackend::new(db.clone())));
+        let (token, _) = auth
+            .create_token("alice", "cli", vec![Scope::Read], None)
+            .await
+            .unwrap();
+        let orders = db
+            .save_result("orders", "Success", "ok", None, None)
+            .await
+            .unwrap();
+        // More newer results of a check alice cannot view than fit in a page.
+        for _ in 0..250 {
+            db.save_result("clicks", "Success", "ok", None, None)
+                .await
+                .unwrap();
+        }
+
+        let history: serde_json::Value = reqwest::Client::new()
+            .get(format!("{}/api/history", url))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let ids: Vec<_> = history
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(ids, vec![orders]);
+    }
+
+    #[tokio::test]
+    async fn test_only_failing_checks_can_be_acknowledged() {
+        let mut checks = CheckRegistry::new();
+        let check = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Success));
+        checks.register(Arc::new(check)).unwrap();
+        let (url, db) = serve_checks(false, checks).await;
+        let client = reqwest::Client::new();
+
+        for (status, accepted) in [
+            ("Success", false),
+            ("Warning", false),
+            ("Skipped", false),
+            ("Suppressed", false),
+            ("Failure", true),
+            ("TimedOut", true),
+        ] {
+            db.save_result("orders", status, "", None, None)
+                .await
+                .unwrap();
+            let response: serde_json::Value = client
+                .post(format!("{}/api/checks/orders/acknowledge", url))
+                .json(&serde_json::json!({ "author": "alice" }))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            assert_eq!(
+                response.get("Ok").is_some(),
+                accepted,
+                "{}: {}",
+                status,
+                response
+            );
+            db.clear_acknowledgement("orders").await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disable_user_keeps_password() {
+        let (url, db) = serve(false).await;
+        let (hash, _) = db.get_user("alice").await.unwrap().unwrap();
+        let client = reqwest::Client::new();
+
+        let response: serde_json::Value = client
+            .put(format!("{}/api/users/alice/disabled", url))
+            .json(&serde_json::json!({ "disabled": true }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response, serde_json::json!({ "Ok": null }));
+        assert_eq!(db.get_user("alice").await.unwrap(), Some((hash, true)));
+
+        let response: serde_json::Value = client
+            .put(format!("{}/api/users/bob/disabled", url))
+            .json(&serde_json::json!({ "disabled": true }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(response["Err"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_auth_allows_anonymous() {
+        let (url, _) = serve(false).await;
+        let me: serde_json::Value = reqwest::get(format!("{}/api/auth/me", url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(me["method"], "Anonymous");
+    }
+}
@@ -0,0 +1,337 @@
+//! Environments and the per-environment overrides of connection profiles and
+//! data sources.
+
+use super::auth::Access;
+use super::{audit, require_data_source, AppState};
+use crate::audit::{Actor, AuditAction, AuditEvent, ResourceType};
+use crate::auth::Identity;
+use crate::environments::{ConnectionOverride, DataSourceOverride, Environment};
+use axum::{
+    extract::{Path, State},
+    response::Response,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct ConnectionOverrideRequest {
+    #[serde(default)]
+    pub driver: Option<String>,
+    #[serde(default)]
+    pub connection_string_template: Option<String>,
+    #[serde(default)]
+    pub secret_ref: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DataSourceOverrideRequest {
+    #[serde(default)]
+    pub connection_name: Option<String>,
+    #[serde(default)]
+    pub secret_key: Option<String>,
+}
+
+/// Everything an environment overrides.
+#[derive(Serialize)]
+pub struct EnvironmentOverrides {
+    pub connections: Vec<ConnectionOverride>,
+    pub data_sources: Vec<DataSourceOverride>,
+}
+
+pub async fn list_environments(State(state): State<Arc<AppState>>) -> Json<Vec<Environment>> {
+    match state.db.get_environments().await {
+        Ok(environments) => Json(
+            environments
+                .into_iter()
+                .map(|(name, description)| Environment { name, description })
+                .collect(),
+        ),
+        Err(_) => Json(vec![]),
+    }
+}
+
+async fn environment_snapshot(state: &AppState, name: &str) -> Option<Value> {
+    let environments = state.db.get_environments().await.ok()?;
+    environments
+        .into_iter()
+        .find(|(n, _)| n == name)
+        .map(|(name, description)| json!(Environment { name, description }))
+}
+
+pub async fn save_environment(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Json(environment): Json<Environment>,
+) -> Json<Result<(), String>> {
+    if environment.name.trim().is_empty() {
+        return Json(Err("Environment name must not be empty".to_string()));
+    }
+    let before = environment_snapshot(&state, &environment.name).await;
+    match state
+        .db
+        .save_environment(&environment.name, environment.description.as_deref())
+        .await
+    {
+        Ok(_) => {
+            let event = AuditEvent::saved(ResourceType::Environment, &environment.name, before)
+                .after(Some(json!(environment)));
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+/// Deletes an environment and its overrides. Past results keep their
+/// environment tag.
+pub async fn delete_environment(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(name): Path<String>,
+) -> Json<Result<(), String>> {
+    let before = environment_snapshot(&state, &name).await;
+    match state.db.delete_environment(&name).await {
+        Ok(_) => {
+            let event = AuditEvent::new(AuditAction::Delete, ResourceType::Environment, &name)
+                .before(before);
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+pub async fn list_overrides(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Json<Result<EnvironmentOverrides, String>> {
+    let connections = match state.db.get_connection_overrides(Some(&name)).await {
+        Ok(records) => records.into_iter().map(ConnectionOverride::from).collect(),
+        Err(e) => return Json(Err(e.to_string())),
+    };
+    let data_sources = match state.db.get_data_source_overrides(Some(&name)).await {
+        Ok(records) => records.into_iter().map(DataSourceOverride::from).collect(),
+        Err(e) => return Json(Err(e.to_string())),
+    };
+    Json(Ok(EnvironmentOverrides {
+        connections,
+        data_sources,
+    }))
+}
+
+async fn connection_override_snapshot(state: &AppState, name: &str, env: &str) -> Option<Value> {
+    let overrides = state.db.get_connection_overrides(Some(env)).await.ok()?;
+    overrides
+        .into_iter()
+        .find(|o| o.profile_name == name)
+        .map(|o| json!(ConnectionOverride::from(o)))
+}
+
+/// Overrides a connection profile in one environment. Overrides are audited
+/// as changes to the profile they apply to.
+pub async fn save_connection_override(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path((name, env)): Path<(String, String)>,
+    Json(req): Json<ConnectionOverrideRequest>,
+) -> Json<Result<(), String>> {
+    let before = connection_override_snapshot(&state, &name, &env).await;
+    let o = ConnectionOverride {
+        profile_name: name,
+        environment: env,
+        driver: req.driver,
+        connection_string_template: req.connection_string_template,
+        secret_ref: req.secret_ref,
+    };
+    match state.db.save_connection_override(&(&o).into()).await {
+        Ok(_) => {
+            let event = AuditEvent::new(
+                AuditAction::Update,
+                ResourceType::Connection,
+                &o.profile_name,
+            )
+            .before(before)
+            .after(Some(json!(o)));
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+pub async fn delete_connection_override(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path((name, env)): Path<(String, String)>,
+) -> Json<Result<(), String>> {
+    let before = connection_override_snapshot(&state, &name, &env).await;
+    match state.db.delete_connection_override(&name, &env).await {
+        Ok(true) => {
+            let event = AuditEvent::new(AuditAction::Update, ResourceType::Connection, &name)
+                .before(before);
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
+        Ok(false) => Json(Err(format!(
+            "Connection {} has no override in environment {}",
+            name, env
+        ))),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+async fn data_source_override_snapshot(state: &AppState, name: &str, env: &str) -> Option<Value> {
+    let overrides = state.db.get_data_source_overrides(Some(env)).await.ok()?;
+    overrides
+        .into_iter()
+        .find(|o| o.data_source == name)
+        .map(|o| json!(DataSourceOverride::from(o)))
+}
+
+pub async fn save_data_source_override(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    actor: Actor,
+    Path((name, env)): Path<(String, String)>,
+    Json(req): Json<DataSourceOverrideRequest>,
+) -> Result<Json<Result<(), String>>, Response> {
+    require_data_source(&Access::load(&state, identity).await, &name)?;
+    let before = data_source_override_snapshot(&state, &name, &env).await;
+    let o = DataSourceOverride {
+        data_source: name,
+        environment: env,
+        connection_name: req.connection_name,
+        secret_key: req.secret_key,
+    };
+    Ok(
+        match state.db.save_data_source_override(&(&o).into()).await {
+            Ok(_) => {
+                let event = AuditEvent::new(
+                    AuditAction::Update,
+                    ResourceType::DataSource,
+                    &o.data_source,
+                )
+                .before(before)
+                .after(Some(json!(o)));
+                audit::record(&state, &actor, event).await;
+                Json(Ok(()))
+            }
+            Err(e) => Json(Err(e.to_string())),
+        },
+    )
+}
+
+pub async fn delete_data_source_override(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    actor: Actor,
+    Path((name, env)): Path<(String, String)>,
+) -> Result<Json<Result<(), String>>, Response> {
+    require_data_source(&Access::load(&state, identity).await, &name)?;
+    let before = data_source_override_snapshot(&state, &name, &env).await;
+    Ok(
+        match state.db.delete_data_source_override(&name, &env).await {
+            Ok(true) => {
+                let event = AuditEvent::new(AuditAction::Update, ResourceType::DataSource, &name)
+                    .before(before);
+                audit::record(&state, &actor, event).await;
+                Json(Ok(()))
+            }
+            Ok(false) => Json(Err(format!(
+                "Data source {} has no override in environment {}",
+                name, env
+            ))),
+            Err(e) => Json(Err(e.to_string())),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::{app_router, AppState};
+    use crate::checks::registry::CheckRegistry;
+    use crate::db::Db;
+    use crate::runner::runs::RunManager;
+    use crate::runner::testing::MockContext;
+    use crate::runner::CheckRunner;
+    use serde_json::{json, Value};
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_connection_overrides() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        db.save_connection_profile("warehouse", "Postgres", "host=dev", None, None, None)
+            .await
+            .unwrap();
+        let runner = Arc::new(CheckRunner::new(db.clone(), Arc::new(MockContext)));
+        let state = Arc::new(AppState {
+            checks: CheckRegistry::new(),
+            runs: Arc::new(RunManager::new(db.clone(), runner.clone())),
+            runner,
+            db,
+            auth: None,
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = app_router(state).into_make_service_with_connect_info::<SocketAddr>();
+            axum::serve(listener, app).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+        let put_override = || {
+            client
+                .put(format!(
+                    "{}/api/connections/warehouse/environments/prod",
+                    url
+                ))
+                .json(&json!({ "connection_string_template": "host=prod" }))
+                .send()
+        };
+
+        // Overrides need an existing environment.
+        let body: Value = put_override().await.unwrap().json().await.unwrap();
+        assert!(body.get("Err").is_some());
+
+        client
+            .post(format!("{}/api/environments", url))
+            .json(&json!({ "name": "prod" }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = put_override().await.unwrap().json().await.unwrap();
+        assert_eq!(body, json!({ "Ok": null }));
+
+        let overrides: Value = client
+            .get(format!("{}/api/environments/prod/overrides", url))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let connections = overrides["Ok"]["connections"].as_array().unwrap();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0]["connection_string_template"], "host=prod");
+        assert_eq!(connections[0]["driver"], Value::Null);
+
+        // Deleting the environment drops its overrides.
+        client
+            .delete(format!("{}/api/environments/prod", url))
+            .send()
+            .await
+            .unwrap();
+        let environments: Value = client
+            .get(format!("{}/api/environments", url))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(environments, json!([]));
+    }
+}
@@ -1,25 +1,77 @@
-use crate::checks::{CheckContext, CheckResult, DataCheck};
-use crate::connections::ConnectionProfile;
+pub mod audit;
+pub mod auth;
+pub mod environments;
+
+use crate::alerting::email::EmailConfig;
+use crate::alerting::webhook::WebhookConfig;
+use crate::audit::{Actor, AuditAction, AuditEvent, ResourceType, REDACTED};
+use crate::auth::rbac::Permission;
+use crate::auth::{AuthMethod, Authenticator, Identity};
+use crate::checks::registry::CheckRegistry;
+use crate::checks::{CheckMetadata, CheckResult, DataCheck, Severity};
+use crate::connections::{ConnectionProfile, DataSourceBindings};
 use crate::db::Db;
+use crate::maintenance::{MaintenanceWindow, Silence};
+use crate::runner::batch::BatchSummary;
+use crate::runner::runs::{Run, RunManager};
+use crate::runner::{CheckRunner, RunOptions};
+use auth::Access;
 use axum::{
-    extract::{Path, State},
-    routing::{get, post},
-    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Extension, Json, Router,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub struct AppState {
-    pub checks: HashMap<String, Arc<dyn DataCheck>>,
-    pub check_context: Arc<dyn CheckContext>,
+    pub checks: CheckRegistry,
+    pub runner: Arc<CheckRunner>,
+    pub runs: Arc<RunManager>,
     pub db: Db,
+    /// `None` when authentication is disabled; every request is then made
+    /// by `Identity::anonymous()`.
+    pub auth: Option<Arc<Authenticator>>,
 }
 
 #[derive(Deserialize)]
 pub struct ExecuteRequest {
     pub params: HashMap<String, Value>,
+    /// Queue the check in the background and return a run id instead of
+    /// waiting for the result.
+    #[serde(default, rename = "async")]
+    pub run_async: bool,
+    /// Overrides the check's default timeout for this run.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Run against this environment instead of the server's default.
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct BatchRequest {
+    /// Parameters passed to every check in the batch.
+    #[serde(default)]
+    pub params: HashMap<String, Value>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum ExecuteResponse {
+    Completed(CheckResult),
+    Queued { run_id: String },
 }
 
 #[derive(Deserialize)]
@@ -38,9 +90,47 @@ pub struct UnixGroupRequest {
 pub fn app_router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/api/checks", get(list_checks))
+        .route("/api/checks/run-all", post(run_all_checks))
         .route("/api/checks/:id/execute", post(execute_check))
+        .route("/api/groups/:group/run", post(run_group))
+        .route("/api/runs/:id", get(get_run).delete(cancel_run))
+        .route("/api/alerts", get(list_alerts))
+        .route("/api/webhooks", get(list_webhooks).post(save_webhook))
+        .route("/api/webhooks/:name", axum::routing::delete(delete_webhook))
+        .route(
+            "/api/webhooks/:name/deliveries",
+            get(list_webhook_deliveries),
+        )
+        .route(
+            "/api/email-channels",
+            get(list_email_channels).post(save_email_channel),
+        )
+        .route(
+            "/api/email-channels/:name",
+            axum::routing::delete(delete_email_channel),
+        )
+        .route(
+            "/api/maintenance-windows",
+            get(list_maintenance_windows).post(create_maintenance_window),
+        )
+        .route(
+            "/api/maintenance-windows/:id",
+            axum::routing::delete(delete_maintenance_window),
+        )
+        .route("/api/checks/:id/silence", post(silence_check))
+        .route("/api/silences", get(list_silences))
+        .route("/api/silences/:id", axum::routing::delete(delete_silence))
         .route("/api/check-statuses", get(get_check_statuses))
         .route("/api/history", get(get_history))
+        .route("/api/results/:id/attempts", get(get_attempts))
+        .route(
+            "/api/results/:id/annotations",
+            get(list_annotations).post(add_annotation),
+        )
+        .route(
+            "/api/checks/:id/acknowledge",
+            post(acknowledge_check).delete(unacknowledge_check),
+        )
         .route(
             "/api/connections",
             get(list_connections).post(save_connection),
@@ -67,17 +157,103 @@ pub fn app_router(state: Arc<AppState>) -> Router {
             "/api/unix-groups/:name",
             axum::routing::delete(delete_unix_group),
         )
+        .route("/api/auth/me", get(auth::me))
+        .route("/api/auth/logout", post(auth::logout))
+        .route(
+            "/api/auth/tokens",
+            get(auth::list_tokens).post(auth::create_token),
+        )
+        .route(
+            "/api/auth/tokens/:id",
+            axum::routing::delete(auth::revoke_token),
+        )
+        .route("/api/audit", get(audit::list_audit))
+        .route("/api/users", get(auth::list_users).post(auth::save_user))
+        .route("/api/users/:username/roles", put(auth::set_user_roles))
+        .route(
+            "/api/users/:username/disabled",
+            put(auth::set_user_disabled),
+        )
+        .route("/api/roles", get(auth::list_roles).post(auth::save_role))
+        .route("/api/roles/:name", axum::routing::delete(auth::delete_role))
+        .route(
+            "/api/environments",
+            get(environments::list_environments).post(environments::save_environment),
+        )
+        .route(
+            "/api/environments/:name",
+            axum::routing::delete(environments::delete_environment),
+        )
+        .route(
+            "/api/environments/:name/overrides",
+            get(environments::list_overrides),
+        )
+        .route(
+            "/api/connections/:name/environments/:env",
+            put(environments::save_connection_override)
+                .delete(environments::delete_connection_override),
+        )
+        .route(
+            "/api/data-sources/:name/environments/:env",
+            put(environments::save_data_source_override)
+                .delete(environments::delete_data_source_override),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_auth,
+        ))
+        .route("/api/auth/login", post(auth::login))
         .with_state(state)
 }
 
-async fn list_checks(State(state): State<Arc<AppState>>) -> Json<Vec<CheckSummary>> {
+/// Query filters for `GET /api/checks`. All given filters must match.
+#[derive(Deserialize, Default)]
+pub struct CheckFilter {
+    pub tag: Option<String>,
+    pub group: Option<String>,
+    pub owner: Option<String>,
+    pub severity: Option<Severity>,
+    pub dataset: Option<String>,
+}
+
+impl CheckFilter {
+    pub fn matches(&self, metadata: &CheckMetadata) -> bool {
+        self.tag.iter().all(|t| metadata.tags.contains(t))
+            && self.group.iter().all(|g| metadata.groups.contains(g))
+            && self
+                .owner
+                .iter()
+                .all(|o| metadata.owner.as_ref() == Some(o))
+            && self.severity.iter().all(|s| metadata.severity == Some(*s))
+            && self
+                .dataset
+                .iter()
+                .all(|d| metadata.dataset.as_ref() == Some(d))
+    }
+}
+
+async fn list_checks(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Query(filter): Query<CheckFilter>,
+) -> Json<Vec<CheckSummary>> {
+    let access = Access::load(&state, identity).await;
+    let bindings = DataSourceBindings::load(&state.db)
+        .await
+        .unwrap_or_default();
     let summaries = state
         .checks
         .values()
-        .map(|c| CheckSummary {
+        .filter(|c| access.allows(Permission::View, c.as_ref()))
+        .map(|c| (c, c.metadata()))
+        .filter(|(_, metadata)| filter.matches(metadata))
+        .map(|(c, metadata)| CheckSummary {
             id: c.id().to_string(),
             description: c.description().to_string(),
             parameters: c.parameters(),
+            dependencies: c.dependencies(),
+            data_sources: bindings.data_sources_of(c.as_ref()),
+            metadata,
         })
         .collect();
     Json(summaries)
@@ -85,60 +261,675 @@ async fn list_checks(State(state): State<Arc<AppState>>) -> Json<Vec<CheckSummar
 
 async fn execute_check(
     State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    actor: Actor,
     Path(id): Path<String>,
     Json(payload): Json<ExecuteRequest>,
-) -> Json<Result<CheckResult, String>> {
-    if let Some(check) = state.checks.get(&id) {
-        match check
-            .execute(state.check_context.as_ref(), &payload.params)
+) -> Result<Json<Result<ExecuteResponse, String>>, Response> {
+    let Some(check) = state.checks.get(&id) else {
+        return Ok(Json(Err("Check not found".to_string())));
+    };
+    Access::load(&state, identity)
+        .await
+        .require_id(&state.checks, Permission::Run, &id)?;
+    let event = AuditEvent::new(AuditAction::Execute, ResourceType::Check, &id).after(Some(
+        serde_json::json!({
+            "params": payload.params,
+            "async": payload.run_async,
+            "environment": payload.environment,
+        }),
+    ));
+    audit::record(&state, &actor, event).await;
+
+    let options = RunOptions {
+        timeout: payload.timeout_secs.map(Duration::from_secs),
+        environment: payload.environment,
+    };
+
+    if payload.run_async {
+        return Ok(
+            match state
+                .runs
+                .submit(check.clone(), payload.params, options)
+                .await
+            {
+                Ok(run_id) => Json(Ok(ExecuteResponse::Queued { run_id })),
+                Err(e) => Json(Err(e.to_string())),
+            },
+        );
+    }
+
+    Ok(
+        match state
+            .runner
+            .run(check.as_ref(), &payload.params, &options)
             .await
         {
-            Ok(result) => {
-                // Save to DB
-                let status_str = format!("{:?}", result.status);
-                let _ = state
-                    .db
-                    .save_result(&id, &status_str, &result.message, result.details.as_ref())
-                    .await;
-                Json(Ok(result))
-            }
+            Ok(result) => Json(Ok(ExecuteResponse::Completed(result))),
             Err(e) => Json(Err(e.to_string())),
+        },
+    )
+}
+
+/// Run every check the caller may run.
+async fn run_all_checks(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    actor: Actor,
+    payload: Option<Json<BatchRequest>>,
+) -> Json<BatchSummary> {
+    let access = Access::load(&state, identity).await;
+    let checks: Vec<_> = state
+        .checks
+        .values()
+        .filter(|c| access.allows(Permission::Run, c.as_ref()))
+        .cloned()
+        .collect();
+    record_batch(&state, &actor, "*", &checks).await;
+    Json(run_batch(&state, checks, payload.map(|p| p.0).unwrap_or_default()).await)
+}
+
+async fn run_group(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    actor: Actor,
+    Path(group): Path<String>,
+    payload: Option<Json<BatchRequest>>,
+) -> Result<Json<Result<BatchSummary, String>>, Response> {
+    let checks: Vec<_> = state
+        .checks
+        .values()
+        .filter(|c| c.metadata().groups.contains(&group))
+        .cloned()
+        .collect();
+    if checks.is_empty() {
+        return Ok(Json(Err(format!("No checks in group {}", group))));
+    }
+    let access = Access::load(&state, identity).await;
+    if let Some(denied) = checks
+        .iter()
+        .find(|c| !access.allows(Permission::Run, c.as_ref()))
+    {
+        return Err(auth::forbidden(
+            Permission::Run,
+            &format!("check {}", denied.id()),
+        ));
+    }
+    record_batch(&state, &actor, &group, &checks).await;
+    Ok(Json(Ok(run_batch(
+        &state,
+        checks,
+        payload.map(|p| p.0).unwrap_or_default(),
+    )
+    .await)))
+}
+
+/// Records a batch run in the audit log; `group` is `*` for all checks.
+async fn record_batch(state: &AppState, actor: &Actor, group: &str, checks: &[Arc<dyn DataCheck>]) {
+    let ids: Vec<&str> = checks.iter().map(|c| c.id()).collect();
+    let event = AuditEvent::new(AuditAction::Execute, ResourceType::CheckGroup, group)
+        .after(Some(serde_json::json!({ "checks": ids })));
+    audit::record(state, actor, event).await;
+}
+
+async fn run_batch(
+    state: &AppState,
+    checks: Vec<Arc<dyn DataCheck>>,
+    req: BatchRequest,
+) -> BatchSummary {
+    let options = RunOptions {
+        timeout: req.timeout_secs.map(Duration::from_secs),
+        environment: req.environment,
+    };
+    state.runner.run_batch(checks, req.params, options).await
+}
+
+async fn get_run(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+) -> Result<Json<Result<Run, String>>, Response> {
+    Ok(match state.runs.get(&id).await {
+        Ok(Some(run)) => {
+            Access::load(&state, identity).await.require_id(
+                &state.checks,
+                Permission::View,
+                &run.check_id,
+            )?;
+            Json(Ok(run))
         }
-    } else {
-        Json(Err("Check not found".to_string()))
+        Ok(None) => Json(Err("Run not found".to_string())),
+        Err(e) => Json(Err(e.to_string())),
+    })
+}
+
+async fn cancel_run(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<Json<Result<(), String>>, Response> {
+    match state.runs.get(&id).await {
+        Ok(Some(run)) => {
+            Access::load(&state, identity).await.require_id(
+                &state.checks,
+                Permission::Run,
+                &run.check_id,
+            )?;
+            if run.status.is_terminal() {
+                return Ok(Json(Err(format!("Run already {:?}", run.status))));
+            }
+        }
+        Ok(None) => return Ok(Json(Err("Run not found".to_string()))),
+        Err(e) => return Ok(Json(Err(e.to_string()))),
     }
+
+    Ok(match state.runs.cancel(&id).await {
+        Ok(true) => {
+            let event = AuditEvent::new(AuditAction::Cancel, ResourceType::Run, &id);
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
+        Ok(false) => Json(Err("Run already finished".to_string())),
+        Err(e) => Json(Err(e.to_string())),
+    })
 }
 
-async fn get_history(State(state): State<Arc<AppState>>) -> Json<Vec<HistoryEntry>> {
-    match state.db.get_recent_results(50).await {
-        Ok(results) => {
-            let history = results
-                .into_iter()
-                .map(|(id, status, time)| HistoryEntry {
-                    check_id: id,
+async fn get_history(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+) -> Json<Vec<HistoryEntry>> {
+    const ENTRIES: usize = 50;
+    const PAGE_SIZE: i64 = 200;
+
+    let access = Access::load(&state, identity).await;
+    // Results the caller may not view are skipped, so read further pages
+    // until enough entries are found or the results run out.
+    let mut history = Vec::new();
+    let mut offset = 0;
+    while history.len() < ENTRIES {
+        let Ok(page) = state.db.get_results_page(offset, PAGE_SIZE).await else {
+            break;
+        };
+        let last_page = (page.len() as i64) < PAGE_SIZE;
+        history.extend(
+            page.into_iter()
+                .filter(|(_, check_id, ..)| {
+                    access.allows_id(&state.checks, Permission::View, check_id)
+                })
+                .map(|(id, check_id, status, time, environment)| HistoryEntry {
+                    id,
+                    check_id,
                     status,
                     executed_at: time.to_rfc3339(),
-                })
-                .collect();
-            Json(history)
+                    environment,
+                }),
+        );
+        if last_page {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    history.truncate(ENTRIES);
+    Json(history)
+}
+
+/// Check that the caller has `permission` on the check a result belongs to.
+async fn require_result(
+    state: &AppState,
+    identity: Identity,
+    permission: Permission,
+    result_id: i64,
+) -> Result<(), Response> {
+    match state.db.get_result_check_id(result_id).await {
+        Ok(Some(check_id)) => {
+            Access::load(state, identity)
+                .await
+                .require_id(&state.checks, permission, &check_id)
         }
+        Ok(None) => Ok(()),
+        Err(e) => Err(auth::internal_error(e.to_string())),
+    }
+}
+
+async fn get_attempts(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<AttemptEntry>>, Response> {
+    require_result(&state, identity, Permission::View, id).await?;
+    Ok(match state.db.get_attempts(id).await {
+        Ok(attempts) => Json(
+            attempts
+                .into_iter()
+                .map(|a| AttemptEntry {
+                    attempt: a.attempt,
+                    status: a.status,
+                    message: a.message,
+                    started_at: a.started_at.to_rfc3339(),
+                    finished_at: a.finished_at.to_rfc3339(),
+                })
+                .collect(),
+        ),
+        Err(_) => Json(vec![]),
+    })
+}
+
+#[derive(Deserialize)]
+struct AlertQuery {
+    check_id: Option<String>,
+    limit: Option<i64>,
+}
+
+async fn list_alerts(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Query(query): Query<AlertQuery>,
+) -> Json<Vec<AlertEntry>> {
+    let access = Access::load(&state, identity).await;
+    match state
+        .db
+        .get_alerts(query.check_id.as_deref(), query.limit.unwrap_or(50))
+        .await
+    {
+        Ok(alerts) => Json(
+            alerts
+                .into_iter()
+                .filter(|a| access.allows_id(&state.checks, Permission::View, &a.check_id))
+                .map(|a| AlertEntry {
+                    id: a.id,
+                    check_id: a.check_id,
+                    kind: a.kind,
+                    status: a.status,
+                    message: a.message,
+                    result_id: a.result_id,
+                    deliveries: a.deliveries,
+                    created_at: a.created_at.to_rfc3339(),
+                })
+                .collect(),
+        ),
+        Err(_) => Json(vec![]),
+    }
+}
+
+async fn list_webhooks(State(state): State<Arc<AppState>>) -> Json<Vec<WebhookConfig>> {
+    match state.db.get_webhooks().await {
+        Ok(webhooks) => Json(webhooks.into_iter().map(WebhookConfig::from).collect()),
+        Err(_) => Json(vec![]),
+    }
+}
+
+async fn save_webhook(
+    State(state): State<Arc<AppState>>,
+    Json(webhook): Json<WebhookConfig>,
+) -> Json<Result<(), String>> {
+    if let Err(e) = webhook.validate() {
+        return Json(Err(e));
+    }
+    match state.db.save_webhook(&(&webhook).into()).await {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Json<Result<(), String>> {
+    match state.db.delete_webhook(&name).await {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+async fn list_webhook_deliveries(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Json<Vec<WebhookDeliveryEntry>> {
+    match state.db.get_webhook_deliveries(&name, 100).await {
+        Ok(deliveries) => Json(
+            deliveries
+                .into_iter()
+                .map(|d| WebhookDeliveryEntry {
+                    check_id: d.check_id,
+                    alert_kind: d.alert_kind,
+                    attempt: d.attempt,
+                    status_code: d.status_code,
+                    error: d.error,
+                    delivered: d.delivered,
+                    created_at: d.created_at.to_rfc3339(),
+                })
+                .collect(),
+        ),
+        Err(_) => Json(vec![]),
+    }
+}
+
+async fn list_email_channels(State(state): State<Arc<AppState>>) -> Json<Vec<EmailConfig>> {
+    match state.db.get_email_channels().await {
+        Ok(channels) => Json(
+            channels
+                .into_iter()
+                .filter_map(|c| EmailConfig::try_from(c).ok())
+                .collect(),
+        ),
+        Err(_) => Json(vec![]),
+    }
+}
+
+async fn save_email_channel(
+    State(state): State<Arc<AppState>>,
+    Json(channel): Json<EmailConfig>,
+) -> Json<Result<(), String>> {
+    match state.db.save_email_channel(&(&channel).into()).await {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+async fn delete_email_channel(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Json<Result<(), String>> {
+    match state.db.delete_email_channel(&name).await {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+async fn list_maintenance_windows(
+    State(state): State<Arc<AppState>>,
+) -> Json<Vec<MaintenanceWindow>> {
+    match state.db.get_maintenance_windows().await {
+        Ok(windows) => Json(
+            windows
+                .into_iter()
+                .filter_map(|w| MaintenanceWindow::try_from(w).ok())
+                .collect(),
+        ),
         Err(_) => Json(vec![]),
     }
 }
 
+async fn create_maintenance_window(
+    State(state): State<Arc<AppState>>,
+    Json(window): Json<MaintenanceWindow>,
+) -> Json<Result<i64, String>> {
+    if let Err(e) = window.schedule.validate() {
+        return Json(Err(e));
+    }
+    let schedule = serde_json::to_value(&window.schedule).unwrap_or_default();
+    let scope = serde_json::to_value(&window.scope).unwrap_or_default();
+    match state
+        .db
+        .create_maintenance_window(&window.name, window.reason.as_deref(), &schedule, &scope)
+        .await
+    {
+        Ok(id) => Json(Ok(id)),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+async fn delete_maintenance_window(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Json<Result<(), String>> {
+    match state.db.delete_maintenance_window(id).await {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    }
+}
+
+/// The author recorded for a silence, acknowledgement or annotation: the
+/// authenticated user. Only when authentication is disabled is the author
+/// given in the request used.
+fn author_of(identity: &Identity, given: Option<String>) -> String {
+    match (identity.method, given) {
+        (AuthMethod::Anonymous, Some(author)) if !author.trim().is_empty() => author,
+        _ => identity.username.clone(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SilenceRequest {
+    pub until: DateTime<Utc>,
+    pub reason: String,
+    /// Ignored when authentication is enabled.
+    #[serde(default)]
+    pub author: Option<String>,
+}
+
+async fn silence_check(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+    Json(request): Json<SilenceRequest>,
+) -> Result<Json<Result<i64, String>>, Response> {
+    if state.checks.get(&id).is_none() {
+        return Ok(Json(Err(format!("Check not found: {}", id))));
+    }
+    let author = author_of(&identity, request.author);
+    Access::load(&state, identity)
+        .await
+        .require_id(&state.checks, Permission::Run, &id)?;
+    if request.until <= Utc::now() {
+        return Ok(Json(Err("Silence must end in the future".to_string())));
+    }
+    Ok(
+        match state
+            .db
+            .create_silence(&id, request.until, &request.reason, &author)
+            .await
+        {
+            Ok(silence_id) => Json(Ok(silence_id)),
+            Err(e) => Json(Err(e.to_string())),
+        },
+    )
+}
+
+#[derive(Deserialize)]
+struct SilenceQuery {
+    check_id: Option<String>,
+    /// Include silences that have already ended.
+    #[serde(default)]
+    expired: bool,
+}
+
+async fn list_silences(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Query(query): Query<SilenceQuery>,
+) -> Json<Vec<Silence>> {
+    let access = Access::load(&state, identity).await;
+    let now = Utc::now();
+    match state.db.get_silences(query.check_id.as_deref()).await {
+        Ok(silences) => Json(
+            silences
+                .into_iter()
+                .map(Silence::from)
+                .filter(|s| query.expired || s.until > now)
+                .filter(|s| access.allows_id(&state.checks, Permission::View, &s.check_id))
+                .collect(),
+        ),
+        Err(_) => Json(vec![]),
+    }
+}
+
+async fn delete_silence(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<i64>,
+) -> Result<Json<Result<(), String>>, Response> {
+    let silence = match state.db.get_silences(None).await {
+        Ok(silences) => silences.into_iter().find(|s| s.id == id),
+        Err(e) => return Ok(Json(Err(e.to_string()))),
+    };
+    if let Some(silence) = silence {
+        Access::load(&state, identity).await.require_id(
+            &state.checks,
+            Permission::Run,
+            &silence.check_id,
+        )?;
+    }
+    Ok(match state.db.delete_silence(id).await {
+        Ok(_) => Json(Ok(())),
+        Err(e) => Json(Err(e.to_string())),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct AcknowledgeRequest {
+    /// Ignored when authentication is enabled.
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// Mark a failing check as being handled. The acknowledgement is cleared
+/// the next time the check succeeds.
+async fn acknowledge_check(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+    Json(request): Json<AcknowledgeRequest>,
+) -> Result<Json<Result<(), String>>, Response> {
+    if state.checks.get(&id).is_none() {
+        return Ok(Json(Err(format!("Check not found: {}", id))));
+    }
+    let author = author_of(&identity, request.author);
+    Access::load(&state, identity)
+        .await
+        .require_id(&state.checks, Permission::Run, &id)?;
+    let latest = match state.db.get_latest_check_statuses().await {
+        Ok(statuses) => statuses.get(&id).map(|(status, _)| status.clone()),
+        Err(e) => return Ok(Json(Err(e.to_string()))),
+    };
+    // Skipped and suppressed results say nothing about the check itself.
+    if !matches!(latest.as_deref(), Some("Failure") | Some("TimedOut")) {
+        return Ok(Json(Err(format!("Check {} is not failing", id))));
+    }
+
+    Ok(
+        match state
+            .db
+            .acknowledge_check(&id, &author, request.comment.as_deref())
+            .await
+        {
+            Ok(_) => Json(Ok(())),
+            Err(e) => Json(Err(e.to_string())),
+        },
+    )
+}
+
+async fn unacknowledge_check(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+) -> Result<Json<Result<(), String>>, Response> {
+    Access::load(&state, identity)
+        .await
+        .require_id(&state.checks, Permission::Run, &id)?;
+    Ok(match state.db.clear_acknowledgement(&id).await {
+        Ok(true) => Json(Ok(())),
+        Ok(false) => Json(Err(format!("Check {} is not acknowledged", id))),
+        Err(e) => Json(Err(e.to_string())),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct AnnotationRequest {
+    /// Ignored when authentication is enabled.
+    #[serde(default)]
+    pub author: Option<String>,
+    pub text: String,
+}
+
+async fn add_annotation(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<i64>,
+    Json(request): Json<AnnotationRequest>,
+) -> Result<Json<Result<i64, String>>, Response> {
+    if request.text.trim().is_empty() {
+        return Ok(Json(Err("Annotation text must not be empty".to_string())));
+    }
+    let author = author_of(&identity, request.author);
+    match state.db.get_result_check_id(id).await {
+        Ok(Some(check_id)) => Access::load(&state, identity).await.require_id(
+            &state.checks,
+            Permission::Run,
+            &check_id,
+        )?,
+        Ok(None) => return Ok(Json(Err(format!("Result not found: {}", id)))),
+        Err(e) => return Ok(Json(Err(e.to_string()))),
+    }
+    Ok(
+        match state.db.add_annotation(id, &author, &request.text).await {
+            Ok(annotation_id) => Json(Ok(annotation_id)),
+            Err(e) => Json(Err(e.to_string())),
+        },
+    )
+}
+
+async fn list_annotations(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<AnnotationEntry>>, Response> {
+    require_result(&state, identity, Permission::View, id).await?;
+    Ok(match state.db.get_annotations(id).await {
+        Ok(annotations) => Json(
+            annotations
+                .into_iter()
+                .map(|a| AnnotationEntry {
+                    id: a.id,
+                    author: a.author,
+                    text: a.text,
+                    created_at: a.created_at.to_rfc3339(),
+                })
+                .collect(),
+        ),
+        Err(_) => Json(vec![]),
+    })
+}
+
 async fn get_check_statuses(
     State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
 ) -> Json<HashMap<String, CheckStatus>> {
+    let access = Access::load(&state, identity).await;
+    let mut acknowledgements: HashMap<String, Acknowledgement> =
+        match state.db.get_acknowledgements().await {
+            Ok(acks) => acks
+                .into_iter()
+                .map(|a| {
+                    (
+                        a.check_id,
+                        Acknowledgement {
+                            author: a.author,
+                            comment: a.comment,
+                            acknowledged_at: a.acknowledged_at.to_rfc3339(),
+                        },
+                    )
+                })
+                .collect(),
+            Err(_) => HashMap::new(),
+        };
+
     match state.db.get_latest_check_statuses().await {
         Ok(statuses) => {
             let result = statuses
                 .into_iter()
+                .filter(|(check_id, _)| access.allows_id(&state.checks, Permission::View, check_id))
                 .map(|(check_id, (status, executed_at))| {
+                    let acknowledged = acknowledgements.remove(&check_id);
                     (
                         check_id,
                         CheckStatus {
                             status,
                             executed_at: executed_at.to_rfc3339(),
+                            acknowledged,
                         },
                     )
                 })
@@ -151,29 +942,25 @@ async fn get_check_statuses(
 
 async fn list_connections(State(state): State<Arc<AppState>>) -> Json<Vec<ConnectionProfile>> {
     match state.db.get_connection_profiles().await {
-        Ok(profiles) => {
-            let result = profiles
-                .into_iter()
-                .map(
-                    |(name, driver, tmpl, connection_type, secret_ref)| ConnectionProfile {
-                        name,
-                        driver,
-                        connection_string_template: tmpl,
-                        connection_type,
-                        secret_ref,
-                    },
-                )
-                .collect();
-            Json(result)
-        }
+        Ok(profiles) => Json(profiles.into_iter().map(ConnectionProfile::from).collect()),
         Err(_) => Json(vec![]),
     }
 }
 
+async fn connection_snapshot(state: &AppState, name: &str) -> Option<Value> {
+    let profiles = state.db.get_connection_profiles().await.ok()?;
+    profiles
+        .into_iter()
+        .find(|p| p.name == name)
+        .map(|p| serde_json::json!(ConnectionProfile::from(p)))
+}
+
 async fn save_connection(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Json(profile): Json<ConnectionProfile>,
 ) -> Json<Result<(), String>> {
+    let before = connection_snapshot(&state, &profile.name).await;
     match state
         .db
         .save_connection_profile(
@@ -182,22 +969,99 @@ async fn save_connection(
             &profile.connection_string_template,
             profile.connection_type.as_deref(),
             profile.secret_ref.as_deref(),
+            profile.auth.as_ref().map(ToString::to_string).as_deref(),
         )
         .await
     {
-        Ok(_) => Json(Ok(())),
+        Ok(_) => {
+            let event = AuditEvent::saved(ResourceType::Connection, &profile.name, before)
+                .after(Some(serde_json::json!(profile)));
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
         Err(e) => Json(Err(e.to_string())),
     }
 }
 
+#[derive(Deserialize)]
+struct DeleteQuery {
+    /// Delete dependent data sources too, instead of refusing.
+    #[serde(default)]
+    cascade: bool,
+}
+
+/// Body of a 409 response to a delete refused because of dependents.
+#[derive(Serialize)]
+struct DependentsConflict {
+    #[serde(rename = "Err")]
+    error: String,
+    data_sources: Vec<String>,
+}
+
+fn dependents_conflict(what: &str, data_sources: Vec<String>) -> Response {
+    let body = DependentsConflict {
+        error: format!(
+            "{} is used by data sources: {}. Delete them first or pass cascade=true",
+            what,
+            data_sources.join(", ")
+        ),
+        data_sources,
+    };
+    (StatusCode::CONFLICT, Json(body)).into_response()
+}
+
+/// Records the data sources removed by a cascading delete.
+async fn record_cascade(state: &AppState, actor: &Actor, snapshots: Vec<(String, Option<Value>)>) {
+    for (name, before) in snapshots {
+        let event =
+            AuditEvent::new(AuditAction::Delete, ResourceType::DataSource, &name).before(before);
+        audit::record(state, actor, event).await;
+    }
+}
+
+async fn data_source_snapshots(state: &AppState, names: &[String]) -> Vec<(String, Option<Value>)> {
+    let mut snapshots = Vec::with_capacity(names.len());
+    for name in names {
+        snapshots.push((name.clone(), data_source_snapshot(state, name).await));
+    }
+    snapshots
+}
+
 async fn delete_connection(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(name): Path<String>,
-) -> Json<Result<(), String>> {
-    match state.db.delete_connection_profile(&name).await {
-        Ok(_) => Json(Ok(())),
-        Err(e) => Json(Err(e.to_string())),
+    Query(query): Query<DeleteQuery>,
+) -> Result<Json<Result<(), String>>, Response> {
+    let dependents = match state.db.get_connection_dependents(&name).await {
+        Ok(dependents) => dependents,
+        Err(e) => return Ok(Json(Err(e.to_string()))),
+    };
+    if !dependents.is_empty() && !query.cascade {
+        return Err(dependents_conflict(
+            &format!("Connection {}", name),
+            dependents,
+        ));
     }
+
+    let before = connection_snapshot(&state, &name).await;
+    let cascaded = data_source_snapshots(&state, &dependents).await;
+    Ok(
+        match state
+            .db
+            .delete_connection_profile(&name, query.cascade)
+            .await
+        {
+            Ok(_) => {
+                record_cascade(&state, &actor, cascaded).await;
+                let event = AuditEvent::new(AuditAction::Delete, ResourceType::Connection, &name)
+                    .before(before);
+                audit::record(&state, &actor, event).await;
+                Json(Ok(()))
+            }
+            Err(e) => Json(Err(e.to_string())),
+        },
+    )
 }
 
 async fn list_secrets(State(state): State<Arc<AppState>>) -> Json<Vec<String>> {
@@ -207,33 +1071,94 @@ async fn list_secrets(State(state): State<Arc<AppState>>) -> Json<Vec<String>> {
     }
 }
 
+/// A secret as seen by the audit log: its key, never its value.
+fn secret_entry(key: &str) -> Value {
+    serde_json::json!({ "key": key, "value": REDACTED })
+}
+
+async fn secret_snapshot(state: &AppState, key: &str) -> Option<Value> {
+    let keys = state.db.get_secrets().await.ok()?;
+    keys.iter().any(|k| k == key).then(|| secret_entry(key))
+}
+
 async fn save_secret(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Json(req): Json<SaveSecretRequest>,
 ) -> Json<Result<(), String>> {
+    let before = secret_snapshot(&state, &req.key).await;
     match state.db.save_secret(&req.key, &req.value).await {
-        Ok(_) => Json(Ok(())),
+        Ok(_) => {
+            let event = AuditEvent::saved(ResourceType::Secret, &req.key, before)
+                .after(Some(secret_entry(&req.key)));
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
         Err(e) => Json(Err(e.to_string())),
     }
 }
 
+/// Deletes a secret. Data sources using it block the delete unless
+/// `cascade=true`; data sources whose connection profile refers to it are
+/// kept and marked invalid.
 async fn delete_secret(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(key): Path<String>,
-) -> Json<Result<(), String>> {
-    match state.db.delete_secret(&key).await {
-        Ok(_) => Json(Ok(())),
-        Err(e) => Json(Err(e.to_string())),
+    Query(query): Query<DeleteQuery>,
+) -> Result<Json<Result<(), String>>, Response> {
+    let dependents = match state.db.get_secret_dependents(&key).await {
+        Ok((data_sources, _profiles)) => data_sources,
+        Err(e) => return Ok(Json(Err(e.to_string()))),
+    };
+    if !dependents.is_empty() && !query.cascade {
+        return Err(dependents_conflict(&format!("Secret {}", key), dependents));
     }
+
+    let before = secret_snapshot(&state, &key).await;
+    let cascaded = data_source_snapshots(&state, &dependents).await;
+    Ok(match state.db.delete_secret(&key, query.cascade).await {
+        Ok(_) => {
+            record_cascade(&state, &actor, cascaded).await;
+            let event =
+                AuditEvent::new(AuditAction::Delete, ResourceType::Secret, &key).before(before);
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
+        Err(e) => Json(Err(e.to_string())),
+    })
 }
 
-async fn list_data_sources(State(state): State<Arc<AppState>>) -> Json<Vec<DataSourceInfo>> {
+async fn list_data_sources(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+) -> Json<Vec<DataSourceInfo>> {
+    let access = Access::load(&state, identity).await;
     match state.db.get_data_sources().await {
         Ok(sources) => {
+            let bindings = DataSourceBindings::new(
+                sources
+                    .iter()
+                    .map(|(name, connection, _, _)| (name.clone(), connection.clone()))
+                    .collect(),
+            );
+            let mut used_by: HashMap<String, Vec<String>> = HashMap::new();
+            for check in state.checks.values() {
+                for name in bindings.data_sources_of(check.as_ref()) {
+                    used_by
+                        .entry(name)
+                        .or_default()
+                        .push(check.id().to_string());
+                }
+            }
             let result = sources
                 .into_iter()
+                .filter(|(name, _, _, _)| {
+                    access.allows_data_source(Permission::ManageConnections, name)
+                })
                 .map(
                     |(name, connection_name, secret_key, is_valid)| DataSourceInfo {
+                        checks: used_by.remove(&name).unwrap_or_default(),
                         name,
                         connection_name,
                         secret_key,
@@ -247,28 +1172,75 @@ async fn list_data_sources(State(state): State<Arc<AppState>>) -> Json<Vec<DataS
     }
 }
 
+#[allow(clippy::result_large_err)]
+fn require_data_source(access: &Access, name: &str) -> Result<(), Response> {
+    if access.allows_data_source(Permission::ManageConnections, name) {
+        Ok(())
+    } else {
+        Err(auth::forbidden(
+            Permission::ManageConnections,
+            &format!("data source {}", name),
+        ))
+    }
+}
+
+async fn data_source_snapshot(state: &AppState, name: &str) -> Option<Value> {
+    let sources = state.db.get_data_sources().await.ok()?;
+    sources.into_iter().find(|(n, ..)| n == name).map(
+        |(name, connection_name, secret_key, is_valid)| {
+            serde_json::json!({
+                "name": name,
+                "connection_name": connection_name,
+                "secret_key": secret_key,
+                "is_valid": is_valid,
+            })
+        },
+    )
+}
+
 async fn save_data_source(
     State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    actor: Actor,
     Json(req): Json<DataSourceRequest>,
-) -> Json<Result<(), String>> {
-    match state
-        .db
-        .save_data_source(&req.name, &req.connection_name, &req.secret_key)
-        .await
-    {
-        Ok(_) => Json(Ok(())),
-        Err(e) => Json(Err(e.to_string())),
-    }
+) -> Result<Json<Result<(), String>>, Response> {
+    require_data_source(&Access::load(&state, identity).await, &req.name)?;
+    let before = data_source_snapshot(&state, &req.name).await;
+    Ok(
+        match state
+            .db
+            .save_data_source(&req.name, &req.connection_name, &req.secret_key)
+            .await
+        {
+            Ok(_) => {
+                let after = data_source_snapshot(&state, &req.name).await;
+                let event =
+                    AuditEvent::saved(ResourceType::DataSource, &req.name, before).after(after);
+                audit::record(&state, &actor, event).await;
+                Json(Ok(()))
+            }
+            Err(e) => Json(Err(e.to_string())),
+        },
+    )
 }
 
 async fn delete_data_source(
     State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    actor: Actor,
     Path(name): Path<String>,
-) -> Json<Result<(), String>> {
-    match state.db.delete_data_source(&name).await {
-        Ok(_) => Json(Ok(())),
+) -> Result<Json<Result<(), String>>, Response> {
+    require_data_source(&Access::load(&state, identity).await, &name)?;
+    let before = data_source_snapshot(&state, &name).await;
+    Ok(match state.db.delete_data_source(&name).await {
+        Ok(_) => {
+            let event = AuditEvent::new(AuditAction::Delete, ResourceType::DataSource, &name)
+                .before(before);
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
         Err(e) => Json(Err(e.to_string())),
-    }
+    })
 }
 
 #[derive(Serialize)]
@@ -276,19 +1248,74 @@ struct CheckSummary {
     id: String,
     description: String,
     parameters: Vec<crate::checks::ParameterDefinition>,
+    dependencies: Vec<String>,
+    /// Data sources the check declares or reaches through its connections.
+    data_sources: Vec<String>,
+    metadata: CheckMetadata,
 }
 
 #[derive(Serialize)]
 struct HistoryEntry {
+    id: i64,
     check_id: String,
     status: String,
     executed_at: String,
+    environment: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AlertEntry {
+    id: i64,
+    check_id: String,
+    kind: String,
+    status: String,
+    message: String,
+    result_id: Option<i64>,
+    deliveries: Option<Value>,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+struct WebhookDeliveryEntry {
+    check_id: String,
+    alert_kind: String,
+    attempt: i64,
+    status_code: Option<i64>,
+    error: Option<String>,
+    delivered: bool,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+struct AttemptEntry {
+    attempt: i64,
+    status: String,
+    message: String,
+    started_at: String,
+    finished_at: String,
 }
 
 #[derive(Serialize)]
 struct CheckStatus {
     status: String,
     executed_at: String,
+    /// Set while someone is handling the failure.
+    acknowledged: Option<Acknowledgement>,
+}
+
+#[derive(Serialize)]
+struct Acknowledgement {
+    author: String,
+    comment: Option<String>,
+    acknowledged_at: String,
+}
+
+#[derive(Serialize)]
+struct AnnotationEntry {
+    id: i64,
+    author: String,
+    text: String,
+    created_at: String,
 }
 
 #[derive(Serialize)]
@@ -297,6 +1324,8 @@ struct DataSourceInfo {
     connection_name: String,
     secret_key: String,
     is_valid: bool,
+    /// Ids of the checks using the data source.
+    checks: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -330,26 +1359,55 @@ async fn list_unix_groups(State(state): State<Arc<AppState>>) -> Json<Vec<UnixGr
     }
 }
 
+async fn unix_group_snapshot(state: &AppState, name: &str) -> Option<Value> {
+    let groups = state.db.get_unix_groups().await.ok()?;
+    groups
+        .into_iter()
+        .find(|(n, ..)| n == name)
+        .map(|(group_name, file_path, permissions)| {
+            serde_json::json!(UnixGroupInfo {
+                group_name,
+                file_path,
+                permissions,
+            })
+        })
+}
+
 async fn save_unix_group(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Json(req): Json<UnixGroupRequest>,
 ) -> Json<Result<(), String>> {
+    let before = unix_group_snapshot(&state, &req.group_name).await;
     match state
         .db
         .save_unix_group(&req.group_name, &req.file_path, &req.permissions)
         .await
     {
-        Ok(_) => Json(Ok(())),
+        Ok(_) => {
+            let after = unix_group_snapshot(&state, &req.group_name).await;
+            let event =
+                AuditEvent::saved(ResourceType::UnixGroup, &req.group_name, before).after(after);
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
         Err(e) => Json(Err(e.to_string())),
     }
 }
 
 async fn delete_unix_group(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(name): Path<String>,
 ) -> Json<Result<(), String>> {
+    let before = unix_group_snapshot(&state, &name).await;
     match state.db.delete_unix_group(&name).await {
-        Ok(_) => Json(Ok(())),
+        Ok(_) => {
+            let event =
+                AuditEvent::new(AuditAction::Delete, ResourceType::UnixGroup, &name).before(before);
+            audit::record(&state, &actor, event).await;
+            Json(Ok(()))
+        }
         Err(e) => Json(Err(e.to_string())),
     }
 }
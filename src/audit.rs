@@ -0,0 +1,331 @@
+//! Append-only audit log of configuration changes and check executions.
+//!
+//! Every change to connections, secrets, data sources and unix groups made
+//! through the API, and every check execution it triggers, is recorded with
+//! the acting user, their IP address and a snapshot of the resource before
+//! and after the change. Secret values never reach the log: snapshots pass
+//! through [`redact`] before they are stored.
+
+use crate::db::{AuditRecord, Db};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Placeholder stored instead of a sensitive value.
+pub const REDACTED: &str = "[redacted]";
+
+/// Object keys whose values are replaced by [`REDACTED`]. Keys naming a
+/// secret (`secret_ref`, `secret_key`) are references and are kept.
+const SENSITIVE_KEYS: &[&str] = &["value", "password", "token", "api_key"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Execute,
+    Cancel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResourceType {
+    Connection,
+    Secret,
+    DataSource,
+    UnixGroup,
+    Check,
+    CheckGroup,
+    Run,
+    Environment,
+    Schedule,
+}
+
+/// Who made a change, and from where.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub username: String,
+    pub ip: Option<String>,
+}
+
+/// A change about to be recorded.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub action: AuditAction,
+    pub resource_type: ResourceType,
+    pub resource_id: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction, resource_type: ResourceType, resource_id: &str) -> Self {
+        Self {
+            action,
+            resource_type,
+            resource_id: resource_id.to_string(),
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn before(mut self, snapshot: Option<Value>) -> Self {
+        self.before = snapshot;
+        self
+    }
+
+    pub fn after(mut self, snapshot: Option<Value>) -> Self {
+        self.after = snapshot;
+        self
+    }
+
+    /// The action implied by the snapshots of a save: `Create` when there
+    /// was nothing before, `Update` otherwise.
+    pub fn saved(resource_type: ResourceType, resource_id: &str, before: Option<Value>) -> Self {
+        let action = if before.is_some() {
+            AuditAction::Update
+        } else {
+            AuditAction::Create
+        };
+        Self::new(action, resource_type, resource_id).before(before)
+    }
+}
+
+/// A field that differs between the before and after snapshots.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: String,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: String,
+    pub ip: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub changes: Vec<FieldChange>,
+}
+
+impl From<AuditRecord> for AuditEntry {
+    fn from(record: AuditRecord) -> Self {
+        let changes = diff(record.before.as_ref(), record.after.as_ref());
+        Self {
+            id: record.id,
+            occurred_at: record.occurred_at,
+            actor: record.actor,
+            action: record.action,
+            resource_type: record.resource_type,
+            resource_id: record.resource_id,
+            ip: record.ip,
+            before: record.before,
+            after: record.after,
+            changes,
+        }
+    }
+}
+
+/// Replaces the values of sensitive keys, at any depth, by [`REDACTED`].
+pub fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let sensitive = SENSITIVE_KEYS.contains(&key.to_lowercase().as_str());
+                    if sensitive && !value.is_null() {
+                        (key, Value::String(REDACTED.to_string()))
+                    } else {
+                        (key, redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        other => other,
+    }
+}
+
+/// The top-level fields that differ between two object snapshots. A missing
+/// snapshot (creation or deletion) reports every field of the other one.
+pub fn diff(before: Option<&Value>, after: Option<&Value>) -> Vec<FieldChange> {
+    let empty = serde_json::Map::new();
+    let fields = |v: Option<&Value>| v.and_then(Value::as_object).unwrap_or(&empty).clone();
+    let (before, after) = (fields(before), fields(after));
+
+    let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| before.get(*key) != after.get(*key))
+        .map(|key| FieldChange {
+            field: key.clone(),
+            before: before.get(key).cloned(),
+            after: after.get(key).cloned(),
+        })
+        .collect()
+}
+
+/// Query filters for the audit log. All of them are optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Clone)]
+pub struct AuditLog {
+    db: Db,
+}
+
+impl AuditLog {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Appends an event, redacting its snapshots.
+    pub async fn record(&self, actor: &Actor, event: AuditEvent) -> anyhow::Result<i64> {
+        let record = AuditRecord {
+            id: 0,
+            occurred_at: Utc::now(),
+            actor: actor.username.clone(),
+            action: format!("{:?}", event.action),
+            resource_type: format!("{:?}", event.resource_type),
+            resource_id: event.resource_id,
+            ip: actor.ip.clone(),
+            before: event.before.map(redact),
+            after: event.after.map(redact),
+        };
+        self.db.insert_audit_entry(&record).await
+    }
+
+    pub async fn query(&self, filter: &AuditFilter) -> anyhow::Result<Vec<AuditEntry>> {
+        let records = self
+            .db
+            .get_audit_entries(
+                filter.actor.as_deref(),
+                filter.action.as_deref(),
+                filter.resource_type.as_deref(),
+                filter.resource_id.as_deref(),
+                filter.since,
+                filter.until,
+                filter.limit.unwrap_or(DEFAULT_LIMIT).try_into()?,
+            )
+            .await?;
+        Ok(records.into_iter().map(AuditEntry::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn actor() -> Actor {
+        Actor {
+            username: "alice".to_string(),
+            ip: Some("127.0.0.1".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_redact() {
+        let redacted = redact(json!({
+            "key": "db_password",
+            "value": "hunter2",
+            "secret_ref": "db_password",
+            "headers": [{ "Password": "hunter2" }],
+            "token": null,
+        }));
+        assert_eq!(
+            redacted,
+            json!({
+                "key": "db_password",
+                "value": REDACTED,
+                "secret_ref": "db_password",
+                "headers": [{ "Password": REDACTED }],
+                "token": null,
+            })
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = json!({ "driver": "Oracle", "template": "a", "secret_ref": "x" });
+        let after = json!({ "driver": "Oracle", "template": "b" });
+        let changes = diff(Some(&before), Some(&after));
+        assert_eq!(
+            changes,
+            vec![
+                FieldChange {
+                    field: "secret_ref".to_string(),
+                    before: Some(json!("x")),
+                    after: None,
+                },
+                FieldChange {
+                    field: "template".to_string(),
+                    before: Some(json!("a")),
+                    after: Some(json!("b")),
+                },
+            ]
+        );
+        assert_eq!(diff(None, Some(&after)).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_record_and_query() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let log = AuditLog::new(db.clone());
+
+        log.record(
+            &actor(),
+            AuditEvent::saved(ResourceType::Secret, "db_password", None)
+                .after(Some(json!({ "key": "db_password", "value": "hunter2" }))),
+        )
+        .await
+        .unwrap();
+        log.record(
+            &actor(),
+            AuditEvent::new(AuditAction::Delete, ResourceType::UnixGroup, "etl")
+                .before(Some(json!({ "group_name": "etl" }))),
+        )
+        .await
+        .unwrap();
+
+        let all = log.query(&AuditFilter::default()).await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].action, "Delete");
+        assert_eq!(all[1].action, "Create");
+        assert_eq!(all[1].after.as_ref().unwrap()["value"], REDACTED);
+        assert_eq!(all[1].ip.as_deref(), Some("127.0.0.1"));
+
+        let secrets = log
+            .query(&AuditFilter {
+                resource_type: Some("Secret".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(secrets.len(), 1);
+
+        let future = log
+            .query(&AuditFilter {
+                since: Some(Utc::now() + chrono::Duration::hours(1)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(future.is_empty());
+    }
+}
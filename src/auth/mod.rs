@@ -0,0 +1,470 @@
+//! Authentication.
+//!
+//! Two kinds of credentials are accepted: API tokens for automation and
+//! session tokens for the UI, obtained by logging in with a username and
+//! password. Passwords are checked by an `IdentityBackend`; the default
+//! backend uses the local `users` table. Only SHA-256 hashes of tokens are
+//! stored, so a leaked database does not leak usable credentials.
+//!
+//! What an authenticated user may do is decided by their roles; see `rbac`.
+
+pub mod rbac;
+
+use crate::db::{ApiTokenRecord, Db};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rbac::{Grant, Permission, Target};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Name of the cookie carrying the UI session token.
+pub const SESSION_COOKIE: &str = "dd_session";
+
+/// Default lifetime of a UI session.
+pub const DEFAULT_SESSION_TTL: ChronoDuration = ChronoDuration::hours(12);
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("Invalid username or password")]
+    InvalidCredentials,
+    #[error("Identity backend error: {0}")]
+    Backend(String),
+    #[error("Storage error: {0}")]
+    Storage(#[from] anyhow::Error),
+}
+
+/// What an API token may do. Sessions always have every scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Read-only requests (`GET`, `HEAD`).
+    Read,
+    /// Requests that run checks or change configuration.
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AuthMethod {
+    /// Authentication is disabled.
+    Anonymous,
+    Session,
+    Token,
+}
+
+/// The caller of a request.
+#[derive(Debug, Clone, Serialize)]
+pub struct Identity {
+    pub username: String,
+    pub method: AuthMethod,
+    pub scopes: Vec<Scope>,
+    /// Permissions from the user's roles.
+    pub grants: Vec<Grant>,
+}
+
+impl Identity {
+    /// The identity used for every request when authentication is disabled.
+    pub fn anonymous() -> Self {
+        Self {
+            username: "anonymous".to_string(),
+            method: AuthMethod::Anonymous,
+            scopes: vec![Scope::Read, Scope::Write],
+            grants: vec![Grant::global(Permission::Admin)],
+        }
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub fn can(&self, permission: Permission, target: &Target) -> bool {
+        rbac::is_allowed(&self.grants, permission, target)
+    }
+
+    /// Whether the identity has `permission` for anything at all.
+    pub fn holds(&self, permission: Permission) -> bool {
+        rbac::holds(&self.grants, permission)
+    }
+}
+
+/// Checks usernames and passwords.
+#[async_trait]
+pub trait IdentityBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Returns the canonical username if the credentials are valid.
+    async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<String>, AuthError>;
+}
+
+/// Users stored in the local `users` table with Argon2 password hashes.
+pub struct LocalIdentityBackend {
+    db: Db,
+}
+
+impl LocalIdentityBackend {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl IdentityBackend for LocalIdentityBackend {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<String>, AuthError> {
+        match self.db.get_user(username).await? {
+            Some((hash, disabled)) if !disabled && verify_password(password, &hash) => {
+                Ok(Some(username.to_string()))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+pub fn hash_password(password: &str) -> Result<String, AuthError> {
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes())
+        .map_err(|e| AuthError::Backend(e.to_string()))?;
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| AuthError::Backend(e.to_string()))?;
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn generate_token(prefix: &str) -> String {
+    format!(
+        "{}_{}{}",
+        prefix,
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// An API token as shown to its owner. The token itself is only returned
+/// once, when it is created.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub username: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiTokenRecord> for ApiToken {
+    fn from(r: ApiTokenRecord) -> Self {
+        ApiToken {
+            id: r.id,
+            name: r.name,
+            username: r.username,
+            scopes: serde_json::from_value(r.scopes).unwrap_or_default(),
+            expires_at: r.expires_at,
+            created_at: r.created_at,
+            last_used_at: r.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    #[serde(skip)]
+    pub token: String,
+    pub username: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct Authenticator {
+    db: Db,
+    backend: Arc<dyn IdentityBackend>,
+    session_ttl: ChronoDuration,
+    secure_cookie: bool,
+}
+
+impl Authenticator {
+    pub fn new(db: Db, backend: Arc<dyn IdentityBackend>) -> Self {
+        Self {
+            db,
+            backend,
+            session_ttl: DEFAULT_SESSION_TTL,
+            secure_cookie: false,
+        }
+    }
+
+    pub fn with_session_ttl(mut self, ttl: ChronoDuration) -> Self {
+        self.session_ttl = ttl;
+        self
+    }
+
+    /// Mark the session cookie `Secure`, so browsers only send it over
+    /// HTTPS. Needed whenever the server is reachable beyond loopback.
+    pub fn with_secure_cookie(mut self, secure: bool) -> Self {
+        self.secure_cookie = secure;
+        self
+    }
+
+    pub fn secure_cookie(&self) -> bool {
+        self.secure_cookie
+    }
+
+    /// Check a username and password and open a UI session.
+    pub async fn login(&self, username: &str, password: &str) -> Result<Session, AuthError> {
+        let username = self
+            .backend
+            .authenticate(username, password)
+            .await?
+            .ok_or(AuthError::InvalidCredentials)?;
+
+        let now = Utc::now();
+        self.db.delete_expired_sessions(now).await?;
+        let session = Session {
+            token: generate_token("dds"),
+            username,
+            expires_at: now + self.session_ttl,
+        };
+        self.db
+            .create_session(
+                &hash_token(&session.token),
+                &session.username,
+                session.expires_at,
+            )
+            .await?;
+        tracing::info!(
+            "User {} logged in via {} backend",
+            session.username,
+            self.backend.name()
+        );
+        Ok(session)
+    }
+
+    pub async fn logout(&self, session_token: &str) -> Result<(), AuthError> {
+        self.db.delete_session(&hash_token(session_token)).await?;
+        Ok(())
+    }
+
+    pub async fn session_identity(
+        &self,
+        session_token: &str,
+    ) -> Result<Option<Identity>, AuthError> {
+        let session = self.db.get_session(&hash_token(session_token)).await?;
+        let Some((username, _)) = session.filter(|(_, expires_at)| *expires_at > Utc::now()) else {
+            return Ok(None);
+        };
+        if self.is_disabled(&username).await? {
+            return Ok(None);
+        }
+        Ok(Some(Identity {
+            grants: rbac::load_grants(&self.db, &username).await?,
+            username,
+            method: AuthMethod::Session,
+            scopes: vec![Scope::Read, Scope::Write],
+        }))
+    }
+
+    /// Create an API token for `username`. Returns the plaintext token,
+    /// which cannot be recovered later, and its stored description.
+    pub async fn create_token(
+        &self,
+        username: &str,
+        name: &str,
+        scopes: Vec<Scope>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(String, ApiToken), AuthError> {
+        let token = generate_token("ddt");
+        let record = ApiTokenRecord {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            username: username.to_string(),
+            scopes: serde_json::to_value(&scopes).unwrap_or_default(),
+            expires_at,
+            created_at: Utc::now(),
+            last_used_at: None,
+        };
+        self.db
+            .create_api_token(&record, &hash_token(&token))
+            .await?;
+        Ok((token, record.into()))
+    }
+
+    pub async fn token_identity(&self, token: &str) -> Result<Option<Identity>, AuthError> {
+        let Some(record) = self.db.get_api_token_by_hash(&hash_token(token)).await? else {
+            return Ok(None);
+        };
+        let now = Utc::now();
+        if record
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            return Ok(None);
+        }
+        if self.is_disabled(&record.username).await? {
+            return Ok(None);
+        }
+        self.db.touch_api_token(&record.id, now).await?;
+
+        let token = ApiToken::from(record);
+        Ok(Some(Identity {
+            grants: rbac::load_grants(&self.db, &token.username).await?,
+            username: token.username,
+            method: AuthMethod::Token,
+            scopes: token.scopes,
+        }))
+    }
+
+    /// Whether `username` is a local user who has been disabled. Users of
+    /// other identity backends have no row and are not disabled here.
+    async fn is_disabled(&self, username: &str) -> Result<bool, AuthError> {
+        Ok(matches!(self.db.get_user(username).await?, Some((_, true))))
+    }
+}
+
+/// Refuse to serve a non-loopback address unless authentication is enabled.
+pub fn check_bind_address(addr: &SocketAddr, auth_enabled: bool) -> anyhow::Result<()> {
+    if !auth_enabled && !addr.ip().is_loopback() {
+        anyhow::bail!(
+            "Refusing to listen on {} without authentication: enable auth or bind to a loopback address",
+            addr
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn authenticator() -> (Db, Authenticator) {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        db.save_user("alice", &hash_password("correct horse").unwrap())
+            .await
+            .unwrap();
+        let backend = Arc::new(LocalIdentityBackend::new(db.clone()));
+        (db.clone(), Authenticator::new(db, backend))
+    }
+
+    #[tokio::test]
+    async fn test_login_and_logout() {
+        let (_, auth) = authenticator().await;
+        assert!(matches!(
+            auth.login("alice", "wrong").await,
+            Err(AuthError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            auth.login("bob", "correct horse").await,
+            Err(AuthError::InvalidCredentials)
+        ));
+
+        let session = auth.login("alice", "correct horse").await.unwrap();
+        let identity = auth.session_identity(&session.token).await.unwrap();
+        assert_eq!(identity.unwrap().username, "alice");
+
+        auth.logout(&session.token).await.unwrap();
+        assert!(auth
+            .session_identity(&session.token)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_hashed_scoped_and_expire() {
+        let (db, auth) = authenticator().await;
+        let (token, created) = auth
+            .create_token("alice", "ci", vec![Scope::Read], None)
+            .await
+            .unwrap();
+
+        let identity = auth.token_identity(&token).await.unwrap().unwrap();
+        assert_eq!(identity.method, AuthMethod::Token);
+        assert!(identity.has_scope(Scope::Read));
+        assert!(!identity.has_scope(Scope::Write));
+        assert!(db.get_api_token_by_hash(&token).await.unwrap().is_none());
+        assert!(auth.token_identity("ddt_bogus").await.unwrap().is_none());
+
+        let listed = db.get_api_tokens("alice").await.unwrap();
+        assert_eq!(listed[0].id, created.id);
+        assert!(listed[0].last_used_at.is_some());
+
+        let (expired, _) = auth
+            .create_token(
+                "alice",
+                "old",
+                vec![Scope::Read],
+                Some(Utc::now() - ChronoDuration::minutes(1)),
+            )
+            .await
+            .unwrap();
+        assert!(auth.token_identity(&expired).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_user_loses_access() {
+        let (db, auth) = authenticator().await;
+        let session = auth.login("alice", "correct horse").await.unwrap();
+        let (token, _) = auth
+            .create_token("alice", "ci", vec![Scope::Read], None)
+            .await
+            .unwrap();
+
+        assert!(db.set_user_disabled("alice", true).await.unwrap());
+        assert!(auth
+            .session_identity(&session.token)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(auth.token_identity(&token).await.unwrap().is_none());
+        assert!(db.get_api_tokens("alice").await.unwrap().is_empty());
+
+        // Credentials issued while disabled are refused as well.
+        db.create_session(
+            &hash_token("dds_late"),
+            "alice",
+            Utc::now() + ChronoDuration::hours(1),
+        )
+        .await
+        .unwrap();
+        assert!(auth.session_identity("dds_late").await.unwrap().is_none());
+        assert!(matches!(
+            auth.login("alice", "correct horse").await,
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+
+    #[test]
+    fn test_bind_address_requires_auth() {
+        let public: SocketAddr = "0.0.0.0:3000".parse().unwrap();
+        let local: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        assert!(check_bind_address(&public, false).is_err());
+        assert!(check_bind_address(&public, true).is_ok());
+        assert!(check_bind_address(&local, false).is_ok());
+    }
+}
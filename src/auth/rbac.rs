@@ -0,0 +1,321 @@
+//! Role-based access control.
+//!
+//! Users are assigned roles, each a named set of permissions. An assignment
+//! can be scoped to check tags and data sources, in which case its
+//! permissions only apply to checks carrying one of the tags or using one
+//! of the data sources, and to those data sources themselves. Everything
+//! here is plain data, so the model can be tested without a server.
+//!
+//! `route_requirement` lists the permission needed by every API route. It is
+//! enforced by the authentication middleware; handlers of scoped routes
+//! then check the individual checks or data sources they touch.
+
+use crate::db::Db;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Permission {
+    /// See checks, their results and alerts.
+    View,
+    /// Run checks and act on their results (acknowledge, annotate, silence).
+    Run,
+    /// Edit connection profiles, data sources, unix groups and maintenance windows.
+    ManageConnections,
+    /// List, write and delete secrets.
+    ManageSecrets,
+    /// Everything, including users, roles and notification channels.
+    Admin,
+}
+
+impl Permission {
+    /// Whether holding `self` grants `other`.
+    pub fn implies(self, other: Permission) -> bool {
+        self == other || self == Permission::Admin
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Permission::View => "view",
+            Permission::Run => "run",
+            Permission::ManageConnections => "manage-connections",
+            Permission::ManageSecrets => "manage-secrets",
+            Permission::Admin => "admin",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Limits a role assignment to some checks and data sources. An empty
+/// scope is global.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GrantScope {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub data_sources: Vec<String>,
+}
+
+impl GrantScope {
+    pub fn is_global(&self) -> bool {
+        self.tags.is_empty() && self.data_sources.is_empty()
+    }
+}
+
+/// What a permission is being checked against.
+#[derive(Debug, Clone, Copy)]
+pub enum Target<'a> {
+    /// Operations not tied to a check or data source. Only global grants
+    /// cover them.
+    Global,
+    Check {
+        tags: &'a [String],
+        data_sources: &'a [String],
+    },
+    DataSource(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Grant {
+    pub permission: Permission,
+    #[serde(default)]
+    pub scope: GrantScope,
+}
+
+impl Grant {
+    pub fn global(permission: Permission) -> Self {
+        Self {
+            permission,
+            scope: GrantScope::default(),
+        }
+    }
+
+    pub fn covers(&self, permission: Permission, target: &Target) -> bool {
+        if !self.permission.implies(permission) {
+            return false;
+        }
+        if self.scope.is_global() {
+            return true;
+        }
+        match target {
+            Target::Global => false,
+            Target::Check { tags, data_sources } => {
+                tags.iter().any(|t| self.scope.tags.contains(t))
+                    || data_sources
+                        .iter()
+                        .any(|ds| self.scope.data_sources.contains(ds))
+            }
+            Target::DataSource(name) => self.scope.data_sources.iter().any(|ds| ds == name),
+        }
+    }
+}
+
+pub fn is_allowed(grants: &[Grant], permission: Permission, target: &Target) -> bool {
+    grants.iter().any(|g| g.covers(permission, target))
+}
+
+/// Whether any grant, scoped or not, carries `permission`.
+pub fn holds(grants: &[Grant], permission: Permission) -> bool {
+    grants.iter().any(|g| g.permission.implies(permission))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Role {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoleAssignment {
+    pub role: String,
+    #[serde(default)]
+    pub scope: GrantScope,
+}
+
+/// The grants of a user, from all of their role assignments.
+pub async fn load_grants(db: &Db, username: &str) -> anyhow::Result<Vec<Grant>> {
+    let mut grants = Vec::new();
+    for (permissions, scope) in db.get_user_permissions(username).await? {
+        let permissions: Vec<Permission> = serde_json::from_value(permissions)?;
+        let scope: GrantScope = serde_json::from_value(scope)?;
+        grants.extend(permissions.into_iter().map(|permission| Grant {
+            permission,
+            scope: scope.clone(),
+        }));
+    }
+    Ok(grants)
+}
+
+/// Permission needed to call an API route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Requirement {
+    pub permission: Permission,
+    /// Whether a scoped grant is enough to reach the handler, which then
+    /// checks the individual checks or data sources.
+    pub scoped: bool,
+}
+
+/// The permission required for `method` on the route pattern `route`
+/// (e.g. `/api/checks/:id/execute`). `None` means any authenticated caller
+/// may use it. Routes missing from the table require `admin`.
+pub fn route_requirement(method: &str, route: &str) -> Option<Requirement> {
+    use Permission::*;
+
+    let read = method == "GET" || method == "HEAD";
+    let (permission, scoped) = match route {
+        "/api/auth/me" | "/api/auth/logout" | "/api/auth/tokens" | "/api/auth/tokens/:id" => {
+            return None
+        }
+        "/api/checks"
+        | "/api/check-statuses"
+        | "/api/history"
+        | "/api/alerts"
+        | "/api/results/:id/attempts"
+        | "/api/silences" => (View, true),
+        "/api/runs/:id" | "/api/results/:id/annotations" | "/api/silences/:id" if read => {
+            (View, true)
+        }
+        "/api/runs/:id" | "/api/results/:id/annotations" | "/api/silences/:id" => (Run, true),
+        "/api/checks/run-all"
+        | "/api/checks/:id/execute"
+        | "/api/groups/:group/run"
+        | "/api/checks/:id/acknowledge"
+        | "/api/checks/:id/silence" => (Run, true),
+        "/api/maintenance-windows" | "/api/environments" if read => (View, false),
+        "/api/maintenance-windows"
+        | "/api/maintenance-windows/:id"
+        | "/api/connections"
+        | "/api/connections/:name"
+        | "/api/unix-groups"
+        | "/api/unix-groups/:name"
+        | "/api/environments"
+        | "/api/environments/:name"
+        | "/api/environments/:name/overrides"
+        | "/api/connections/:name/environments/:env" => (ManageConnections, false),
+        "/api/data-sources"
+        | "/api/data-sources/:name"
+        | "/api/data-sources/:name/environments/:env" => (ManageConnections, true),
+        "/api/secrets" | "/api/secrets/:key" => (ManageSecrets, false),
+        _ => (Admin, false),
+    };
+    Some(Requirement { permission, scoped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(t: &[&str]) -> Vec<String> {
+        t.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_scoped_grants() {
+        let finance_runner = Grant {
+            permission: Permission::Run,
+            scope: GrantScope {
+                tags: tags(&["finance"]),
+                data_sources: vec![],
+            },
+        };
+        let grants = vec![Grant::global(Permission::View), finance_runner];
+
+        let finance = tags(&["finance"]);
+        let marketing = tags(&["marketing"]);
+        let finance_check = Target::Check {
+            tags: &finance,
+            data_sources: &[],
+        };
+        let marketing_check = Target::Check {
+            tags: &marketing,
+            data_sources: &[],
+        };
+
+        assert!(is_allowed(&grants, Permission::View, &marketing_check));
+        assert!(is_allowed(&grants, Permission::Run, &finance_check));
+        assert!(!is_allowed(&grants, Permission::Run, &marketing_check));
+        assert!(!is_allowed(&grants, Permission::Run, &Target::Global));
+        assert!(holds(&grants, Permission::Run));
+        assert!(!holds(&grants, Permission::ManageSecrets));
+    }
+
+    #[test]
+    fn test_data_source_scope() {
+        let grants = vec![Grant {
+            permission: Permission::ManageConnections,
+            scope: GrantScope {
+                tags: vec![],
+                data_sources: tags(&["warehouse"]),
+            },
+        }];
+        let uses_warehouse = tags(&["warehouse"]);
+
+        assert!(is_allowed(
+            &grants,
+            Permission::ManageConnections,
+            &Target::DataSource("warehouse")
+        ));
+        assert!(!is_allowed(
+            &grants,
+            Permission::ManageConnections,
+            &Target::DataSource("crm")
+        ));
+        assert!(is_allowed(
+            &grants,
+            Permission::ManageConnections,
+            &Target::Check {
+                tags: &[],
+                data_sources: &uses_warehouse
+            }
+        ));
+    }
+
+    #[test]
+    fn test_admin_implies_everything() {
+        let grants = vec![Grant::global(Permission::Admin)];
+        for permission in [
+            Permission::View,
+            Permission::Run,
+            Permission::ManageConnections,
+            Permission::ManageSecrets,
+        ] {
+            assert!(is_allowed(&grants, permission, &Target::Global));
+        }
+    }
+
+    #[test]
+    fn test_route_requirements() {
+        let req = |m, r| route_requirement(m, r).map(|r| r.permission);
+        assert_eq!(req("GET", "/api/checks"), Some(Permission::View));
+        assert_eq!(
+            req("POST", "/api/checks/:id/execute"),
+            Some(Permission::Run)
+        );
+        assert_eq!(req("GET", "/api/secrets"), Some(Permission::ManageSecrets));
+        assert_eq!(
+            req("DELETE", "/api/connections/:name"),
+            Some(Permission::ManageConnections)
+        );
+        assert_eq!(
+            req("GET", "/api/results/:id/annotations"),
+            Some(Permission::View)
+        );
+        assert_eq!(
+            req("POST", "/api/results/:id/annotations"),
+            Some(Permission::Run)
+        );
+        assert_eq!(req("GET", "/api/environments"), Some(Permission::View));
+        assert_eq!(
+            req("PUT", "/api/connections/:name/environments/:env"),
+            Some(Permission::ManageConnections)
+        );
+        assert_eq!(req("POST", "/api/webhooks"), Some(Permission::Admin));
+        assert_eq!(req("GET", "/api/some-new-route"), Some(Permission::Admin));
+        assert_eq!(req("GET", "/api/auth/me"), None);
+    }
+}
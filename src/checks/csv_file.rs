@@ -0,0 +1,492 @@
+//! Checks the content of delimited text files.
+//!
+//! Files are streamed record by record, so their size is not limited by
+//! memory. Files ending in `.gz` are decompressed on the fly, and input in
+//! other encodings is transcoded to UTF-8 before parsing.
+
+use super::file::{blocking, date_parameter, matching_files, resolve_path};
+use super::{
+    CheckContext, CheckError, CheckMetadata, CheckResult, CheckStatus, DataCheck,
+    ParameterDefinition,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use flate2::read::MultiGzDecoder;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Number of violating rows kept in the result details by default.
+const DEFAULT_SAMPLE_SIZE: usize = 10;
+
+/// The values a column may hold. Empty fields are nulls and are checked
+/// against `ColumnRule::nullable` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColumnType {
+    String,
+    Integer,
+    Float,
+    /// `true`/`false` or `1`/`0`, in any case.
+    Boolean,
+    /// `YYYY-MM-DD`.
+    Date,
+    /// RFC 3339, or `YYYY-MM-DD HH:MM:SS` without a time zone.
+    Timestamp,
+}
+
+impl ColumnType {
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            ColumnType::String => true,
+            ColumnType::Integer => value.parse::<i64>().is_ok(),
+            ColumnType::Float => value.parse::<f64>().is_ok(),
+            ColumnType::Boolean => ["true", "false", "1", "0"]
+                .iter()
+                .any(|b| value.eq_ignore_ascii_case(b)),
+            ColumnType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+            ColumnType::Timestamp => {
+                DateTime::parse_from_rfc3339(value).is_ok()
+                    || NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").is_ok()
+            }
+        }
+    }
+}
+
+/// Type and nullability of a named column.
+#[derive(Debug, Clone)]
+pub struct ColumnRule {
+    pub name: String,
+    pub column_type: ColumnType,
+    pub nullable: bool,
+}
+
+impl ColumnRule {
+    /// A nullable column of `column_type`.
+    pub fn new(name: impl Into<String>, column_type: ColumnType) -> Self {
+        Self {
+            name: name.into(),
+            column_type,
+            nullable: true,
+        }
+    }
+
+    pub fn not_null(mut self) -> Self {
+        self.nullable = false;
+        self
+    }
+}
+
+/// Validates the content of the CSV files matching the path of a `file`
+/// connection profile. The path supports the placeholders and wildcards of
+/// `FileCheck`; row counts are summed over all matched files.
+///
+/// The first row of every file is its header.
+#[derive(Clone)]
+pub struct CsvCheck {
+    id: String,
+    description: String,
+    connection: String,
+    delimiter: u8,
+    quote: u8,
+    encoding: String,
+    header: Option<Vec<String>>,
+    min_rows: Option<u64>,
+    max_rows: Option<u64>,
+    columns: Vec<ColumnRule>,
+    sample_size: usize,
+    metadata: CheckMetadata,
+}
+
+impl CsvCheck {
+    pub fn new(id: impl Into<String>, connection: impl Into<String>) -> Self {
+        let connection = connection.into();
+        Self {
+            id: id.into(),
+            description: format!("Validates the CSV content of {}", connection),
+            connection,
+            delimiter: b',',
+            quote: b'"',
+            encoding: "utf-8".to_string(),
+            header: None,
+            min_rows: None,
+            max_rows: None,
+            columns: vec![],
+            sample_size: DEFAULT_SAMPLE_SIZE,
+            metadata: CheckMetadata::default(),
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn quote(mut self, quote: u8) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Encoding of the files by WHATWG label, e.g. `windows-1252` or
+    /// `utf-16le`. A byte order mark takes precedence.
+    pub fn encoding(mut self, label: impl Into<String>) -> Self {
+        self.encoding = label.into();
+        self
+    }
+
+    /// Columns the header must list, in order.
+    pub fn header<S: Into<String>>(mut self, columns: impl IntoIterator<Item = S>) -> Self {
+        self.header = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Fewest data rows, not counting headers.
+    pub fn min_rows(mut self, rows: u64) -> Self {
+        self.min_rows = Some(rows);
+        self
+    }
+
+    /// Most data rows, not counting headers.
+    pub fn max_rows(mut self, rows: u64) -> Self {
+        self.max_rows = Some(rows);
+        self
+    }
+
+    pub fn column(mut self, rule: ColumnRule) -> Self {
+        self.columns.push(rule);
+        self
+    }
+
+    /// How many violating rows the result details list.
+    pub fn sample_size(mut self, rows: usize) -> Self {
+        self.sample_size = rows;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: CheckMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    fn inspect(&self, pattern: &str) -> Result<CheckResult, CheckError> {
+        let encoding = Encoding::for_label(self.encoding.as_bytes()).ok_or_else(|| {
+            CheckError::ConfigError(format!("Unknown encoding {}", self.encoding))
+        })?;
+        let paths = matching_files(pattern)?;
+        if paths.is_empty() {
+            return Ok(CheckResult {
+                status: CheckStatus::Failure,
+                message: format!("No files match {}", pattern),
+                details: Some(json!({ "pattern": pattern, "files": [] })),
+            });
+        }
+
+        let mut scan = Scan::default();
+        for path in &paths {
+            self.scan_file(path, encoding, &mut scan)?;
+        }
+
+        if let Some(min_rows) = self.min_rows {
+            if scan.rows < min_rows {
+                scan.problems.push(format!(
+                    "{} rows, expected at least {}",
+                    scan.rows, min_rows
+                ));
+            }
+        }
+        if let Some(max_rows) = self.max_rows {
+            if scan.rows > max_rows {
+                scan.problems
+                    .push(format!("{} rows, expected at most {}", scan.rows, max_rows));
+            }
+        }
+        if scan.invalid_rows > 0 {
+            scan.problems.push(format!(
+                "{} of {} rows violate the column rules",
+                scan.invalid_rows, scan.rows
+            ));
+        }
+
+        let (status, message) = if scan.problems.is_empty() {
+            (
+                CheckStatus::Success,
+                format!("{} rows in {} files are valid", scan.rows, paths.len()),
+            )
+        } else {
+            (CheckStatus::Failure, scan.problems.join("; "))
+        };
+        Ok(CheckResult {
+            status,
+            message,
+            details: Some(json!({
+                "pattern": pattern,
+                "files": paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>(),
+                "rows": scan.rows,
+                "invalid_rows": scan.invalid_rows,
+                "problems": scan.problems,
+                "sample": scan.sample,
+            })),
+        })
+    }
+
+    fn scan_file(
+        &self,
+        path: &Path,
+        encoding: &'static Encoding,
+        scan: &mut Scan,
+    ) -> Result<(), CheckError> {
+        let io_error =
+            |e: std::io::Error| CheckError::ExecutionError(format!("{}: {}", path.display(), e));
+        let file = File::open(path).map_err(io_error)?;
+        let input: Box<dyn Read> = if path.extension().is_some_and(|e| e == "gz") {
+            Box::new(MultiGzDecoder::new(BufReader::new(file)))
+        } else {
+            Box::new(file)
+        };
+        let decoded = DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding))
+            .build(input);
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .flexible(true)
+            .from_reader(BufReader::new(decoded));
+
+        let csv_error =
+            |e: csv::Error| CheckError::ExecutionError(format!("{}: {}", path.display(), e));
+        let header: Vec<String> = reader
+            .headers()
+            .map_err(csv_error)?
+            .iter()
+            .map(str::to_string)
+            .collect();
+        if let Some(expected) = &self.header {
+            if &header != expected {
+                scan.problems.push(format!(
+                    "{} has header [{}], expected [{}]",
+                    path.display(),
+                    header.join(", "),
+                    expected.join(", ")
+                ));
+            }
+        }
+
+        let mut rules = Vec::new();
+        for rule in &self.columns {
+            match header.iter().position(|c| *c == rule.name) {
+                Some(index) => rules.push((index, rule)),
+                None => {
+                    scan.problems
+                        .push(format!("{} has no column {}", path.display(), rule.name))
+                }
+            }
+        }
+
+        let mut record = csv::StringRecord::new();
+        while reader.read_record(&mut record).map_err(csv_error)? {
+            scan.rows += 1;
+            let mut errors = Vec::new();
+            if record.len() != header.len() {
+                errors.push(format!(
+                    "expected {} fields, got {}",
+                    header.len(),
+                    record.len()
+                ));
+            }
+            for (index, rule) in &rules {
+                match record.get(*index).filter(|v| !v.is_empty()) {
+                    None if !rule.nullable => errors.push(format!("{} is null", rule.name)),
+                    Some(value) if !rule.column_type.accepts(value) => errors.push(format!(
+                        "{} is not a valid {:?}: {:?}",
+                        rule.name, rule.column_type, value
+                    )),
+                    _ => {}
+                }
+            }
+            if errors.is_empty() {
+                continue;
+            }
+            scan.invalid_rows += 1;
+            if scan.sample.len() < self.sample_size {
+                scan.sample.push(json!({
+                    "file": path.display().to_string(),
+                    "line": record.position().map(|p| p.line()),
+                    "row": record.iter().collect::<Vec<_>>(),
+                    "errors": errors,
+                }));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Running totals over the files of one execution.
+#[derive(Default)]
+struct Scan {
+    rows: u64,
+    invalid_rows: u64,
+    problems: Vec<String>,
+    sample: Vec<Value>,
+}
+
+#[async_trait]
+impl DataCheck for CsvCheck {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Vec<ParameterDefinition> {
+        vec![date_parameter()]
+    }
+
+    fn metadata(&self) -> CheckMetadata {
+        self.metadata.clone()
+    }
+
+    fn connections(&self) -> Vec<String> {
+        vec![self.connection.clone()]
+    }
+
+    async fn execute(
+        &self,
+        ctx: &dyn CheckContext,
+        params: &HashMap<String, Value>,
+    ) -> Result<CheckResult, CheckError> {
+        let pattern = resolve_path(ctx, &self.connection, params).await?;
+        let check = self.clone();
+        blocking(move || check.inspect(&pattern)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::testing::FileContext;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    const REPORT: &str = "id,region,amount,day\n\
+                          1,emea,10.5,2024-03-09\n\
+                          2,,abc,2024-03-09\n\
+                          ,apac,7,09/03/2024\n\
+                          4,amer,1\n";
+
+    fn orders_check() -> CsvCheck {
+        CsvCheck::new("report_valid", "CSV Data Export")
+            .header(["id", "region", "amount", "day"])
+            .column(ColumnRule::new("id", ColumnType::Integer).not_null())
+            .column(ColumnRule::new("amount", ColumnType::Float))
+            .column(ColumnRule::new("day", ColumnType::Date))
+    }
+
+    #[tokio::test]
+    async fn test_csv_check_reports_violations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daily_report.csv");
+        std::fs::write(&path, REPORT).unwrap();
+        let ctx = FileContext(path.display().to_string());
+
+        let result = orders_check()
+            .min_rows(5)
+            .sample_size(2)
+            .execute(&ctx, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        assert_eq!(
+            result.message,
+            "4 rows, expected at least 5; 3 of 4 rows violate the column rules"
+        );
+        let details = result.details.unwrap();
+        assert_eq!(details["invalid_rows"], 3);
+        assert_eq!(
+            details["sample"],
+            json!([
+                {
+                    "file": path.display().to_string(),
+                    "line": 3,
+                    "row": ["2", "", "abc", "2024-03-09"],
+                    "errors": ["amount is not a valid Float: \"abc\""],
+                },
+                {
+                    "file": path.display().to_string(),
+                    "line": 4,
+                    "row": ["", "apac", "7", "09/03/2024"],
+                    "errors": ["id is null", "day is not a valid Date: \"09/03/2024\""],
+                },
+            ])
+        );
+
+        let result = CsvCheck::new("report_valid", "CSV Data Export")
+            .header(["id", "amount"])
+            .max_rows(4)
+            .execute(&ctx, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        assert!(result
+            .message
+            .contains("has header [id, region, amount, day], expected [id, amount]"));
+        assert!(result
+            .message
+            .ends_with("1 of 4 rows violate the column rules"));
+    }
+
+    #[tokio::test]
+    async fn test_csv_check_gzip_and_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.csv.gz");
+        // "Zürich" in Windows-1252, semicolon separated with single quotes.
+        let content = b"city;note\n'Z\xfcrich';'a;b'\n";
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap();
+        let ctx = FileContext(format!("{}/*.csv.gz", dir.path().display()));
+
+        let check = CsvCheck::new("report_valid", "CSV Data Export")
+            .delimiter(b';')
+            .quote(b'\'')
+            .encoding("windows-1252")
+            .header(["city", "note"])
+            .column(ColumnRule::new("city", ColumnType::String).not_null())
+            .min_rows(1)
+            .max_rows(1);
+        let result = check.execute(&ctx, &HashMap::new()).await.unwrap();
+        assert!(
+            matches!(result.status, CheckStatus::Success),
+            "{}",
+            result.message
+        );
+        assert_eq!(result.message, "1 rows in 1 files are valid");
+
+        let result = check
+            .encoding("klingon")
+            .execute(&ctx, &HashMap::new())
+            .await;
+        assert!(matches!(result, Err(CheckError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_column_types() {
+        assert!(ColumnType::Integer.accepts("-12"));
+        assert!(!ColumnType::Integer.accepts("1.5"));
+        assert!(ColumnType::Boolean.accepts("TRUE"));
+        assert!(!ColumnType::Boolean.accepts("yes"));
+        assert!(ColumnType::Timestamp.accepts("2024-03-09T10:00:00Z"));
+        assert!(ColumnType::Timestamp.accepts("2024-03-09 10:00:00"));
+        assert!(!ColumnType::Timestamp.accepts("2024-03-09"));
+    }
+}
@@ -1,4 +1,7 @@
-use super::{CheckContext, CheckError, CheckResult, CheckStatus, DataCheck, ParameterDefinition};
+use super::{
+    CheckContext, CheckError, CheckMetadata, CheckResult, CheckStatus, DataCheck,
+    ParameterDefinition, Severity,
+};
 use async_trait::async_trait;
 use serde_json::Value;
 use std::collections::HashMap;
@@ -23,6 +26,24 @@ impl DataCheck for ExampleCheck {
         }]
     }
 
+    fn connections(&self) -> Vec<String> {
+        vec!["default_db".to_string()]
+    }
+
+    fn metadata(&self) -> CheckMetadata {
+        CheckMetadata {
+            tags: vec!["example".to_string()],
+            groups: vec!["examples".to_string()],
+            severity: Some(Severity::Low),
+            documentation: Some(
+                "Template for new checks. Resolves the `default_db` connection and \
+                 requires a `target_date` parameter."
+                    .to_string(),
+            ),
+            ..Default::default()
+        }
+    }
+
     async fn execute(
         &self,
         ctx: &dyn CheckContext,
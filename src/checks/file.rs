@@ -0,0 +1,481 @@
+//! Checks that files delivered by upstream systems are present and complete.
+//!
+//! A `FileCheck` reads the path of a `file` connection profile, which may
+//! contain glob wildcards and `{{date:FORMAT}}` placeholders, e.g.
+//! `/exports/{{date:%Y%m%d}}/*.csv`. Placeholders are rendered with the
+//! `date` parameter (today in UTC by default) using chrono's strftime syntax;
+//! `{{date}}` is short for `{{date:%Y-%m-%d}}`.
+
+use super::{
+    CheckContext, CheckError, CheckMetadata, CheckResult, CheckStatus, DataCheck,
+    ParameterDefinition,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Renders the `{{date:FORMAT}}` placeholders of a path template.
+pub fn render_path(template: &str, date: NaiveDate) -> Result<String, CheckError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{date") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start + 2..start + len];
+        let format = match placeholder.strip_prefix("date") {
+            Some("") => "%Y-%m-%d",
+            Some(format) => match format.strip_prefix(':') {
+                Some(format) => format,
+                None => {
+                    return Err(CheckError::ConfigError(format!(
+                        "Invalid placeholder {{{{{}}}}} in {}",
+                        placeholder, template
+                    )))
+                }
+            },
+            None => unreachable!(),
+        };
+        rendered.push_str(&rest[..start]);
+        // Formatting fails, rather than panicking as `to_string` would, on
+        // invalid specifiers and on those needing a time, like `%H`.
+        write!(rendered, "{}", date.format(format)).map_err(|_| {
+            CheckError::ConfigError(format!("Invalid date format {} in {}", format, template))
+        })?;
+        rest = &rest[start + len + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// The `date` parameter of checks reading a templated path.
+pub(crate) fn date_parameter() -> ParameterDefinition {
+    ParameterDefinition {
+        name: "date".to_string(),
+        description: "Date (YYYY-MM-DD) rendered into {{date:...}} placeholders of the path"
+            .to_string(),
+        default: Some("today".to_string()),
+    }
+}
+
+/// The value of the `date` parameter.
+pub(crate) fn date_param(params: &HashMap<String, Value>) -> Result<NaiveDate, CheckError> {
+    match params.get("date").and_then(Value::as_str) {
+        None | Some("today") => Ok(Utc::now().date_naive()),
+        Some(date) => date.parse().map_err(|_| {
+            CheckError::ConfigError(format!("Invalid date {}, expected YYYY-MM-DD", date))
+        }),
+    }
+}
+
+/// The path of the `connection` profile, rendered for the `date` parameter.
+pub(crate) async fn resolve_path(
+    ctx: &dyn CheckContext,
+    connection: &str,
+    params: &HashMap<String, Value>,
+) -> Result<String, CheckError> {
+    let date = date_param(params)?;
+    let template = ctx
+        .get_connection_string(connection)
+        .await
+        .map_err(|e| CheckError::ExecutionError(e.to_string()))?;
+    render_path(&template, date)
+}
+
+/// The files matching `pattern`, in path order. Directories are skipped.
+pub(crate) fn matching_files(pattern: &str) -> Result<Vec<PathBuf>, CheckError> {
+    let paths = glob::glob(pattern)
+        .map_err(|e| CheckError::ConfigError(format!("Invalid file pattern {}: {}", pattern, e)))?;
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(|e| CheckError::ExecutionError(e.to_string()))?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Runs blocking file access off the async runtime; files may sit on slow
+/// network mounts.
+pub(crate) async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, CheckError> + Send + 'static,
+) -> Result<T, CheckError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| CheckError::ExecutionError(e.to_string()))?
+}
+
+/// Checks the files matching the path of a `file` connection profile.
+///
+/// By default at least one file must match; the other expectations are
+/// opt-in through the builder methods.
+#[derive(Clone)]
+pub struct FileCheck {
+    id: String,
+    description: String,
+    connection: String,
+    min_files: usize,
+    max_files: Option<usize>,
+    min_size: Option<u64>,
+    max_age: Option<Duration>,
+    success_marker: Option<String>,
+    metadata: CheckMetadata,
+}
+
+impl FileCheck {
+    pub fn new(id: impl Into<String>, connection: impl Into<String>) -> Self {
+        let connection = connection.into();
+        Self {
+            id: id.into(),
+            description: format!("Checks the files of {}", connection),
+            connection,
+            min_files: 1,
+            max_files: None,
+            min_size: None,
+            max_age: None,
+            success_marker: None,
+            metadata: CheckMetadata::default(),
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Fewest files that must match. Zero allows an empty match.
+    pub fn min_files(mut self, min_files: usize) -> Self {
+        self.min_files = min_files;
+        self
+    }
+
+    /// Most files that may match.
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+
+    /// Smallest size in bytes of every matched file, to catch truncated or
+    /// empty deliveries.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = Some(bytes);
+        self
+    }
+
+    /// How long ago every matched file may have been modified.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Name of a marker file, e.g. `_SUCCESS`, that must exist next to the
+    /// matched files once the producer has finished writing them. Markers are
+    /// not counted as matched files.
+    pub fn success_marker(mut self, name: impl Into<String>) -> Self {
+        self.success_marker = Some(name.into());
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: CheckMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Matches `pattern` and compares the files found with the expectations.
+    fn inspect(&self, pattern: &str, now: DateTime<Utc>) -> Result<CheckResult, CheckError> {
+        let mut files = Vec::new();
+        let mut problems = Vec::new();
+        let mut directories = BTreeSet::new();
+        for path in matching_files(pattern)? {
+            let metadata = std::fs::metadata(&path).map_err(|e| {
+                CheckError::ExecutionError(format!("Cannot read {}: {}", path.display(), e))
+            })?;
+            if self.is_marker(&path) {
+                continue;
+            }
+            let modified: DateTime<Utc> = metadata
+                .modified()
+                .map_err(|e| CheckError::ExecutionError(e.to_string()))?
+                .into();
+
+            if let Some(min_size) = self.min_size {
+                if metadata.len() < min_size {
+                    problems.push(format!(
+                        "{} is {} bytes, expected at least {}",
+                        path.display(),
+                        metadata.len(),
+                        min_size
+                    ));
+                }
+            }
+            if let Some(max_age) = self.max_age {
+                let age = (now - modified).to_std().unwrap_or_default();
+                if age > max_age {
+                    problems.push(format!(
+                        "{} was modified {}s ago, expected within {}s",
+                        path.display(),
+                        age.as_secs(),
+                        max_age.as_secs()
+                    ));
+                }
+            }
+            if let Some(parent) = path.parent() {
+                directories.insert(parent.to_path_buf());
+            }
+            files.push(json!({
+                "path": path.display().to_string(),
+                "size": metadata.len(),
+                "modified": modified,
+            }));
+        }
+
+        if files.len() < self.min_files {
+            problems.insert(
+                0,
+                format!(
+                    "{} files match {}, expected at least {}",
+                    files.len(),
+                    pattern,
+                    self.min_files
+                ),
+            );
+        }
+        if let Some(max_files) = self.max_files {
+            if files.len() > max_files {
+                problems.insert(
+                    0,
+                    format!(
+                        "{} files match {}, expected at most {}",
+                        files.len(),
+                        pattern,
+                        max_files
+                    ),
+                );
+            }
+        }
+
+        let mut markers = Vec::new();
+        if let Some(marker) = &self.success_marker {
+            for directory in &directories {
+                let path = directory.join(marker);
+                if !path.is_file() {
+                    problems.push(format!("{} is missing", path.display()));
+                }
+                markers.push(path.display().to_string());
+            }
+        }
+
+        let (status, message) = if problems.is_empty() {
+            (
+                CheckStatus::Success,
+                format!("{} files match {}", files.len(), pattern),
+            )
+        } else {
+            (CheckStatus::Failure, problems.join("; "))
+        };
+        Ok(CheckResult {
+            status,
+            message,
+            details: Some(json!({
+                "pattern": pattern,
+                "files": files,
+                "markers": markers,
+                "problems": problems,
+            })),
+        })
+    }
+
+    fn is_marker(&self, path: &Path) -> bool {
+        self.success_marker
+            .as_deref()
+            .is_some_and(|marker| path.file_name().is_some_and(|name| name == marker))
+    }
+}
+
+#[async_trait]
+impl DataCheck for FileCheck {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Vec<ParameterDefinition> {
+        vec![date_parameter()]
+    }
+
+    fn metadata(&self) -> CheckMetadata {
+        self.metadata.clone()
+    }
+
+    fn connections(&self) -> Vec<String> {
+        vec![self.connection.clone()]
+    }
+
+    async fn execute(
+        &self,
+        ctx: &dyn CheckContext,
+        params: &HashMap<String, Value>,
+    ) -> Result<CheckResult, CheckError> {
+        let pattern = resolve_path(ctx, &self.connection, params).await?;
+
+        let check = self.clone();
+        blocking(move || check.inspect(&pattern, Utc::now())).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::testing::FileContext;
+
+    /// Creates `path` with `bytes` bytes.
+    fn write_file(path: PathBuf, bytes: usize) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, vec![b'x'; bytes]).unwrap();
+    }
+
+    fn date_params(date: &str) -> HashMap<String, Value> {
+        HashMap::from([("date".to_string(), json!(date))])
+    }
+
+    #[test]
+    fn test_render_path() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+        assert_eq!(
+            render_path("/exports/{{date:%Y%m%d}}/*.csv", date).unwrap(),
+            "/exports/20240309/*.csv"
+        );
+        assert_eq!(
+            render_path("/exports/{{date}}/part-{{date:%d}}.csv", date).unwrap(),
+            "/exports/2024-03-09/part-09.csv"
+        );
+        assert_eq!(
+            render_path("/data/sample.txt", date).unwrap(),
+            "/data/sample.txt"
+        );
+        assert!(matches!(
+            render_path("/exports/{{date:%Q}}", date),
+            Err(CheckError::ConfigError(_))
+        ));
+        assert!(matches!(
+            render_path("/exports/{{date:%H}}", date),
+            Err(CheckError::ConfigError(_))
+        ));
+        assert!(matches!(
+            render_path("/exports/{{dated}}", date),
+            Err(CheckError::ConfigError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_file_check_counts_and_markers() {
+        let dir = tempfile::tempdir().unwrap();
+        let day = dir.path().join("20240309");
+        write_file(day.join("a.csv"), 10);
+        write_file(day.join("b.csv"), 10);
+        write_file(day.join("notes.txt"), 10);
+        let ctx = FileContext(format!(
+            "{}/{{{{date:%Y%m%d}}}}/*.csv",
+            dir.path().display()
+        ));
+
+        let check = FileCheck::new("exports_present", "exports")
+            .min_files(2)
+            .max_files(2)
+            .success_marker("_SUCCESS");
+        let result = check
+            .execute(&ctx, &date_params("2024-03-09"))
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        assert!(result.message.ends_with("_SUCCESS is missing"));
+
+        write_file(day.join("_SUCCESS"), 0);
+        let result = check
+            .execute(&ctx, &date_params("2024-03-09"))
+            .await
+            .unwrap();
+        assert!(
+            matches!(result.status, CheckStatus::Success),
+            "{}",
+            result.message
+        );
+        let details = result.details.unwrap();
+        let files: Vec<_> = details["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["path"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                day.join("a.csv").display().to_string(),
+                day.join("b.csv").display().to_string()
+            ]
+        );
+
+        let result = check
+            .execute(&ctx, &date_params("2024-03-10"))
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        assert!(result.message.starts_with("0 files match"));
+
+        let result = FileCheck::new("exports_present", "exports")
+            .max_files(1)
+            .execute(&ctx, &date_params("2024-03-09"))
+            .await
+            .unwrap();
+        assert!(result.message.contains("expected at most 1"));
+    }
+
+    #[tokio::test]
+    async fn test_file_check_size_and_age() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path().join("daily_report.csv"), 100);
+        let ctx = FileContext(dir.path().join("daily_report.csv").display().to_string());
+
+        let check = FileCheck::new("report_fresh", "exports")
+            .min_size(50)
+            .max_age(Duration::from_secs(3600));
+        let result = check.execute(&ctx, &HashMap::new()).await.unwrap();
+        assert!(
+            matches!(result.status, CheckStatus::Success),
+            "{}",
+            result.message
+        );
+
+        let result = check
+            .inspect(&ctx.0, Utc::now() + chrono::Duration::hours(2))
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        assert!(result.message.contains("expected within 3600s"));
+
+        let result = FileCheck::new("report_fresh", "exports")
+            .min_size(101)
+            .execute(&ctx, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(result
+            .message
+            .contains("is 100 bytes, expected at least 101"));
+    }
+
+    #[tokio::test]
+    async fn test_file_check_invalid_date() {
+        let ctx = FileContext("/exports/{{date}}".to_string());
+        let result = FileCheck::new("exports_present", "exports")
+            .execute(&ctx, &date_params("yesterday"))
+            .await;
+        assert!(matches!(result, Err(CheckError::ConfigError(_))));
+    }
+}
@@ -0,0 +1,412 @@
+//! Validates JSON and newline-delimited JSON files against a JSON Schema.
+//!
+//! NDJSON files (`.ndjson`, `.jsonl`) are streamed line by line and every
+//! line is a record. Other files are parsed whole: a top-level array holds
+//! one record per element, anything else is a single record. Files ending
+//! in `.gz` are decompressed on the fly.
+
+use super::file::{blocking, date_parameter, matching_files, resolve_path};
+use super::{
+    CheckContext, CheckError, CheckMetadata, CheckResult, CheckStatus, DataCheck,
+    ParameterDefinition,
+};
+use async_trait::async_trait;
+use flate2::read::MultiGzDecoder;
+use jsonschema::Validator;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Number of invalid records kept in the result details by default.
+const DEFAULT_SAMPLE_SIZE: usize = 10;
+
+/// Validates the records of the JSON files matching the path of a `file`
+/// connection profile against a schema stored with the check.
+///
+/// By default a single invalid record fails the check. With a threshold,
+/// invalid records within it make the result a warning instead.
+#[derive(Clone)]
+pub struct JsonSchemaCheck {
+    id: String,
+    description: String,
+    connection: String,
+    schema: Value,
+    max_invalid: u64,
+    max_invalid_fraction: Option<f64>,
+    sample_size: usize,
+    metadata: CheckMetadata,
+}
+
+impl JsonSchemaCheck {
+    pub fn new(id: impl Into<String>, connection: impl Into<String>, schema: Value) -> Self {
+        let connection = connection.into();
+        Self {
+            id: id.into(),
+            description: format!("Validates the JSON records of {}", connection),
+            connection,
+            schema,
+            max_invalid: 0,
+            max_invalid_fraction: None,
+            sample_size: DEFAULT_SAMPLE_SIZE,
+            metadata: CheckMetadata::default(),
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Most invalid records tolerated.
+    pub fn max_invalid(mut self, records: u64) -> Self {
+        self.max_invalid = records;
+        self
+    }
+
+    /// Largest share of invalid records tolerated, e.g. `0.001` for 0.1%.
+    /// Takes precedence over `max_invalid`.
+    pub fn max_invalid_fraction(mut self, fraction: f64) -> Self {
+        self.max_invalid_fraction = Some(fraction);
+        self
+    }
+
+    /// How many invalid records the result details list.
+    pub fn sample_size(mut self, records: usize) -> Self {
+        self.sample_size = records;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: CheckMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Validates records that did not come from a file, e.g. the elements
+    /// of an API response body read with `HttpResponse::json`, with the same
+    /// thresholds. Invalid records are located by their position.
+    pub fn validate_records<'a>(
+        &self,
+        records: impl IntoIterator<Item = &'a Value>,
+    ) -> Result<CheckResult, CheckError> {
+        let mut tally = self.tally()?;
+        for (index, record) in records.into_iter().enumerate() {
+            tally.validate(record, json!({ "index": index }));
+        }
+        Ok(self.result(tally, json!({})))
+    }
+
+    fn inspect(&self, pattern: &str) -> Result<CheckResult, CheckError> {
+        let mut tally = self.tally()?;
+        let paths = matching_files(pattern)?;
+        if paths.is_empty() {
+            return Ok(CheckResult {
+                status: CheckStatus::Failure,
+                message: format!("No files match {}", pattern),
+                details: Some(json!({ "pattern": pattern, "files": [] })),
+            });
+        }
+
+        for path in &paths {
+            tally.scan_file(path)?;
+        }
+        let files: Vec<_> = paths.iter().map(|p| p.display().to_string()).collect();
+        Ok(self.result(tally, json!({ "pattern": pattern, "files": files })))
+    }
+
+    fn tally(&self) -> Result<Tally, CheckError> {
+        let validator = jsonschema::validator_for(&self.schema)
+            .map_err(|e| CheckError::ConfigError(format!("Invalid JSON Schema: {}", e)))?;
+        Ok(Tally {
+            validator,
+            sample_size: self.sample_size,
+            records: 0,
+            invalid: 0,
+            sample: vec![],
+        })
+    }
+
+    /// The result for `tally` under the thresholds, with the totals and the
+    /// sample added to `details`.
+    fn result(&self, tally: Tally, mut details: Value) -> CheckResult {
+        let invalid_fraction = if tally.records == 0 {
+            0.0
+        } else {
+            tally.invalid as f64 / tally.records as f64
+        };
+        let within_threshold = match self.max_invalid_fraction {
+            Some(fraction) => invalid_fraction <= fraction,
+            None => tally.invalid <= self.max_invalid,
+        };
+        let status = match (tally.invalid, within_threshold) {
+            (0, _) => CheckStatus::Success,
+            (_, true) => CheckStatus::Warning,
+            (_, false) => CheckStatus::Failure,
+        };
+        let message = if tally.invalid == 0 {
+            format!("{} records are valid", tally.records)
+        } else {
+            format!(
+                "{} of {} records ({:.3}%) are invalid",
+                tally.invalid,
+                tally.records,
+                invalid_fraction * 100.0
+            )
+        };
+        details["records"] = tally.records.into();
+        details["invalid"] = tally.invalid.into();
+        details["invalid_fraction"] = invalid_fraction.into();
+        details["sample"] = tally.sample.into();
+        CheckResult {
+            status,
+            message,
+            details: Some(details),
+        }
+    }
+}
+
+/// Running totals over the files of one execution.
+struct Tally {
+    validator: Validator,
+    sample_size: usize,
+    records: u64,
+    invalid: u64,
+    sample: Vec<Value>,
+}
+
+impl Tally {
+    fn scan_file(&mut self, path: &Path) -> Result<(), CheckError> {
+        let io_error =
+            |e: std::io::Error| CheckError::ExecutionError(format!("{}: {}", path.display(), e));
+        let file = File::open(path).map_err(io_error)?;
+        let (input, name): (Box<dyn Read>, _) = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) if name.ends_with(".gz") => (
+                Box::new(MultiGzDecoder::new(BufReader::new(file))),
+                name.trim_end_matches(".gz"),
+            ),
+            name => (Box::new(file), name.unwrap_or_default()),
+        };
+        let file = path.display().to_string();
+
+        if name.ends_with(".ndjson") || name.ends_with(".jsonl") {
+            for (index, line) in BufReader::new(input).lines().enumerate() {
+                let line = line.map_err(io_error)?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let location = json!({ "file": file, "line": index + 1 });
+                match serde_json::from_str(&line) {
+                    Ok(record) => self.validate(&record, location),
+                    Err(e) => self.add_invalid(location, vec![parse_error(&e)]),
+                }
+            }
+            return Ok(());
+        }
+
+        match serde_json::from_reader(BufReader::new(input)) {
+            Ok(Value::Array(records)) => {
+                for (index, record) in records.iter().enumerate() {
+                    self.validate(record, json!({ "file": file, "index": index }));
+                }
+            }
+            Ok(record) => self.validate(&record, json!({ "file": file })),
+            Err(e) if e.is_io() => return Err(io_error(e.into())),
+            Err(e) => self.add_invalid(json!({ "file": file }), vec![parse_error(&e)]),
+        }
+        Ok(())
+    }
+
+    fn validate(&mut self, record: &Value, location: Value) {
+        let errors: Vec<Value> = self
+            .validator
+            .iter_errors(record)
+            .map(|e| {
+                json!({
+                    "pointer": e.instance_path.to_string(),
+                    "schema_pointer": e.schema_path.to_string(),
+                    "message": e.to_string(),
+                })
+            })
+            .collect();
+        if errors.is_empty() {
+            self.records += 1;
+        } else {
+            self.add_invalid(location, errors);
+        }
+    }
+
+    fn add_invalid(&mut self, mut location: Value, errors: Vec<Value>) {
+        self.records += 1;
+        self.invalid += 1;
+        if self.sample.len() < self.sample_size {
+            location["errors"] = errors.into();
+            self.sample.push(location);
+        }
+    }
+}
+
+fn parse_error(e: &serde_json::Error) -> Value {
+    json!({ "pointer": "", "message": format!("Invalid JSON: {}", e) })
+}
+
+#[async_trait]
+impl DataCheck for JsonSchemaCheck {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Vec<ParameterDefinition> {
+        vec![date_parameter()]
+    }
+
+    fn metadata(&self) -> CheckMetadata {
+        self.metadata.clone()
+    }
+
+    fn connections(&self) -> Vec<String> {
+        vec![self.connection.clone()]
+    }
+
+    async fn execute(
+        &self,
+        ctx: &dyn CheckContext,
+        params: &HashMap<String, Value>,
+    ) -> Result<CheckResult, CheckError> {
+        let pattern = resolve_path(ctx, &self.connection, params).await?;
+        let check = self.clone();
+        blocking(move || check.inspect(&pattern)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::HttpResponse;
+    use crate::runner::testing::FileContext;
+
+    fn event_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "kind"],
+            "properties": {
+                "id": { "type": "integer" },
+                "kind": { "enum": ["click", "view"] },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_thresholds_and_sample() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.ndjson");
+        let mut lines: Vec<String> = (0..997)
+            .map(|id| json!({ "id": id, "kind": "view" }).to_string())
+            .collect();
+        lines.push(r#"{"id": 997, "kind": "view", "tags": ["a", 1]}"#.to_string());
+        lines.push(r#"{"kind": "scroll"}"#.to_string());
+        lines.push("{not json".to_string());
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let ctx = FileContext(path.display().to_string());
+
+        let check = JsonSchemaCheck::new("events_valid", "Event Feed", event_schema());
+        let result = check.execute(&ctx, &HashMap::new()).await.unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        assert_eq!(result.message, "3 of 1000 records (0.300%) are invalid");
+        let details = result.details.unwrap();
+        assert_eq!(details["sample"][0]["line"], 998);
+        assert_eq!(details["sample"][0]["errors"][0]["pointer"], "/tags/1");
+        let pointers: Vec<_> = details["sample"][1]["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["pointer"].as_str().unwrap())
+            .collect();
+        assert_eq!(pointers.len(), 2);
+        assert!(pointers.contains(&"/kind"));
+        assert!(pointers.contains(&""));
+        assert_eq!(details["sample"][2]["line"], 1000);
+
+        let result = check
+            .clone()
+            .max_invalid_fraction(0.005)
+            .execute(&ctx, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Warning));
+
+        let result = check
+            .max_invalid_fraction(0.001)
+            .sample_size(1)
+            .execute(&ctx, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        assert_eq!(
+            result.details.unwrap()["sample"].as_array().unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("a.json"),
+            json!([{ "id": 1, "kind": "click" }, { "id": "2", "kind": "view" }]).to_string(),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("b.json"), r#"{"id": 3, "kind": "view"}"#).unwrap();
+        let ctx = FileContext(format!("{}/*.json", dir.path().display()));
+
+        let check =
+            JsonSchemaCheck::new("events_valid", "Event Feed", event_schema()).max_invalid(1);
+        let result = check.execute(&ctx, &HashMap::new()).await.unwrap();
+        assert!(matches!(result.status, CheckStatus::Warning));
+        let details = result.details.unwrap();
+        assert_eq!(details["records"], 3);
+        assert_eq!(details["sample"][0]["index"], 1);
+        assert_eq!(details["sample"][0]["errors"][0]["pointer"], "/id");
+
+        let result = JsonSchemaCheck::new("events_valid", "Event Feed", json!({ "type": 12 }))
+            .execute(&ctx, &HashMap::new())
+            .await;
+        assert!(matches!(result, Err(CheckError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_validate_response_records() {
+        let response = HttpResponse {
+            status: 200,
+            headers: Default::default(),
+            body: json!({
+                "events": [
+                    { "id": 1, "kind": "click" },
+                    { "id": 2, "kind": "scroll" },
+                    { "id": 3, "kind": "view" },
+                ]
+            })
+            .to_string()
+            .into_bytes(),
+        };
+        let body: Value = response.json().unwrap();
+        let events = body["events"].as_array().unwrap();
+
+        let check = JsonSchemaCheck::new("events_api", "Event API", event_schema());
+        let result = check.validate_records(events).unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        assert_eq!(result.message, "1 of 3 records (33.333%) are invalid");
+        let details = result.details.unwrap();
+        assert_eq!(details["records"], 3);
+        assert_eq!(details["sample"][0]["index"], 1);
+        assert_eq!(details["sample"][0]["errors"][0]["pointer"], "/kind");
+
+        let result = check.max_invalid(1).validate_records(events).unwrap();
+        assert!(matches!(result.status, CheckStatus::Warning));
+    }
+}
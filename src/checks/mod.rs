@@ -1,19 +1,136 @@
+pub mod csv_file;
 pub mod example_check;
+pub mod file;
+pub mod json_file;
+pub mod parquet_file;
+pub mod registry;
 
-use crate::connections::ConnectionManager;
+use crate::connections::{ConnectionError, ConnectionManager, DataSource};
+use crate::http_client::HttpClient;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
+/// Timeout applied to checks that do not override `DataCheck::timeout`.
+pub const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(300);
+
 #[derive(Error, Debug)]
 pub enum CheckError {
     #[error("Execution error: {0}")]
     ExecutionError(String),
     #[error("Configuration error: {0}")]
     ConfigError(String),
+    #[error("Check timed out after {}s", .0.as_secs_f64())]
+    TimedOut(Duration),
+    #[error("Check panicked: {message}")]
+    Panicked { message: String, backtrace: String },
+}
+
+/// The variant of a `CheckError`, used to select which errors are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CheckErrorKind {
+    Execution,
+    Config,
+    TimedOut,
+    Panicked,
+}
+
+impl CheckError {
+    pub fn kind(&self) -> CheckErrorKind {
+        match self {
+            CheckError::ExecutionError(_) => CheckErrorKind::Execution,
+            CheckError::ConfigError(_) => CheckErrorKind::Config,
+            CheckError::TimedOut(_) => CheckErrorKind::TimedOut,
+            CheckError::Panicked { .. } => CheckErrorKind::Panicked,
+        }
+    }
+}
+
+/// How a check is retried when an attempt fails with an error.
+///
+/// Only the outcome of the final attempt is stored as the check result;
+/// earlier attempts are kept in the attempt log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the second attempt.
+    pub initial_backoff: Duration,
+    /// Factor applied to the delay after every further attempt.
+    pub backoff_multiplier: f64,
+    /// Upper bound for the delay between attempts.
+    pub max_backoff: Duration,
+    /// Error kinds that are considered transient.
+    pub retry_on: Vec<CheckErrorKind>,
+}
+
+impl RetryPolicy {
+    /// A single attempt, no retries.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::ZERO,
+            backoff_multiplier: 1.0,
+            max_backoff: Duration::ZERO,
+            retry_on: vec![],
+        }
+    }
+
+    /// Retry execution errors and timeouts with exponential backoff.
+    pub fn transient(max_attempts: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff,
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(300),
+            retry_on: vec![CheckErrorKind::Execution, CheckErrorKind::TimedOut],
+        }
+    }
+
+    /// Whether another attempt should follow `attempt` (1-based) failing with `err`.
+    pub fn should_retry(&self, attempt: u32, err: &CheckError) -> bool {
+        attempt < self.max_attempts && self.retry_on.contains(&err.kind())
+    }
+
+    /// The multiplier must be a finite, non-negative number.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.backoff_multiplier.is_finite() || self.backoff_multiplier < 0.0 {
+            return Err(format!(
+                "Invalid backoff multiplier {}: must be finite and not negative",
+                self.backoff_multiplier
+            ));
+        }
+        Ok(())
+    }
+
+    /// Delay to wait after `attempt` (1-based) before the next one.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let max = self.max_backoff.max(self.initial_backoff);
+        if self.initial_backoff.is_zero() {
+            return Duration::ZERO;
+        }
+        // Capped in seconds: building a Duration from an overflowing or
+        // non-finite value panics. An invalid multiplier keeps the delay
+        // at its initial value.
+        let multiplier = if self.validate().is_ok() {
+            self.backoff_multiplier
+        } else {
+            1.0
+        };
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let secs = self.initial_backoff.as_secs_f64() * multiplier.powi(exponent);
+        Duration::try_from_secs_f64(secs).map_or(max, |delay| delay.min(max))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +138,19 @@ pub enum CheckStatus {
     Success,
     Warning,
     Failure,
+    TimedOut,
+    /// Not executed because an upstream dependency did not pass.
+    Skipped,
+    /// Executed during a maintenance window; the original outcome is kept
+    /// in the details.
+    Suppressed,
+}
+
+impl CheckStatus {
+    /// Whether downstream checks may run after this outcome.
+    pub fn is_passing(&self) -> bool {
+        matches!(self, CheckStatus::Success | CheckStatus::Warning)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +160,31 @@ pub struct CheckResult {
     pub details: Option<Value>,
 }
 
+impl CheckResult {
+    /// The result recorded in history when a check returns an error instead
+    /// of a result.
+    pub fn from_error(err: &CheckError) -> Self {
+        let (status, details) = match err {
+            CheckError::TimedOut(timeout) => (
+                CheckStatus::TimedOut,
+                Some(serde_json::json!({ "timeout_secs": timeout.as_secs_f64() })),
+            ),
+            CheckError::Panicked { message, backtrace } => (
+                CheckStatus::Failure,
+                Some(serde_json::json!({ "panic": message, "backtrace": backtrace })),
+            ),
+            CheckError::ExecutionError(_) | CheckError::ConfigError(_) => {
+                (CheckStatus::Failure, None)
+            }
+        };
+        CheckResult {
+            status,
+            message: err.to_string(),
+            details,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParameterDefinition {
     pub name: String,
@@ -37,12 +192,66 @@ pub struct ParameterDefinition {
     pub default: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+/// Descriptive information about a check, shown in the dashboard and used to
+/// filter and group checks. Every field is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CheckMetadata {
+    /// Free-form labels, e.g. the business domain.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Groups the check belongs to, for running related checks together.
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Team that owns the check.
+    pub owner: Option<String>,
+    /// Who to contact when the check fails (email, chat channel, pager).
+    pub contact: Option<String>,
+    pub severity: Option<Severity>,
+    /// Name of the dataset the check validates.
+    pub dataset: Option<String>,
+    pub runbook_url: Option<String>,
+    /// Longer documentation in markdown.
+    pub documentation: Option<String>,
+}
+
 #[async_trait]
 pub trait CheckContext: Send + Sync {
     async fn get_connection_string(
         &self,
         name: &str,
     ) -> Result<String, crate::connections::ConnectionError>;
+
+    /// Resolves a data source by name. Contexts without data sources
+    /// report every name as unknown.
+    async fn get_data_source(&self, name: &str) -> Result<DataSource, ConnectionError> {
+        Err(ConnectionError::DataSourceNotFound(name.to_string()))
+    }
+
+    /// An HTTP client for the `api` connection profile `name`, sending its
+    /// secret as the profile's `auth` says. Contexts without api profiles
+    /// report every name as unknown.
+    async fn http_client(&self, name: &str) -> Result<HttpClient, ConnectionError> {
+        Err(ConnectionError::ProfileNotFound(name.to_string()))
+    }
+
+    /// The environment connections are resolved in, if any.
+    fn environment(&self) -> Option<&str> {
+        None
+    }
+
+    /// A context resolving connections in `environment`, or `None` if this
+    /// context does not support environments.
+    fn for_environment(&self, _environment: &str) -> Option<Arc<dyn CheckContext>> {
+        None
+    }
 }
 
 pub struct StandardCheckContext {
@@ -57,6 +266,24 @@ impl CheckContext for StandardCheckContext {
     ) -> Result<String, crate::connections::ConnectionError> {
         self.connection_manager.get_connection_string(name).await
     }
+
+    async fn get_data_source(&self, name: &str) -> Result<DataSource, ConnectionError> {
+        self.connection_manager.get_data_source(name).await
+    }
+
+    async fn http_client(&self, name: &str) -> Result<HttpClient, ConnectionError> {
+        self.connection_manager.http_client(name).await
+    }
+
+    fn environment(&self) -> Option<&str> {
+        self.connection_manager.environment()
+    }
+
+    fn for_environment(&self, environment: &str) -> Option<Arc<dyn CheckContext>> {
+        Some(Arc::new(StandardCheckContext {
+            connection_manager: Arc::new(self.connection_manager.in_environment(environment)),
+        }))
+    }
 }
 
 #[async_trait]
@@ -64,9 +291,90 @@ pub trait DataCheck: Send + Sync {
     fn id(&self) -> &str;
     fn description(&self) -> &str;
     fn parameters(&self) -> Vec<ParameterDefinition>;
+
+    /// How long a single execution may take before it is abandoned and
+    /// recorded as `TimedOut`. Can be overridden per run.
+    fn timeout(&self) -> Duration {
+        DEFAULT_CHECK_TIMEOUT
+    }
+
+    /// How failed attempts are retried. Defaults to no retries.
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::none()
+    }
+
+    /// Ownership, tags and other descriptive information.
+    fn metadata(&self) -> CheckMetadata {
+        CheckMetadata::default()
+    }
+
+    /// Ids of checks that must pass before this one is worth running.
+    fn dependencies(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Names of the connection profiles the check opens. Used to cap the
+    /// number of concurrent executions against each connection.
+    fn connections(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Names of the data sources the check reads through
+    /// `CheckContext::get_data_source`. Their connections count towards the
+    /// concurrency limits like those of `connections`.
+    fn data_sources(&self) -> Vec<String> {
+        vec![]
+    }
+
     async fn execute(
         &self,
         ctx: &dyn CheckContext,
         params: &HashMap<String, Value>,
     ) -> Result<CheckResult, CheckError>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_backoff() {
+        let policy = RetryPolicy {
+            max_backoff: Duration::from_secs(5),
+            ..RetryPolicy::transient(5, Duration::from_secs(1))
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_retry_policy_backoff_does_not_overflow() {
+        let policy = RetryPolicy::transient(100, Duration::from_secs(1));
+        assert_eq!(policy.backoff(65), Duration::from_secs(300));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(300));
+
+        for multiplier in [-2.0, f64::NAN, f64::INFINITY] {
+            let policy = RetryPolicy {
+                backoff_multiplier: multiplier,
+                ..policy.clone()
+            };
+            assert!(policy.validate().is_err());
+            assert_eq!(policy.backoff(3), Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_only_retries_listed_errors() {
+        let policy = RetryPolicy::transient(3, Duration::ZERO);
+        let transient = CheckError::ExecutionError("connection reset".to_string());
+        let config = CheckError::ConfigError("missing parameter".to_string());
+
+        assert!(policy.should_retry(1, &transient));
+        assert!(policy.should_retry(2, &transient));
+        assert!(!policy.should_retry(3, &transient));
+        assert!(!policy.should_retry(1, &config));
+        assert!(!RetryPolicy::none().should_retry(1, &transient));
+    }
+}
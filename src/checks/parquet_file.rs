@@ -0,0 +1,902 @@
+//! Checks Parquet and Arrow IPC files from their metadata.
+//!
+//! Only footers and message headers are read: schemas, row counts and
+//! Parquet column statistics are available without scanning the data, so
+//! the check stays cheap on large files. Each file's format is detected from
+//! its magic bytes.
+
+use super::file::{blocking, date_param, date_parameter, matching_files, resolve_path};
+use super::{
+    CheckContext, CheckError, CheckMetadata, CheckResult, CheckStatus, DataCheck,
+    ParameterDefinition,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use parquet::file::metadata::ParquetMetaDataReader;
+use parquet::file::statistics::Statistics;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const PARQUET_MAGIC: &[u8] = b"PAR1";
+const ARROW_MAGIC: &[u8] = b"ARROW1";
+
+/// Thresholds for the statistics of one column, compared with the values of
+/// all row groups of all files. Minimum and maximum apply to numeric columns.
+#[derive(Debug, Clone)]
+pub struct ColumnStats {
+    pub column: String,
+    /// Lowest value the column may hold.
+    pub min: Option<f64>,
+    /// Highest value the column may hold.
+    pub max: Option<f64>,
+    /// Most nulls the column may hold.
+    pub max_nulls: Option<u64>,
+}
+
+impl ColumnStats {
+    /// `column` is the dotted path of a leaf column, e.g. `address.city`.
+    pub fn new(column: impl Into<String>) -> Self {
+        Self {
+            column: column.into(),
+            min: None,
+            max: None,
+            max_nulls: None,
+        }
+    }
+
+    pub fn min(mut self, min: f64) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    pub fn max(mut self, max: f64) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    pub fn max_nulls(mut self, nulls: u64) -> Self {
+        self.max_nulls = Some(nulls);
+        self
+    }
+}
+
+/// Partition directories (`key=value` path components) that must hold at
+/// least one matched file.
+#[derive(Debug, Clone)]
+enum Partitions {
+    Values {
+        key: String,
+        values: Vec<String>,
+    },
+    /// The `days` days up to the `date` parameter, as `YYYY-MM-DD`.
+    Daily {
+        key: String,
+        days: u32,
+    },
+}
+
+impl Partitions {
+    fn expected(&self, date: NaiveDate) -> (&str, Vec<String>) {
+        match self {
+            Partitions::Values { key, values } => (key, values.clone()),
+            Partitions::Daily { key, days } => (
+                key,
+                (0..*days)
+                    .rev()
+                    .filter_map(|n| date.checked_sub_days(chrono::Days::new(n.into())))
+                    .map(|d| d.format("%Y-%m-%d").to_string())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Statistics of a column over one or more row groups.
+#[derive(Debug, Clone, Copy, Default)]
+struct Summary {
+    min: Option<f64>,
+    max: Option<f64>,
+    null_count: Option<u64>,
+    /// Some row group holding non-null values recorded no minimum or maximum.
+    bounds_missing: bool,
+    /// Some row group recorded no null count.
+    nulls_missing: bool,
+}
+
+impl Summary {
+    /// Statistics of one row group with `values` values. An all-null row
+    /// group has no minimum or maximum to record.
+    fn row_group(min: Option<f64>, max: Option<f64>, null_count: Option<u64>, values: u64) -> Self {
+        Summary {
+            min,
+            max,
+            null_count,
+            bounds_missing: null_count != Some(values) && (min.is_none() || max.is_none()),
+            nulls_missing: null_count.is_none(),
+        }
+    }
+
+    /// A row group without statistics.
+    fn unknown() -> Self {
+        Summary {
+            bounds_missing: true,
+            nulls_missing: true,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, other: Summary) {
+        self.min = merge(self.min, other.min, f64::min);
+        self.max = merge(self.max, other.max, f64::max);
+        self.null_count = merge(self.null_count, other.null_count, |a, b| a + b);
+        self.bounds_missing |= other.bounds_missing;
+        self.nulls_missing |= other.nulls_missing;
+    }
+
+    /// Minimum, maximum and null count over all row groups; each is `None`
+    /// unless every row group recorded it.
+    fn totals(&self) -> (Option<f64>, Option<f64>, Option<u64>) {
+        let bounds = |v: Option<f64>| v.filter(|_| !self.bounds_missing);
+        (
+            bounds(self.min),
+            bounds(self.max),
+            self.null_count.filter(|_| !self.nulls_missing),
+        )
+    }
+}
+
+/// What was read from the metadata of one file.
+struct FileSummary {
+    format: &'static str,
+    schema: Schema,
+    rows: u64,
+    /// Statistics of each leaf column that has them, by dotted path.
+    stats: HashMap<String, Summary>,
+}
+
+/// Validates the Parquet and Arrow IPC files matching the path of a `file`
+/// connection profile, e.g. `/lake/orders/dt={{date}}/*.parquet`. Row counts
+/// are summed over all matched files; every file must match the schema.
+#[derive(Clone)]
+pub struct ParquetCheck {
+    id: String,
+    description: String,
+    connection: String,
+    fields: Vec<Field>,
+    exact_schema: bool,
+    min_rows: Option<u64>,
+    max_rows: Option<u64>,
+    stats: Vec<ColumnStats>,
+    partitions: Vec<Partitions>,
+    metadata: CheckMetadata,
+}
+
+impl ParquetCheck {
+    pub fn new(id: impl Into<String>, connection: impl Into<String>) -> Self {
+        let connection = connection.into();
+        Self {
+            id: id.into(),
+            description: format!("Validates the Parquet files of {}", connection),
+            connection,
+            fields: vec![],
+            exact_schema: false,
+            min_rows: None,
+            max_rows: None,
+            stats: vec![],
+            partitions: vec![],
+            metadata: CheckMetadata::default(),
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// A column the files must have, with its Arrow type. A column expected
+    /// not nullable must not be nullable in the file either.
+    pub fn field(mut self, name: impl Into<String>, data_type: DataType, nullable: bool) -> Self {
+        self.fields.push(Field::new(name, data_type, nullable));
+        self
+    }
+
+    /// Also fail on columns not declared with `field`.
+    pub fn exact_schema(mut self) -> Self {
+        self.exact_schema = true;
+        self
+    }
+
+    pub fn min_rows(mut self, rows: u64) -> Self {
+        self.min_rows = Some(rows);
+        self
+    }
+
+    pub fn max_rows(mut self, rows: u64) -> Self {
+        self.max_rows = Some(rows);
+        self
+    }
+
+    /// Statistics thresholds for a column. Arrow IPC files carry no
+    /// statistics, so these fail for them.
+    pub fn stats(mut self, stats: ColumnStats) -> Self {
+        self.stats.push(stats);
+        self
+    }
+
+    /// Requires a `key=value` directory with data for every value.
+    pub fn partitions<S: Into<String>>(
+        mut self,
+        key: impl Into<String>,
+        values: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.partitions.push(Partitions::Values {
+            key: key.into(),
+            values: values.into_iter().map(Into::into).collect(),
+        });
+        self
+    }
+
+    /// Requires a `key=YYYY-MM-DD` directory with data for each of the
+    /// `days` days up to the `date` parameter. The path should match all of
+    /// them, e.g. `/lake/orders/dt=*/*.parquet`.
+    pub fn daily_partitions(mut self, key: impl Into<String>, days: u32) -> Self {
+        self.partitions.push(Partitions::Daily {
+            key: key.into(),
+            days,
+        });
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: CheckMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    fn inspect(&self, pattern: &str, date: NaiveDate) -> Result<CheckResult, CheckError> {
+        let paths = matching_files(pattern)?;
+        if paths.is_empty() {
+            return Ok(CheckResult {
+                status: CheckStatus::Failure,
+                message: format!("No files match {}", pattern),
+                details: Some(json!({ "pattern": pattern, "files": [] })),
+            });
+        }
+
+        let mut problems = Vec::new();
+        let mut files = Vec::new();
+        let mut rows = 0;
+        let mut stats: HashMap<&str, Summary> = HashMap::new();
+        for path in &paths {
+            let summary = read_summary(path)?;
+            self.compare_schema(path, &summary.schema, &mut problems);
+            for rule in &self.stats {
+                let Some(column) = summary.stats.get(&rule.column) else {
+                    problems.push(format!(
+                        "{} has no statistics for {}",
+                        path.display(),
+                        rule.column
+                    ));
+                    stats
+                        .entry(&rule.column)
+                        .or_default()
+                        .add(Summary::unknown());
+                    continue;
+                };
+                stats.entry(&rule.column).or_default().add(*column);
+            }
+            rows += summary.rows;
+            files.push(json!({
+                "path": path.display().to_string(),
+                "format": summary.format,
+                "rows": summary.rows,
+                "schema": summary
+                    .schema
+                    .fields()
+                    .iter()
+                    .map(|f| format!("{}: {}", f.name(), f.data_type()))
+                    .collect::<Vec<_>>(),
+            }));
+        }
+
+        if let Some(min_rows) = self.min_rows {
+            if rows < min_rows {
+                problems.push(format!("{} rows, expected at least {}", rows, min_rows));
+            }
+        }
+        if let Some(max_rows) = self.max_rows {
+            if rows > max_rows {
+                problems.push(format!("{} rows, expected at most {}", rows, max_rows));
+            }
+        }
+
+        let mut column_stats = serde_json::Map::new();
+        for rule in &self.stats {
+            let Some((min, max, nulls)) = stats.get(rule.column.as_str()).map(Summary::totals)
+            else {
+                continue;
+            };
+            column_stats.insert(
+                rule.column.clone(),
+                json!({ "min": min, "max": max, "null_count": nulls }),
+            );
+            if let Some(limit) = rule.min {
+                match min {
+                    Some(min) if min >= limit => {}
+                    Some(min) => problems.push(format!(
+                        "{} has minimum {}, expected at least {}",
+                        rule.column, min, limit
+                    )),
+                    None => problems.push(format!("{} has no minimum", rule.column)),
+                }
+            }
+            if let Some(limit) = rule.max {
+                match max {
+                    Some(max) if max <= limit => {}
+                    Some(max) => problems.push(format!(
+                        "{} has maximum {}, expected at most {}",
+                        rule.column, max, limit
+                    )),
+                    None => problems.push(format!("{} has no maximum", rule.column)),
+                }
+            }
+            if let Some(limit) = rule.max_nulls {
+                match nulls {
+                    Some(nulls) if nulls <= limit => {}
+                    Some(nulls) => problems.push(format!(
+                        "{} has {} nulls, expected at most {}",
+                        rule.column, nulls, limit
+                    )),
+                    None => problems.push(format!("{} has no null count", rule.column)),
+                }
+            }
+        }
+
+        let mut missing_partitions = Vec::new();
+        for partitions in &self.partitions {
+            let (key, expected) = partitions.expected(date);
+            let prefix = format!("{}=", key);
+            let found: BTreeSet<String> = paths
+                .iter()
+                .flat_map(|p| p.iter())
+                .filter_map(|c| c.to_str()?.strip_prefix(&prefix).map(str::to_string))
+                .collect();
+            let missing: Vec<_> = expected
+                .into_iter()
+                .filter(|value| !found.contains(value))
+                .map(|value| format!("{}{}", prefix, value))
+                .collect();
+            if !missing.is_empty() {
+                problems.push(format!("Missing partitions {}", missing.join(", ")));
+            }
+            missing_partitions.extend(missing);
+        }
+
+        let (status, message) = if problems.is_empty() {
+            (
+                CheckStatus::Success,
+                format!("{} rows in {} files", rows, paths.len()),
+            )
+        } else {
+            (CheckStatus::Failure, problems.join("; "))
+        };
+        Ok(CheckResult {
+            status,
+            message,
+            details: Some(json!({
+                "pattern": pattern,
+                "files": files,
+                "rows": rows,
+                "column_stats": column_stats,
+                "missing_partitions": missing_partitions,
+                "problems": problems,
+            })),
+        })
+    }
+
+    fn compare_schema(&self, path: &Path, schema: &Schema, problems: &mut Vec<String>) {
+        for expected in &self.fields {
+            match schema.field_with_name(expected.name()) {
+                Err(_) => problems.push(format!(
+                    "{} has no column {}",
+                    path.display(),
+                    expected.name()
+                )),
+                Ok(actual) if actual.data_type() != expected.data_type() => problems.push(format!(
+                    "{}: {} is {}, expected {}",
+                    path.display(),
+                    expected.name(),
+                    actual.data_type(),
+                    expected.data_type()
+                )),
+                Ok(actual) if actual.is_nullable() && !expected.is_nullable() => problems.push(
+                    format!("{}: {} is nullable", path.display(), expected.name()),
+                ),
+                Ok(_) => {}
+            }
+        }
+        if self.exact_schema {
+            for actual in schema.fields() {
+                if !self.fields.iter().any(|f| f.name() == actual.name()) {
+                    problems.push(format!(
+                        "{} has unexpected column {}",
+                        path.display(),
+                        actual.name()
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Combines an optional running value with the next one.
+fn merge<T>(total: Option<T>, next: Option<T>, f: impl Fn(T, T) -> T) -> Option<T> {
+    match (total, next) {
+        (Some(a), Some(b)) => Some(f(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn read_summary(path: &Path) -> Result<FileSummary, CheckError> {
+    let error = |e: &dyn std::fmt::Display| {
+        CheckError::ExecutionError(format!("{}: {}", path.display(), e))
+    };
+    let mut file = File::open(path).map_err(|e| error(&e))?;
+    let mut magic = [0; 6];
+    let len = file.read(&mut magic).map_err(|e| error(&e))?;
+    if magic[..len].starts_with(PARQUET_MAGIC) {
+        read_parquet(&file).map_err(|e| error(&e))
+    } else if magic[..len].starts_with(ARROW_MAGIC) {
+        read_arrow_ipc(&mut file).map_err(|e| error(&e))
+    } else {
+        Err(error(&"not a Parquet or Arrow IPC file"))
+    }
+}
+
+fn read_parquet(file: &File) -> Result<FileSummary, parquet::errors::ParquetError> {
+    let metadata = ParquetMetaDataReader::new().parse_and_finish(file)?;
+    let file_metadata = metadata.file_metadata();
+    let schema = parquet::arrow::parquet_to_arrow_schema(
+        file_metadata.schema_descr(),
+        file_metadata.key_value_metadata(),
+    )?;
+
+    let mut stats: HashMap<String, Summary> = HashMap::new();
+    let mut without_statistics = HashSet::new();
+    for row_group in metadata.row_groups() {
+        for column in row_group.columns() {
+            let Some(statistics) = column.statistics() else {
+                without_statistics.insert(column.column_path().string());
+                continue;
+            };
+            let (min, max) = match statistics {
+                Statistics::Int32(s) => (
+                    s.min_opt().map(|v| *v as f64),
+                    s.max_opt().map(|v| *v as f64),
+                ),
+                Statistics::Int64(s) => (
+                    s.min_opt().map(|v| *v as f64),
+                    s.max_opt().map(|v| *v as f64),
+                ),
+                Statistics::Float(s) => (
+                    s.min_opt().map(|v| *v as f64),
+                    s.max_opt().map(|v| *v as f64),
+                ),
+                Statistics::Double(s) => (s.min_opt().copied(), s.max_opt().copied()),
+                _ => (None, None),
+            };
+            stats
+                .entry(column.column_path().string())
+                .or_default()
+                .add(Summary::row_group(
+                    min,
+                    max,
+                    statistics.null_count_opt(),
+                    column.num_values().max(0) as u64,
+                ));
+        }
+    }
+    // Columns without statistics in any row group are left out; those missing
+    // them only in some row groups have unknown totals.
+    for column in without_statistics {
+        if let Some(summary) = stats.get_mut(&column) {
+            summary.add(Summary::unknown());
+        }
+    }
+
+    Ok(FileSummary {
+        format: "parquet",
+        schema,
+        rows: file_metadata.num_rows().max(0) as u64,
+        stats,
+    })
+}
+
+/// Reads the schema from the footer and the row counts from the record
+/// batch headers, skipping the batch bodies.
+fn read_arrow_ipc(file: &mut File) -> Result<FileSummary, ArrowError> {
+    // Lengths and offsets are checked against the file size before buffers
+    // are allocated for them, so a corrupt file cannot request huge ones.
+    let len = file.metadata()?.len();
+    if len < 10 {
+        return Err(ArrowError::ParseError(
+            "File too short for an Arrow IPC file".to_string(),
+        ));
+    }
+    let mut trailer = [0; 10];
+    file.seek(SeekFrom::End(-10))?;
+    file.read_exact(&mut trailer)?;
+    let footer_len = arrow_ipc::reader::read_footer_length(trailer)?;
+    let footer_start = (len - 10).checked_sub(footer_len as u64).ok_or_else(|| {
+        ArrowError::ParseError(format!("Footer of {} bytes exceeds the file", footer_len))
+    })?;
+    let mut footer = vec![0; footer_len];
+    file.seek(SeekFrom::Start(footer_start))?;
+    file.read_exact(&mut footer)?;
+    let footer =
+        arrow_ipc::root_as_footer(&footer).map_err(|e| ArrowError::ParseError(e.to_string()))?;
+    let schema = footer
+        .schema()
+        .map(arrow_ipc::convert::fb_to_schema)
+        .ok_or_else(|| ArrowError::ParseError("No schema".to_string()))?;
+
+    let mut rows = 0;
+    for block in footer.recordBatches().into_iter().flatten() {
+        let (offset, header_len) = (block.offset(), block.metaDataLength());
+        let in_range = u64::try_from(offset)
+            .ok()
+            .zip(u64::try_from(header_len).ok())
+            .filter(|(offset, header_len)| {
+                *header_len >= 8 && offset.saturating_add(*header_len) <= footer_start
+            });
+        let Some((offset, header_len)) = in_range else {
+            return Err(ArrowError::ParseError(format!(
+                "Invalid record batch block at offset {} with {} bytes of metadata",
+                offset, header_len
+            )));
+        };
+        let mut header = vec![0; header_len as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        // Headers start with a continuation marker and their length, or
+        // only the length in files written before format version 0.15.
+        let start = if header.starts_with(&[0xff; 4]) { 8 } else { 4 };
+        let message = arrow_ipc::root_as_message(&header[start..])
+            .map_err(|e| ArrowError::ParseError(e.to_string()))?;
+        if let Some(batch) = message.header_as_record_batch() {
+            rows += batch.length().max(0) as u64;
+        }
+    }
+
+    Ok(FileSummary {
+        format: "arrow_ipc",
+        schema,
+        rows,
+        stats: HashMap::new(),
+    })
+}
+
+#[async_trait]
+impl DataCheck for ParquetCheck {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Vec<ParameterDefinition> {
+        vec![date_parameter()]
+    }
+
+    fn metadata(&self) -> CheckMetadata {
+        self.metadata.clone()
+    }
+
+    fn connections(&self) -> Vec<String> {
+        vec![self.connection.clone()]
+    }
+
+    async fn execute(
+        &self,
+        ctx: &dyn CheckContext,
+        params: &HashMap<String, Value>,
+    ) -> Result<CheckResult, CheckError> {
+        let date = date_param(params)?;
+        let pattern = resolve_path(ctx, &self.connection, params).await?;
+        let check = self.clone();
+        blocking(move || check.inspect(&pattern, date)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::testing::FileContext;
+    use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+    use parquet::arrow::ArrowWriter;
+    use std::sync::Arc;
+
+    fn orders(ids: Vec<i64>, amounts: Vec<Option<f64>>) -> RecordBatch {
+        let regions: Vec<_> = ids.iter().map(|_| "emea").collect();
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("region", DataType::Utf8, false),
+            Field::new("amount", DataType::Float64, true),
+        ]);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(ids)),
+            Arc::new(StringArray::from(regions)),
+            Arc::new(Float64Array::from(amounts)),
+        ];
+        RecordBatch::try_new(Arc::new(schema), columns).unwrap()
+    }
+
+    fn write_parquet(path: &Path, batches: &[RecordBatch]) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut writer =
+            ArrowWriter::try_new(File::create(path).unwrap(), batches[0].schema(), None).unwrap();
+        for batch in batches {
+            writer.write(batch).unwrap();
+            // One row group per batch.
+            writer.flush().unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    fn orders_check() -> ParquetCheck {
+        ParquetCheck::new("orders_lake", "Orders Lake")
+            .field("id", DataType::Int64, false)
+            .field("amount", DataType::Float64, true)
+    }
+
+    #[tokio::test]
+    async fn test_parquet_check() {
+        let dir = tempfile::tempdir().unwrap();
+        write_parquet(
+            &dir.path().join("dt=2024-03-08/part-0.parquet"),
+            &[
+                orders(vec![1, 2], vec![Some(5.0), None]),
+                orders(vec![3], vec![Some(250.0)]),
+            ],
+        );
+        write_parquet(
+            &dir.path().join("dt=2024-03-09/part-0.parquet"),
+            &[orders(vec![4, 5], vec![Some(-1.5), Some(7.0)])],
+        );
+        let ctx = FileContext(format!("{}/dt=*/*.parquet", dir.path().display()));
+        let params = HashMap::from([("date".to_string(), json!("2024-03-09"))]);
+
+        let check = orders_check()
+            .min_rows(5)
+            .max_rows(5)
+            .stats(
+                ColumnStats::new("amount")
+                    .min(-10.0)
+                    .max(1000.0)
+                    .max_nulls(1),
+            )
+            .daily_partitions("dt", 2);
+        let result = check.execute(&ctx, &params).await.unwrap();
+        assert!(
+            matches!(result.status, CheckStatus::Success),
+            "{}",
+            result.message
+        );
+        assert_eq!(result.message, "5 rows in 2 files");
+        let details = result.details.unwrap();
+        assert_eq!(
+            details["column_stats"]["amount"],
+            json!({ "min": -1.5, "max": 250.0, "null_count": 1 })
+        );
+        assert_eq!(
+            details["files"][0]["schema"],
+            json!(["id: Int64", "region: Utf8", "amount: Float64"])
+        );
+
+        let result = orders_check()
+            .field("region", DataType::Int32, false)
+            .exact_schema()
+            .stats(ColumnStats::new("amount").min(0.0).max(100.0).max_nulls(0))
+            .daily_partitions("dt", 3)
+            .partitions("dt", ["2024-03-09"])
+            .execute(&ctx, &params)
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        let details = result.details.unwrap();
+        let problems: Vec<_> = details["problems"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p.as_str().unwrap())
+            .filter(|p| !p.contains("region is Utf8, expected Int32"))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                "amount has minimum -1.5, expected at least 0",
+                "amount has maximum 250, expected at most 100",
+                "amount has 1 nulls, expected at most 0",
+                "Missing partitions dt=2024-03-07",
+            ]
+        );
+        assert_eq!(details["missing_partitions"], json!(["dt=2024-03-07"]));
+    }
+
+    #[tokio::test]
+    async fn test_row_group_without_statistics() {
+        use parquet::arrow::arrow_writer::{compute_leaves, get_column_writers};
+        use parquet::arrow::ArrowSchemaConverter;
+        use parquet::file::properties::{EnabledStatistics, WriterProperties};
+        use parquet::file::writer::SerializedFileWriter;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.parquet");
+        let batches = [
+            orders(vec![1, 2], vec![Some(5.0), None]),
+            orders(vec![3], vec![Some(-250.0)]),
+        ];
+        let schema = batches[0].schema();
+        let parquet_schema = ArrowSchemaConverter::new().convert(&schema).unwrap();
+        let with_statistics = Arc::new(WriterProperties::default());
+        let without_statistics = Arc::new(
+            WriterProperties::builder()
+                .set_statistics_enabled(EnabledStatistics::None)
+                .build(),
+        );
+        let mut writer = SerializedFileWriter::new(
+            File::create(&path).unwrap(),
+            parquet_schema.root_schema_ptr(),
+            with_statistics.clone(),
+        )
+        .unwrap();
+        // The second row group is written without statistics.
+        for (batch, props) in batches.iter().zip([&with_statistics, &without_statistics]) {
+            let mut row_group = writer.next_row_group().unwrap();
+            let writers = get_column_writers(&parquet_schema, props, &schema).unwrap();
+            let leaves = schema
+                .fields()
+                .iter()
+                .zip(batch.columns())
+                .flat_map(|(field, array)| compute_leaves(field, array).unwrap());
+            for (mut column, leaf) in writers.into_iter().zip(leaves) {
+                column.write(&leaf).unwrap();
+                column
+                    .close()
+                    .unwrap()
+                    .append_to_row_group(&mut row_group)
+                    .unwrap();
+            }
+            row_group.close().unwrap();
+        }
+        writer.close().unwrap();
+        let ctx = FileContext(path.display().to_string());
+
+        let result = orders_check()
+            .stats(ColumnStats::new("amount").min(0.0).max(100.0).max_nulls(5))
+            .execute(&ctx, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        let details = result.details.unwrap();
+        assert_eq!(
+            details["problems"],
+            json!([
+                "amount has no minimum",
+                "amount has no maximum",
+                "amount has no null count",
+            ])
+        );
+        assert_eq!(
+            details["column_stats"]["amount"],
+            json!({ "min": null, "max": null, "null_count": null })
+        );
+
+        // An all-null row group has no bounds to record.
+        write_parquet(
+            &path,
+            &[
+                orders(vec![1], vec![Some(5.0)]),
+                orders(vec![2], vec![None]),
+            ],
+        );
+        let result = orders_check()
+            .stats(ColumnStats::new("amount").min(0.0).max(100.0).max_nulls(1))
+            .execute(&ctx, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(
+            matches!(result.status, CheckStatus::Success),
+            "{}",
+            result.message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_arrow_ipc_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.arrow");
+        let batch = orders(vec![1, 2, 3], vec![None, None, None]);
+        let mut writer =
+            arrow_ipc::writer::FileWriter::try_new(File::create(&path).unwrap(), &batch.schema())
+                .unwrap();
+        writer.write(&batch).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+        let ctx = FileContext(path.display().to_string());
+
+        let result = orders_check()
+            .min_rows(6)
+            .execute(&ctx, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(
+            matches!(result.status, CheckStatus::Success),
+            "{}",
+            result.message
+        );
+        assert_eq!(result.details.unwrap()["files"][0]["format"], "arrow_ipc");
+
+        let result = orders_check()
+            .field("amount", DataType::Float64, false)
+            .stats(ColumnStats::new("amount").max_nulls(0))
+            .execute(&ctx, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+        assert!(result.message.contains("amount is nullable"));
+        assert!(result.message.contains("has no statistics for amount"));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_arrow_ipc_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.arrow");
+        let batch = orders(vec![1, 2, 3], vec![None, None, None]);
+        let mut writer =
+            arrow_ipc::writer::FileWriter::try_new(File::create(&path).unwrap(), &batch.schema())
+                .unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let footer_len = i32::from_le_bytes(bytes[bytes.len() - 10..][..4].try_into().unwrap());
+        let ctx = FileContext(path.display().to_string());
+
+        // Only the trailer is left, announcing a footer the file cannot hold.
+        std::fs::write(&path, [&bytes[..8], &bytes[bytes.len() - 10..]].concat()).unwrap();
+        let result = orders_check().execute(&ctx, &HashMap::new()).await;
+        assert!(
+            matches!(&result, Err(CheckError::ExecutionError(e)) if e.contains("exceeds the file")),
+            "{:?}",
+            result.err()
+        );
+
+        // The footer is intact, but the record batches it points to are gone.
+        let footer = bytes.len() - 10 - footer_len as usize;
+        std::fs::write(&path, [&bytes[..8], &bytes[footer..]].concat()).unwrap();
+        let result = orders_check().execute(&ctx, &HashMap::new()).await;
+        assert!(
+            matches!(&result, Err(CheckError::ExecutionError(e)) if e.contains("Invalid record batch block")),
+            "{:?}",
+            result.err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.parquet");
+        std::fs::write(&path, "id,amount\n").unwrap();
+        let ctx = FileContext(path.display().to_string());
+
+        let result = orders_check().execute(&ctx, &HashMap::new()).await;
+        assert!(
+            matches!(result, Err(CheckError::ExecutionError(e)) if e.contains("not a Parquet"))
+        );
+    }
+}
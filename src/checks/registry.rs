@@ -0,0 +1,244 @@
+//! Registry of the checks compiled into the application.
+
+use super::{
+    CheckContext, CheckError, CheckMetadata, CheckResult, DataCheck, ParameterDefinition,
+    RetryPolicy,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("Check already registered: {0}")]
+    Duplicate(String),
+    #[error("Dependency cycle: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+    #[error("Invalid retry policy of {0}: {1}")]
+    InvalidRetryPolicy(String, String),
+}
+
+/// Checks keyed by id. Registration rejects duplicate ids, invalid retry
+/// policies and dependency cycles, so the dependency graph of registered
+/// checks is always a DAG.
+///
+/// Dependencies on checks that are not (yet) registered are allowed; they
+/// are resolved at execution time.
+#[derive(Default)]
+pub struct CheckRegistry {
+    checks: BTreeMap<String, Arc<dyn DataCheck>>,
+}
+
+impl CheckRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, check: Arc<dyn DataCheck>) -> Result<(), RegistryError> {
+        let id = check.id().to_string();
+        if self.checks.contains_key(&id) {
+            return Err(RegistryError::Duplicate(id));
+        }
+        check
+            .retry_policy()
+            .validate()
+            .map_err(|e| RegistryError::InvalidRetryPolicy(id.clone(), e))?;
+
+        // A cycle through the new check means one of its dependencies
+        // already (transitively) depends on it.
+        for dep in check.dependencies() {
+            let mut path = vec![id.clone()];
+            if self.reaches(&dep, &id, &mut path, &mut HashSet::new()) {
+                return Err(RegistryError::Cycle(path));
+            }
+        }
+
+        self.checks.insert(id, check);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Arc<dyn DataCheck>> {
+        self.checks.get(id)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Arc<dyn DataCheck>> {
+        self.checks.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.checks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+
+    /// Replaces the metadata a registered check declares in code, e.g. with
+    /// metadata from the configuration file. Returns `false` if no check
+    /// has the id.
+    pub fn set_metadata(&mut self, id: &str, metadata: CheckMetadata) -> bool {
+        let Some(check) = self.checks.get_mut(id) else {
+            return false;
+        };
+        *check = Arc::new(ConfiguredCheck {
+            inner: check.clone(),
+            metadata,
+        });
+        true
+    }
+
+    /// Depth-first search from `from` for `target`, recording the path taken.
+    fn reaches(
+        &self,
+        from: &str,
+        target: &str,
+        path: &mut Vec<String>,
+        visited: &mut HashSet<String>,
+    ) -> bool {
+        path.push(from.to_string());
+        if from == target {
+            return true;
+        }
+        if visited.insert(from.to_string()) {
+            if let Some(check) = self.checks.get(from) {
+                for dep in check.dependencies() {
+                    if self.reaches(&dep, target, path, visited) {
+                        return true;
+                    }
+                }
+            }
+        }
+        path.pop();
+        false
+    }
+}
+
+/// A check whose metadata comes from configuration instead of its code.
+struct ConfiguredCheck {
+    inner: Arc<dyn DataCheck>,
+    metadata: CheckMetadata,
+}
+
+#[async_trait]
+impl DataCheck for ConfiguredCheck {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn parameters(&self) -> Vec<ParameterDefinition> {
+        self.inner.parameters()
+    }
+
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.inner.retry_policy()
+    }
+
+    fn metadata(&self) -> CheckMetadata {
+        self.metadata.clone()
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.inner.dependencies()
+    }
+
+    fn connections(&self) -> Vec<String> {
+        self.inner.connections()
+    }
+
+    fn data_sources(&self) -> Vec<String> {
+        self.inner.data_sources()
+    }
+
+    async fn execute(
+        &self,
+        ctx: &dyn CheckContext,
+        params: &HashMap<String, Value>,
+    ) -> Result<CheckResult, CheckError> {
+        self.inner.execute(ctx, params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::CheckStatus;
+    use crate::runner::testing::{Behavior, ScriptedCheck};
+
+    fn node(id: &str, dependencies: &[&str]) -> Arc<ScriptedCheck> {
+        Arc::new(
+            ScriptedCheck::new(id, Behavior::Return(CheckStatus::Success))
+                .with_dependencies(dependencies),
+        )
+    }
+
+    #[test]
+    fn test_register_rejects_cycles() {
+        let mut registry = CheckRegistry::new();
+        registry.register(node("raw", &["agg"])).unwrap();
+        registry.register(node("clean", &["raw"])).unwrap();
+
+        match registry.register(node("agg", &["clean"])) {
+            Err(RegistryError::Cycle(path)) => {
+                assert_eq!(path, vec!["agg", "clean", "raw", "agg"])
+            }
+            other => panic!("expected cycle, got {:?}", other.err()),
+        }
+        assert!(matches!(
+            registry.register(node("self", &["self"])),
+            Err(RegistryError::Cycle(_))
+        ));
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn test_set_metadata() {
+        let mut registry = CheckRegistry::new();
+        registry.register(node("clean", &["raw"])).unwrap();
+        let metadata = CheckMetadata {
+            owner: Some("data-eng".to_string()),
+            ..Default::default()
+        };
+        assert!(registry.set_metadata("clean", metadata));
+        assert!(!registry.set_metadata("missing", CheckMetadata::default()));
+
+        let check = registry.get("clean").unwrap();
+        assert_eq!(check.metadata().owner.as_deref(), Some("data-eng"));
+        assert_eq!(check.dependencies(), vec!["raw"]);
+    }
+
+    #[test]
+    fn test_register_rejects_duplicates() {
+        let mut registry = CheckRegistry::new();
+        registry.register(node("raw", &[])).unwrap();
+        assert!(matches!(
+            registry.register(node("raw", &[])),
+            Err(RegistryError::Duplicate(_))
+        ));
+    }
+
+    #[test]
+    fn test_register_rejects_invalid_retry_policy() {
+        let policy = RetryPolicy {
+            backoff_multiplier: f64::NAN,
+            ..RetryPolicy::transient(3, Duration::from_secs(1))
+        };
+        let check = ScriptedCheck::new("raw", Behavior::Return(CheckStatus::Success))
+            .with_retry_policy(policy);
+        let mut registry = CheckRegistry::new();
+        assert!(matches!(
+            registry.register(Arc::new(check)),
+            Err(RegistryError::InvalidRetryPolicy(..))
+        ));
+    }
+}
@@ -1,6 +1,11 @@
-use crate::db::Db;
+use crate::checks::DataCheck;
+use crate::db::{ConnectionProfileRecord, Db};
+use crate::environments::{ConnectionOverride, DataSourceOverride};
+use crate::http_client::{HttpAuth, HttpClient, HttpError};
 use crate::secrets::SecretStore;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
 use thiserror::Error;
 
@@ -10,8 +15,15 @@ pub enum ConnectionError {
     SecretError(#[from] crate::secrets::SecretError),
     #[error("Connection profile not found: {0}")]
     ProfileNotFound(String),
+    #[error("Data source not found: {0}")]
+    DataSourceNotFound(String),
+    /// The data source's connection profile or secret no longer resolves.
+    #[error("Data source is invalid: {0}")]
+    InvalidDataSource(String),
     #[error("Driver error: {0}")]
     DriverError(String),
+    #[error("Not an api connection: {0}")]
+    NotAnApi(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,19 +33,139 @@ pub struct ConnectionProfile {
     pub connection_string_template: String,
     pub connection_type: Option<String>,
     pub secret_ref: Option<String>,
+    /// How HTTP requests to an `api` profile send its secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<HttpAuth>,
 }
 
+impl From<ConnectionProfileRecord> for ConnectionProfile {
+    fn from(r: ConnectionProfileRecord) -> Self {
+        let auth = r.auth.and_then(|auth| match auth.parse() {
+            Ok(auth) => Some(auth),
+            Err(e) => {
+                tracing::warn!("Ignoring auth of connection profile {}: {}", r.name, e);
+                None
+            }
+        });
+        Self {
+            name: r.name,
+            driver: r.driver,
+            connection_string_template: r.connection_string_template,
+            connection_type: r.connection_type,
+            secret_ref: r.secret_ref,
+            auth,
+        }
+    }
+}
+
+/// A data source resolved for a check: the bound connection profile and the
+/// secret of the data source, substituted into the connection string.
+#[derive(Clone)]
+pub struct DataSource {
+    pub name: String,
+    pub profile: ConnectionProfile,
+    pub connection_string: String,
+    pub secret: String,
+}
+
+impl fmt::Debug for DataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DataSource")
+            .field("name", &self.name)
+            .field("profile", &self.profile)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Which connection each data source is bound to, for relating checks to
+/// the data sources they use.
+#[derive(Debug, Clone, Default)]
+pub struct DataSourceBindings {
+    connections: HashMap<String, String>,
+}
+
+impl DataSourceBindings {
+    pub fn new(connections: HashMap<String, String>) -> Self {
+        Self { connections }
+    }
+
+    pub async fn load(db: &Db) -> anyhow::Result<Self> {
+        let connections = db
+            .get_data_sources()
+            .await?
+            .into_iter()
+            .map(|(name, connection, _, _)| (name, connection))
+            .collect();
+        Ok(Self { connections })
+    }
+
+    /// Data sources a check uses: those it declares, and those bound to the
+    /// connection profiles it opens directly.
+    pub fn data_sources_of(&self, check: &dyn DataCheck) -> Vec<String> {
+        let connections = check.connections();
+        let mut names = check.data_sources();
+        let mut bound: Vec<String> = self
+            .connections
+            .iter()
+            .filter(|(name, connection)| connections.contains(connection) && !names.contains(name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        bound.sort();
+        names.extend(bound);
+        names
+    }
+
+    /// Connection profiles a check opens, directly or through its declared
+    /// data sources.
+    pub fn connections_of(&self, check: &dyn DataCheck) -> Vec<String> {
+        let mut connections = check.connections();
+        for name in check.data_sources() {
+            if let Some(connection) = self.connections.get(&name) {
+                if !connections.contains(connection) {
+                    connections.push(connection.clone());
+                }
+            }
+        }
+        connections
+    }
+}
+
+fn render_connection_string(template: &str, secret: &str) -> String {
+    // Simple template replacement for now. In a real app, use a proper template engine or specific placeholders.
+    template.replace("{{PASSWORD}}", secret)
+}
+
+#[derive(Clone)]
 pub struct ConnectionManager {
     db: Db,
     secret_store: Arc<dyn SecretStore>,
+    /// Environment whose overrides are applied; `None` uses the base
+    /// profiles and data sources.
+    environment: Option<String>,
 }
 
 impl ConnectionManager {
     pub fn new(db: Db, secret_store: Arc<dyn SecretStore>) -> Self {
-        Self { db, secret_store }
+        Self {
+            db,
+            secret_store,
+            environment: None,
+        }
     }
 
-    pub async fn get_connection_string(&self, name: &str) -> Result<String, ConnectionError> {
+    /// A manager resolving connections in `environment`.
+    pub fn in_environment(&self, environment: &str) -> Self {
+        Self {
+            environment: Some(environment.to_string()),
+            ..self.clone()
+        }
+    }
+
+    pub fn environment(&self) -> Option<&str> {
+        self.environment.as_deref()
+    }
+
+    async fn get_profile(&self, name: &str) -> Result<ConnectionProfile, ConnectionError> {
         let profiles = self
             .db
             .get_connection_profiles()
@@ -42,24 +174,374 @@ impl ConnectionManager {
 
         let profile = profiles
             .into_iter()
-            .find(|(n, _, _, _, _)| n == name)
+            .find(|p| p.name == name)
+            .map(ConnectionProfile::from)
             .ok_or_else(|| ConnectionError::ProfileNotFound(name.to_string()))?;
 
-        let (_, _, tmpl, _conn_type, secret_ref) = profile;
-        let mut conn_str = tmpl;
+        let Some(environment) = &self.environment else {
+            return Ok(profile);
+        };
+        let overrides = self
+            .db
+            .get_connection_overrides(Some(environment))
+            .await
+            .map_err(|e| ConnectionError::DriverError(e.to_string()))?;
+        Ok(
+            match overrides.into_iter().find(|o| o.profile_name == name) {
+                Some(record) => ConnectionOverride::from(record).apply(profile),
+                None => profile,
+            },
+        )
+    }
+
+    pub async fn get_connection_string(&self, name: &str) -> Result<String, ConnectionError> {
+        let profile = self.get_profile(name).await?;
+        let mut conn_str = profile.connection_string_template;
 
-        if let Some(s_ref) = secret_ref {
+        if let Some(s_ref) = profile.secret_ref {
             let secret = self.secret_store.get_secret(&s_ref).await?;
-            // Simple template replacement for now. In a real app, use a proper template engine or specific placeholders.
-            conn_str = conn_str.replace("{{PASSWORD}}", &secret);
+            conn_str = render_connection_string(&conn_str, &secret);
         }
 
         Ok(conn_str)
     }
+
+    /// An HTTP client for an `api` connection profile, with the rendered
+    /// connection string as base URL. Errors show it with the secret masked.
+    pub async fn http_client(&self, name: &str) -> Result<HttpClient, ConnectionError> {
+        let profile = self.get_profile(name).await?;
+        if profile.connection_type.as_deref() != Some("api") {
+            return Err(ConnectionError::NotAnApi(name.to_string()));
+        }
+        let secret = match &profile.secret_ref {
+            Some(s_ref) => Some(self.secret_store.get_secret(s_ref).await?),
+            None => None,
+        };
+        let base_url = render_connection_string(
+            &profile.connection_string_template,
+            secret.as_deref().unwrap_or_default(),
+        );
+        let shown_base_url = render_connection_string(&profile.connection_string_template, "****");
+        HttpClient::new(&base_url, profile.auth, secret)
+            .map_err(|_| HttpError::InvalidUrl(shown_base_url.clone()))
+            .and_then(|client| client.with_shown_base_url(&shown_base_url))
+            .map_err(|e| ConnectionError::DriverError(e.to_string()))
+    }
+
+    /// Resolves a data source to its connection profile and secret. Data
+    /// sources marked invalid are refused, unless the active environment
+    /// rebinds them.
+    pub async fn get_data_source(&self, name: &str) -> Result<DataSource, ConnectionError> {
+        let (binding, is_valid) = self
+            .db
+            .get_data_sources()
+            .await
+            .map_err(|e| ConnectionError::DriverError(e.to_string()))?
+            .into_iter()
+            .find(|(n, ..)| n == name)
+            .map(|(_, connection, secret, is_valid)| ((connection, secret), is_valid))
+            .ok_or_else(|| ConnectionError::DataSourceNotFound(name.to_string()))?;
+
+        let data_source_override = match &self.environment {
+            Some(environment) => self
+                .db
+                .get_data_source_overrides(Some(environment))
+                .await
+                .map_err(|e| ConnectionError::DriverError(e.to_string()))?
+                .into_iter()
+                .find(|o| o.data_source == name)
+                .map(DataSourceOverride::from),
+            None => None,
+        };
+        let (connection_name, secret_key) = match &data_source_override {
+            Some(o) => o.apply(binding),
+            None if is_valid => binding,
+            None => return Err(ConnectionError::InvalidDataSource(name.to_string())),
+        };
+
+        let profile = self.get_profile(&connection_name).await?;
+        let secret = self.secret_store.get_secret(&secret_key).await?;
+        Ok(DataSource {
+            name: name.to_string(),
+            connection_string: render_connection_string(
+                &profile.connection_string_template,
+                &secret,
+            ),
+            profile,
+            secret,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    // Tests removed as they relied on in-memory profiles which are no longer supported directly in ConnectionManager
-    // Integration tests would be needed with a real DB or mocked DB.
+    use super::*;
+    use crate::checks::CheckStatus;
+    use crate::db::{ConnectionOverrideRecord, DataSourceOverrideRecord};
+    use crate::runner::testing::{Behavior, ScriptedCheck};
+    use crate::secrets::DbSecretStore;
+
+    async fn setup() -> Db {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        db.save_secret("wh_password", "hunter2").await.unwrap();
+        db.save_secret("profile_password", "swordfish")
+            .await
+            .unwrap();
+        db.save_connection_profile(
+            "warehouse",
+            "Oracle",
+            "DSN=wh;PWD={{PASSWORD}}",
+            Some("database"),
+            Some("profile_password"),
+            None,
+        )
+        .await
+        .unwrap();
+        db.save_data_source("sales", "warehouse", "wh_password")
+            .await
+            .unwrap();
+        db
+    }
+
+    fn manager(db: &Db) -> ConnectionManager {
+        ConnectionManager::new(db.clone(), Arc::new(DbSecretStore::new(db.clone())))
+    }
+
+    async fn is_valid(db: &Db, name: &str) -> bool {
+        let sources = db.get_data_sources().await.unwrap();
+        sources.into_iter().find(|s| s.0 == name).unwrap().3
+    }
+
+    #[tokio::test]
+    async fn test_data_source_requires_existing_references() {
+        let db = setup().await;
+        assert!(db
+            .save_data_source("orphan", "missing", "wh_password")
+            .await
+            .is_err());
+        assert!(db
+            .save_data_source("orphan", "warehouse", "missing")
+            .await
+            .is_err());
+        assert!(is_valid(&db, "sales").await);
+    }
+
+    #[tokio::test]
+    async fn test_delete_with_dependents() {
+        let db = setup().await;
+        assert_eq!(
+            db.get_connection_dependents("warehouse").await.unwrap(),
+            vec!["sales"]
+        );
+        assert!(db
+            .delete_connection_profile("warehouse", false)
+            .await
+            .is_err());
+        assert!(db.delete_secret("wh_password", false).await.is_err());
+
+        db.delete_connection_profile("warehouse", true)
+            .await
+            .unwrap();
+        assert!(db.get_data_sources().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_profile_secret_changes_revalidate() {
+        let db = setup().await;
+
+        db.delete_secret("profile_password", false).await.unwrap();
+        assert!(!is_valid(&db, "sales").await);
+
+        db.save_secret("profile_password", "swordfish2")
+            .await
+            .unwrap();
+        assert!(is_valid(&db, "sales").await);
+
+        db.save_connection_profile(
+            "warehouse",
+            "Oracle",
+            "DSN=wh;PWD={{PASSWORD}}",
+            Some("database"),
+            Some("not_a_secret"),
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(!is_valid(&db, "sales").await);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_data_source() {
+        let db = setup().await;
+        let manager = manager(&db);
+
+        let source = manager.get_data_source("sales").await.unwrap();
+        assert_eq!(source.profile.name, "warehouse");
+        assert_eq!(source.connection_string, "DSN=wh;PWD=hunter2");
+        assert!(!format!("{:?}", source).contains("hunter2"));
+
+        assert!(matches!(
+            manager.get_data_source("missing").await,
+            Err(ConnectionError::DataSourceNotFound(_))
+        ));
+
+        db.delete_secret("profile_password", false).await.unwrap();
+        assert!(matches!(
+            manager.get_data_source("sales").await,
+            Err(ConnectionError::InvalidDataSource(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_environment_overrides() {
+        let db = setup().await;
+        db.save_secret("prod_password", "s3cret").await.unwrap();
+        db.save_environment("prod", None).await.unwrap();
+        db.save_connection_override(&ConnectionOverrideRecord {
+            profile_name: "warehouse".to_string(),
+            environment: "prod".to_string(),
+            driver: None,
+            connection_string_template: Some("DSN=wh_prod;PWD={{PASSWORD}}".to_string()),
+            secret_ref: None,
+        })
+        .await
+        .unwrap();
+        db.save_data_source_override(&DataSourceOverrideRecord {
+            data_source: "sales".to_string(),
+            environment: "prod".to_string(),
+            connection_name: None,
+            secret_key: Some("prod_password".to_string()),
+        })
+        .await
+        .unwrap();
+
+        let base = manager(&db);
+        let prod = base.in_environment("prod");
+        assert_eq!(prod.environment(), Some("prod"));
+
+        let source = base.get_data_source("sales").await.unwrap();
+        assert_eq!(source.connection_string, "DSN=wh;PWD=hunter2");
+        let source = prod.get_data_source("sales").await.unwrap();
+        assert_eq!(source.connection_string, "DSN=wh_prod;PWD=s3cret");
+        assert_eq!(source.profile.driver, "Oracle");
+
+        // Environments without overrides use the base configuration.
+        let dev = base.in_environment("dev");
+        let source = dev.get_data_source("sales").await.unwrap();
+        assert_eq!(source.connection_string, "DSN=wh;PWD=hunter2");
+
+        db.delete_environment("prod").await.unwrap();
+        assert!(db.get_connection_overrides(None).await.unwrap().is_empty());
+        assert!(db.get_data_source_overrides(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_http_client_for_api_profile() {
+        use axum::{http::HeaderMap, routing::get, Router};
+
+        // Echoes the API key header back.
+        let app =
+            Router::new().route(
+                "/v1/status",
+                get(|headers: HeaderMap| async move {
+                    headers["x-api-key"].to_str().unwrap().to_string()
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let db = setup().await;
+        db.save_secret("crm_key", "k3y").await.unwrap();
+        db.save_connection_profile(
+            "crm",
+            "openapi",
+            &base_url,
+            Some("api"),
+            Some("crm_key"),
+            Some("header:X-API-Key"),
+        )
+        .await
+        .unwrap();
+
+        let client = manager(&db).http_client("crm").await.unwrap();
+        assert_eq!(client.base_url(), format!("{}/", base_url));
+        let response = client.get("status").await.unwrap();
+        assert_eq!(response.text(), "k3y");
+
+        assert!(matches!(
+            manager(&db).http_client("warehouse").await,
+            Err(ConnectionError::NotAnApi(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_client_does_not_show_secret_in_url() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let db = setup().await;
+        db.save_secret("crm_key", "k3y").await.unwrap();
+        for (name, template) in [
+            (
+                "crm",
+                format!(
+                    "http://user:{{{{PASSWORD}}}}@{}/{{{{PASSWORD}}}}/v1",
+                    address
+                ),
+            ),
+            ("broken", "not a url {{PASSWORD}}".to_string()),
+        ] {
+            db.save_connection_profile(
+                name,
+                "openapi",
+                &template,
+                Some("api"),
+                Some("crm_key"),
+                None,
+            )
+            .await
+            .unwrap();
+        }
+
+        let client = manager(&db).http_client("crm").await.unwrap();
+        assert_eq!(
+            client.base_url(),
+            format!("http://user:****@{}/****/v1/", address)
+        );
+        assert!(!format!("{:?}", client).contains("k3y"));
+        let error = client
+            .with_limits(crate::http_client::HttpLimits {
+                max_attempts: 1,
+                ..Default::default()
+            })
+            .get("status")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("/****/v1/status"), "{}", error);
+        assert!(!error.contains("k3y"), "{}", error);
+
+        let error = manager(&db).http_client("broken").await.unwrap_err();
+        assert!(!error.to_string().contains("k3y"), "{}", error);
+    }
+
+    #[test]
+    fn test_bindings() {
+        let bindings = DataSourceBindings::new(HashMap::from([
+            ("sales".to_string(), "warehouse".to_string()),
+            ("finance".to_string(), "warehouse".to_string()),
+            ("crm".to_string(), "postgres".to_string()),
+        ]));
+        let declared = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Success))
+            .with_data_sources(&["crm"]);
+        let direct = ScriptedCheck::new("totals", Behavior::Return(CheckStatus::Success))
+            .with_connections(&["warehouse"]);
+
+        assert_eq!(bindings.data_sources_of(&declared), vec!["crm"]);
+        assert_eq!(bindings.connections_of(&declared), vec!["postgres"]);
+        assert_eq!(bindings.data_sources_of(&direct), vec!["finance", "sales"]);
+        assert_eq!(bindings.connections_of(&direct), vec!["warehouse"]);
+    }
 }
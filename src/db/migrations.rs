@@ -0,0 +1,128 @@
+//! Schema migrations shared by the SQLite and PostgreSQL backends.
+//!
+//! Migrations live in `migrations/` and are applied in order, each in its own
+//! transaction, and recorded in `schema_migrations`. They are written for
+//! SQLite; [`Backend::translate`] adapts them to PostgreSQL. A migration that
+//! cannot be shared also has a `.postgres.sql` variant.
+
+use super::{Backend, Pool};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use sqlx::Executor;
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    sql: &'static str,
+    postgres: Option<&'static str>,
+}
+
+impl Migration {
+    pub fn sql(&self, backend: Backend) -> String {
+        match (backend, self.postgres) {
+            (Backend::Postgres, Some(sql)) => sql.to_string(),
+            _ => backend.translate(self.sql),
+        }
+    }
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial",
+        sql: include_str!("../../migrations/0001_initial.sql"),
+        postgres: None,
+    },
+    Migration {
+        version: 2,
+        description: "append_only_audit_log",
+        sql: include_str!("../../migrations/0002_append_only_audit_log.sql"),
+        postgres: Some(include_str!(
+            "../../migrations/0002_append_only_audit_log.postgres.sql"
+        )),
+    },
+    Migration {
+        version: 3,
+        description: "connection_auth",
+        sql: include_str!("../../migrations/0003_connection_auth.sql"),
+        postgres: None,
+    },
+];
+
+const CREATE_MIGRATIONS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS schema_migrations (
+        version BIGINT PRIMARY KEY,
+        description TEXT NOT NULL,
+        applied_at TIMESTAMPTZ NOT NULL
+    )"#;
+
+/// Key of the advisory lock that keeps servers sharing a PostgreSQL database
+/// from migrating it at the same time.
+const POSTGRES_LOCK_KEY: i64 = 0x6461_7461_6461_7368;
+
+/// Applies the pending migrations on one connection.
+macro_rules! apply_pending {
+    ($conn:expr, $backend:expr) => {{
+        $conn.execute(CREATE_MIGRATIONS_TABLE).await?;
+        let applied: Vec<(i64,)> = sqlx::query_as("SELECT version FROM schema_migrations")
+            .fetch_all(&mut *$conn)
+            .await?;
+        for migration in MIGRATIONS {
+            if applied.iter().any(|(v,)| *v == migration.version) {
+                continue;
+            }
+            let mut tx = $conn.begin().await?;
+            tx.execute(migration.sql($backend).as_str())
+                .await
+                .with_context(|| {
+                    format!(
+                        "Migration {:04}_{} failed",
+                        migration.version, migration.description
+                    )
+                })?;
+            sqlx::query(
+                "INSERT INTO schema_migrations (version, description, applied_at) VALUES ($1, $2, $3)",
+            )
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            tracing::info!(
+                "Applied migration {:04}_{}",
+                migration.version,
+                migration.description
+            );
+        }
+    }};
+}
+
+pub(super) async fn run(pool: &Pool) -> Result<()> {
+    use sqlx::Connection;
+
+    match pool {
+        Pool::Sqlite(pool) => {
+            let mut conn = pool.acquire().await?;
+            apply_pending!(conn, Backend::Sqlite);
+        }
+        Pool::Postgres(pool) => {
+            let mut conn = pool.acquire().await?;
+            sqlx::query("SELECT pg_advisory_lock($1)")
+                .bind(POSTGRES_LOCK_KEY)
+                .execute(&mut *conn)
+                .await?;
+            let result: Result<()> = async {
+                apply_pending!(conn, Backend::Postgres);
+                Ok(())
+            }
+            .await;
+            sqlx::query("SELECT pg_advisory_unlock($1)")
+                .bind(POSTGRES_LOCK_KEY)
+                .execute(&mut *conn)
+                .await?;
+            result?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,551 @@
+//! Storage tests run against every backend.
+//!
+//! PostgreSQL tests use the server at `DATA_DASH_TEST_POSTGRES_URL` if set,
+//! or else a throwaway cluster started with `initdb` and `pg_ctl` from the
+//! `PATH`. They are skipped when neither is available.
+
+use super::*;
+use chrono::Duration;
+use serde_json::json;
+use std::net::TcpListener;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A PostgreSQL cluster in a temporary directory, stopped on drop.
+struct TestCluster {
+    dir: PathBuf,
+    port: u16,
+    /// Owner of the cluster when the tests run as root, which PostgreSQL
+    /// refuses to run as.
+    user: Option<(u32, u32)>,
+}
+
+fn id(args: &[&str]) -> Option<u32> {
+    let output = Command::new("id").args(args).output().ok()?;
+    String::from_utf8(output.stdout).ok()?.trim().parse().ok()
+}
+
+impl TestCluster {
+    fn start() -> Option<Self> {
+        let user = match id(&["-u"]) {
+            Some(0) => Some((id(&["-u", "postgres"])?, id(&["-g", "postgres"])?)),
+            _ => None,
+        };
+        let port = TcpListener::bind("127.0.0.1:0")
+            .ok()?
+            .local_addr()
+            .ok()?
+            .port();
+        let cluster = TestCluster {
+            dir: std::env::temp_dir().join(format!("data-dash-pg-{}", std::process::id())),
+            port,
+            user,
+        };
+        let data = cluster.dir.to_str()?.to_string();
+        cluster
+            .run("initdb", &["-D", &data, "-U", "postgres", "-A", "trust"])
+            .then_some(())?;
+        let options = format!("-p {} -k {} -c listen_addresses=127.0.0.1", port, data);
+        let log = format!("{}/server.log", data);
+        assert!(
+            cluster.run(
+                "pg_ctl",
+                &["-D", &data, "-o", &options, "-l", &log, "-w", "start"]
+            ),
+            "pg_ctl failed to start the test cluster, see {}",
+            log
+        );
+        Some(cluster)
+    }
+
+    fn run(&self, program: &str, args: &[&str]) -> bool {
+        let mut command = Command::new(program);
+        command.args(args).env("LC_ALL", "C");
+        if let Some((uid, gid)) = self.user {
+            command.uid(uid).gid(gid);
+        }
+        matches!(command.output(), Ok(output) if output.status.success())
+    }
+
+    fn url(&self) -> String {
+        format!("postgres://postgres@127.0.0.1:{}/postgres", self.port)
+    }
+}
+
+impl Drop for TestCluster {
+    fn drop(&mut self) {
+        if let Some(data) = self.dir.to_str() {
+            self.run("pg_ctl", &["-D", data, "-m", "immediate", "stop"]);
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A fresh database on a PostgreSQL server, so runs do not see each other's
+/// data.
+async fn postgres_database(server_url: &str) -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!(
+        "data_dash_test_{}_{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::SeqCst)
+    );
+    let pool = PgPool::connect(server_url).await.unwrap();
+    sqlx::query(&format!("CREATE DATABASE {}", name))
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (base, _) = server_url.rsplit_once('/').unwrap();
+    format!("{}/{}", base, name)
+}
+
+async fn run_suite(url: &str) {
+    let db = Db::new(url).await.unwrap();
+    results(&db).await;
+    data_sources(&db).await;
+    runs_and_alerts(&db).await;
+    notifier_channels(&db).await;
+    silences_and_annotations(&db).await;
+    users_and_roles(&db).await;
+    audit_log(&db).await;
+    environments_and_schedules(&db).await;
+    transactions(&db).await;
+
+    // Migrations are applied once.
+    let reopened = Db::new(url).await.unwrap();
+    assert_eq!(reopened.get_secrets().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_sqlite() {
+    let path = std::env::temp_dir().join(format!("data-dash-test-{}.db", std::process::id()));
+    run_suite(&format!("sqlite:{}?mode=rwc", path.display())).await;
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+#[ignore = "needs PostgreSQL: run with --ignored"]
+async fn test_postgres() {
+    let (_cluster, server_url) = match std::env::var("DATA_DASH_TEST_POSTGRES_URL") {
+        Ok(url) => (None, url),
+        Err(_) => {
+            let cluster = TestCluster::start()
+                .expect("PostgreSQL tests need DATA_DASH_TEST_POSTGRES_URL or initdb on PATH");
+            let url = cluster.url();
+            (Some(cluster), url)
+        }
+    };
+    let url = postgres_database(&server_url).await;
+    assert_eq!(Db::new(&url).await.unwrap().backend(), Backend::Postgres);
+    run_suite(&url).await;
+}
+
+#[test]
+fn test_backend_from_url() {
+    assert_eq!(
+        Backend::from_url("sqlite:data_dash.db").unwrap(),
+        Backend::Sqlite
+    );
+    assert_eq!(
+        Backend::from_url("postgresql://dash@db/dash").unwrap(),
+        Backend::Postgres
+    );
+    assert!(Backend::from_url("mysql://dash@db/dash").is_err());
+    assert_eq!(
+        Backend::Postgres.translate("id INTEGER PRIMARY KEY AUTOINCREMENT, n INTEGER"),
+        "id BIGSERIAL PRIMARY KEY, n BIGINT"
+    );
+}
+
+async fn results(db: &Db) {
+    let details = json!({ "rows": 3 });
+    let first = db
+        .save_result("orders", "Success", "ok", Some(&details), None)
+        .await
+        .unwrap();
+    let second = db
+        .save_result("orders", "Failure", "late", None, Some("prod"))
+        .await
+        .unwrap();
+    assert!(second > first);
+
+    let recent = db.get_recent_results(10).await.unwrap();
+    assert_eq!(recent.len(), 2);
+    assert_eq!(recent[0].0, second);
+    assert_eq!(recent[0].4.as_deref(), Some("prod"));
+    let statuses = db.get_latest_check_statuses().await.unwrap();
+    assert_eq!(statuses["orders"].0, "Failure");
+    assert_eq!(
+        db.get_result_check_id(first).await.unwrap().as_deref(),
+        Some("orders")
+    );
+
+    let now = Utc::now();
+    let attempts = [1, 2].map(|attempt| AttemptRecord {
+        attempt,
+        status: "Failure".to_string(),
+        message: format!("attempt {}", attempt),
+        started_at: now,
+        finished_at: now,
+    });
+    db.save_attempts(second, &attempts).await.unwrap();
+    let saved = db.get_attempts(second).await.unwrap();
+    assert_eq!(saved.len(), 2);
+    assert_eq!(saved[1].message, "attempt 2");
+}
+
+async fn data_sources(db: &Db) {
+    db.save_connection_profile("warehouse", "Postgres", "host=db", None, Some("dsn"), None)
+        .await
+        .unwrap();
+    db.save_secret("dsn", "pw").await.unwrap();
+    db.save_secret("token", "t").await.unwrap();
+    db.save_connection_profile(
+        "crm",
+        "openapi",
+        "https://crm.example.com/api",
+        Some("api"),
+        Some("token"),
+        Some("header:X-API-Key"),
+    )
+    .await
+    .unwrap();
+    let profiles = db.get_connection_profiles().await.unwrap();
+    let crm = profiles.iter().find(|p| p.name == "crm").unwrap();
+    assert_eq!(crm.auth.as_deref(), Some("header:X-API-Key"));
+    assert!(db
+        .save_data_source("sales", "missing", "token")
+        .await
+        .is_err());
+    db.save_data_source("sales", "warehouse", "token")
+        .await
+        .unwrap();
+    assert_eq!(
+        db.get_connection_dependents("warehouse").await.unwrap(),
+        ["sales"]
+    );
+    assert_eq!(
+        db.get_secret_dependents("dsn").await.unwrap(),
+        (vec![], vec!["warehouse".to_string()])
+    );
+
+    // The foreign keys refuse to orphan the data source.
+    assert!(db
+        .delete_connection_profile("warehouse", false)
+        .await
+        .is_err());
+
+    // Losing the profile's secret invalidates the data source.
+    db.delete_secret("dsn", false).await.unwrap();
+    assert!(!db.get_data_sources().await.unwrap()[0].3);
+    db.save_secret("dsn", "pw2").await.unwrap();
+    assert!(db.get_data_sources().await.unwrap()[0].3);
+    assert_eq!(db.get_secret("dsn").await.unwrap().as_deref(), Some("pw2"));
+
+    db.save_unix_group("etl", "/etc/etl", "0640").await.unwrap();
+    assert_eq!(db.get_unix_groups().await.unwrap().len(), 1);
+    db.delete_unix_group("etl").await.unwrap();
+    assert!(db.get_unix_groups().await.unwrap().is_empty());
+}
+
+async fn runs_and_alerts(db: &Db) {
+    db.create_run("run-1", "orders", &json!({ "day": 1 }))
+        .await
+        .unwrap();
+    db.mark_run_started("run-1").await.unwrap();
+    assert!(db
+        .finish_run("run-1", "Succeeded", Some(&json!({ "ok": true })), None)
+        .await
+        .unwrap());
+    assert!(!db.finish_run("run-1", "Failed", None, None).await.unwrap());
+    let run = db.get_run("run-1").await.unwrap().unwrap();
+    assert_eq!(run.status, "Succeeded");
+    assert_eq!(run.params, Some(json!({ "day": 1 })));
+    assert!(run.started_at.is_some() && run.finished_at.is_some());
+    db.create_run("run-2", "orders", &json!({})).await.unwrap();
+    assert_eq!(db.fail_interrupted_runs().await.unwrap(), 1);
+
+    db.save_alert_state("orders", "Failing", 2).await.unwrap();
+    assert_eq!(
+        db.get_alert_state("orders").await.unwrap(),
+        Some(("Failing".to_string(), 2))
+    );
+    let alert = db
+        .save_alert("orders", "Failure", "Failure", "late", None)
+        .await
+        .unwrap();
+    db.save_alert_deliveries(alert, &json!(["log"]))
+        .await
+        .unwrap();
+    let alerts = db.get_alerts(Some("orders"), 10).await.unwrap();
+    assert_eq!(alerts[0].deliveries, Some(json!(["log"])));
+    assert!(db.get_alerts(Some("other"), 10).await.unwrap().is_empty());
+}
+
+async fn notifier_channels(db: &Db) {
+    let webhook = WebhookRecord {
+        name: "ops".to_string(),
+        url: "http://hooks.local".to_string(),
+        headers: Some(json!({ "X-Token": "{{secret:token}}" })),
+        payload_template: None,
+        signing_secret_ref: None,
+        max_attempts: 3,
+        enabled: true,
+    };
+    db.save_webhook(&webhook).await.unwrap();
+    assert_eq!(db.get_webhooks().await.unwrap()[0].headers, webhook.headers);
+    db.save_webhook_delivery(
+        "ops",
+        "orders",
+        "Failure",
+        1,
+        Some(500),
+        Some("boom"),
+        false,
+    )
+    .await
+    .unwrap();
+    let deliveries = db.get_webhook_deliveries("ops", 10).await.unwrap();
+    assert_eq!(deliveries[0].status_code, Some(500));
+    assert!(!deliveries[0].delivered);
+
+    let channel = EmailChannelRecord {
+        name: "team".to_string(),
+        host: "smtp.local".to_string(),
+        port: 587,
+        security: "StartTls".to_string(),
+        username: None,
+        password_secret_ref: None,
+        from_address: "dash@local".to_string(),
+        recipients: json!(["team@local"]),
+        send_digest: true,
+        enabled: false,
+    };
+    db.save_email_channel(&channel).await.unwrap();
+    let saved = &db.get_email_channels().await.unwrap()[0];
+    assert_eq!(saved.recipients, channel.recipients);
+    assert!(saved.send_digest && !saved.enabled);
+    db.delete_email_channel("team").await.unwrap();
+    db.delete_webhook("ops").await.unwrap();
+}
+
+async fn silences_and_annotations(db: &Db) {
+    let window = db
+        .create_maintenance_window("upgrade", None, &json!({ "once": {} }), &json!({}))
+        .await
+        .unwrap();
+    assert_eq!(db.get_maintenance_windows().await.unwrap()[0].id, window);
+    db.delete_maintenance_window(window).await.unwrap();
+
+    let until = Utc::now() + Duration::hours(1);
+    let silence = db
+        .create_silence("orders", until, "known", "alice")
+        .await
+        .unwrap();
+    let silences = db.get_silences(None).await.unwrap();
+    assert_eq!(silences[0].id, silence);
+    assert_eq!(silences[0].until.timestamp(), until.timestamp());
+    db.delete_silence(silence).await.unwrap();
+
+    db.acknowledge_check("orders", "alice", Some("on it"))
+        .await
+        .unwrap();
+    assert_eq!(db.get_acknowledgements().await.unwrap().len(), 1);
+    assert!(db.clear_acknowledgement("orders").await.unwrap());
+    assert!(!db.clear_acknowledgement("orders").await.unwrap());
+
+    let (result_id, ..) = db.get_recent_results(1).await.unwrap()[0].clone();
+    db.add_annotation(result_id, "alice", "expected")
+        .await
+        .unwrap();
+    assert_eq!(
+        db.get_annotations(result_id).await.unwrap()[0].text,
+        "expected"
+    );
+}
+
+async fn users_and_roles(db: &Db) {
+    db.save_user("alice", "hash").await.unwrap();
+    assert!(db.set_user_disabled("alice", true).await.unwrap());
+    assert_eq!(
+        db.get_user("alice").await.unwrap(),
+        Some(("hash".to_string(), true))
+    );
+    assert_eq!(db.get_users().await.unwrap().len(), 1);
+
+    let now = Utc::now();
+    db.create_session("old", "alice", now - Duration::minutes(1))
+        .await
+        .unwrap();
+    db.create_session("new", "alice", now + Duration::hours(1))
+        .await
+        .unwrap();
+    db.delete_expired_sessions(now).await.unwrap();
+    assert!(db.get_session("old").await.unwrap().is_none());
+    assert!(db.get_session("new").await.unwrap().is_some());
+
+    let token = ApiTokenRecord {
+        id: "t1".to_string(),
+        name: "ci".to_string(),
+        username: "alice".to_string(),
+        scopes: json!(["read"]),
+        expires_at: None,
+        created_at: now,
+        last_used_at: None,
+    };
+    db.create_api_token(&token, "token-hash").await.unwrap();
+    db.touch_api_token("t1", now).await.unwrap();
+    let saved = db
+        .get_api_token_by_hash("token-hash")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.scopes, json!(["read"]));
+    assert!(saved.last_used_at.is_some());
+    assert_eq!(db.get_api_tokens("alice").await.unwrap().len(), 1);
+    assert!(db.delete_api_token("t1", "alice").await.unwrap());
+
+    // Built-in roles come from the initial migration.
+    assert!(db.get_roles().await.unwrap().iter().any(|r| r.0 == "admin"));
+    db.save_role("auditor", None, &json!(["view"]))
+        .await
+        .unwrap();
+    db.set_user_roles(
+        "alice",
+        &[("auditor".to_string(), json!({ "tags": ["finance"] }))],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        db.get_user_permissions("alice").await.unwrap(),
+        vec![(json!(["view"]), json!({ "tags": ["finance"] }))]
+    );
+    db.delete_role("auditor").await.unwrap();
+    assert!(db.get_user_roles("alice").await.unwrap().is_empty());
+}
+
+async fn audit_log(db: &Db) {
+    let entry = AuditRecord {
+        id: 0,
+        occurred_at: Utc::now(),
+        actor: "alice".to_string(),
+        action: "Update".to_string(),
+        resource_type: "Secret".to_string(),
+        resource_id: "dsn".to_string(),
+        ip: None,
+        before: Some(json!({ "value": "[redacted]" })),
+        after: None,
+    };
+    db.insert_audit_entry(&entry).await.unwrap();
+    let entries = db
+        .get_audit_entries(Some("alice"), None, Some("Secret"), None, None, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].before, entry.before);
+    db.insert_audit_entry(&entry).await.unwrap();
+    let now = Utc::now();
+    let entries = db
+        .get_audit_entries(
+            None,
+            None,
+            None,
+            None,
+            Some(entry.occurred_at),
+            Some(now),
+            1,
+        )
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert!(db
+        .get_audit_entries(None, None, None, None, Some(now), None, 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert!(db.execute(query("DELETE FROM audit_log")).await.is_err());
+    assert!(db
+        .execute(query("UPDATE audit_log SET actor = 'mallory'"))
+        .await
+        .is_err());
+}
+
+async fn transactions(db: &Db) {
+    let failed: Result<()> = db
+        .transaction(|tx| async move {
+            tx.save_environment("staging", None).await?;
+            // Statements within the transaction see its changes.
+            assert!(tx.environment_exists("staging").await?);
+            anyhow::bail!("rolled back")
+        })
+        .await;
+    assert!(failed.is_err());
+    assert!(!db.environment_exists("staging").await.unwrap());
+
+    db.transaction(|tx| async move { tx.save_environment("staging", None).await })
+        .await
+        .unwrap();
+    assert!(db.environment_exists("staging").await.unwrap());
+    db.delete_environment("staging").await.unwrap();
+}
+
+async fn environments_and_schedules(db: &Db) {
+    db.save_environment("prod", Some("Production"))
+        .await
+        .unwrap();
+    assert!(db.environment_exists("prod").await.unwrap());
+    db.save_connection_override(&ConnectionOverrideRecord {
+        profile_name: "warehouse".to_string(),
+        environment: "prod".to_string(),
+        driver: None,
+        connection_string_template: Some("host=prod".to_string()),
+        secret_ref: None,
+    })
+    .await
+    .unwrap();
+    db.save_data_source_override(&DataSourceOverrideRecord {
+        data_source: "sales".to_string(),
+        environment: "prod".to_string(),
+        connection_name: None,
+        secret_key: Some("dsn".to_string()),
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        db.get_connection_overrides(Some("prod"))
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(db.get_data_source_overrides(None).await.unwrap().len(), 1);
+
+    // Overrides go with their environment.
+    db.delete_environment("prod").await.unwrap();
+    assert!(db.get_connection_overrides(None).await.unwrap().is_empty());
+    assert!(db.get_data_source_overrides(None).await.unwrap().is_empty());
+
+    let schedule = ScheduleRecord {
+        name: "morning".to_string(),
+        cron: "0 30 6 * * *".to_string(),
+        checks: vec!["orders".to_string()],
+        check_group: None,
+        environment: None,
+        enabled: false,
+    };
+    db.save_schedule(&schedule).await.unwrap();
+    let saved = &db.get_schedules().await.unwrap()[0];
+    assert_eq!(saved.checks, schedule.checks);
+    assert!(!saved.enabled);
+
+    db.save_check_metadata("orders", &json!({ "owner": "alice" }))
+        .await
+        .unwrap();
+    assert_eq!(
+        db.get_check_metadata().await.unwrap(),
+        vec![("orders".to_string(), json!({ "owner": "alice" }))]
+    );
+}
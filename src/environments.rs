@@ -0,0 +1,107 @@
+//! Named environments (e.g. dev, staging, prod).
+//!
+//! The same checks run against several copies of a warehouse. Connection
+//! profiles and data sources hold the base configuration; an environment
+//! overrides individual fields of them. `ConnectionManager` applies the
+//! overrides of its active environment, chosen at startup with `--env` or
+//! per run, and results record the environment they ran against.
+
+use crate::connections::ConnectionProfile;
+use crate::db::{ConnectionOverrideRecord, DataSourceOverrideRecord};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Environment {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Fields of a connection profile replaced in one environment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionOverride {
+    pub profile_name: String,
+    pub environment: String,
+    pub driver: Option<String>,
+    pub connection_string_template: Option<String>,
+    pub secret_ref: Option<String>,
+}
+
+impl ConnectionOverride {
+    pub fn apply(&self, profile: ConnectionProfile) -> ConnectionProfile {
+        ConnectionProfile {
+            driver: self.driver.clone().unwrap_or(profile.driver),
+            connection_string_template: self
+                .connection_string_template
+                .clone()
+                .unwrap_or(profile.connection_string_template),
+            secret_ref: self.secret_ref.clone().or(profile.secret_ref),
+            ..profile
+        }
+    }
+}
+
+impl From<ConnectionOverrideRecord> for ConnectionOverride {
+    fn from(r: ConnectionOverrideRecord) -> Self {
+        Self {
+            profile_name: r.profile_name,
+            environment: r.environment,
+            driver: r.driver,
+            connection_string_template: r.connection_string_template,
+            secret_ref: r.secret_ref,
+        }
+    }
+}
+
+impl From<&ConnectionOverride> for ConnectionOverrideRecord {
+    fn from(o: &ConnectionOverride) -> Self {
+        Self {
+            profile_name: o.profile_name.clone(),
+            environment: o.environment.clone(),
+            driver: o.driver.clone(),
+            connection_string_template: o.connection_string_template.clone(),
+            secret_ref: o.secret_ref.clone(),
+        }
+    }
+}
+
+/// The connection and secret a data source is bound to in one environment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataSourceOverride {
+    pub data_source: String,
+    pub environment: String,
+    pub connection_name: Option<String>,
+    pub secret_key: Option<String>,
+}
+
+impl DataSourceOverride {
+    /// Applies the override to a `(connection_name, secret_key)` binding.
+    pub fn apply(&self, binding: (String, String)) -> (String, String) {
+        (
+            self.connection_name.clone().unwrap_or(binding.0),
+            self.secret_key.clone().unwrap_or(binding.1),
+        )
+    }
+}
+
+impl From<DataSourceOverrideRecord> for DataSourceOverride {
+    fn from(r: DataSourceOverrideRecord) -> Self {
+        Self {
+            data_source: r.data_source,
+            environment: r.environment,
+            connection_name: r.connection_name,
+            secret_key: r.secret_key,
+        }
+    }
+}
+
+impl From<&DataSourceOverride> for DataSourceOverrideRecord {
+    fn from(o: &DataSourceOverride) -> Self {
+        Self {
+            data_source: o.data_source.clone(),
+            environment: o.environment.clone(),
+            connection_name: o.connection_name.clone(),
+            secret_key: o.secret_key.clone(),
+        }
+    }
+}
@@ -0,0 +1,553 @@
+//! HTTP client bound to an `api` connection profile.
+//!
+//! The profile's rendered connection string is the base URL that request
+//! paths are joined to, and its `auth` field says how its secret is sent:
+//!
+//! - `bearer`: `Authorization: Bearer <secret>`
+//! - `basic` or `basic:<username>`: HTTP basic authentication. Without a
+//!   username the secret holds `username:password`.
+//! - `header:<Name>`: the secret as the value of the header `Name`
+//! - `query:<param>`: the secret as the query parameter `param`
+//!
+//! Requests that fail to connect, time out, or get a 5xx or 429 response are
+//! retried with exponential backoff. Other responses, successful or not, are
+//! returned to the check.
+
+use reqwest::header::{HeaderMap, CONTENT_LENGTH};
+use reqwest::{Method, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HttpError {
+    #[error("Invalid URL {0}")]
+    InvalidUrl(String),
+    #[error("Request to {url} failed: {message}")]
+    Request { url: String, message: String },
+    #[error("Response from {url} exceeds {limit} bytes")]
+    TooLarge { url: String, limit: u64 },
+    #[error("Invalid response body: {0}")]
+    InvalidBody(String),
+}
+
+/// How requests authenticate with the profile's secret.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum HttpAuth {
+    Bearer,
+    Basic { username: Option<String> },
+    Header(String),
+    Query(String),
+}
+
+impl FromStr for HttpAuth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, arg) = match s.split_once(':') {
+            Some((scheme, arg)) => (scheme, Some(arg)),
+            None => (s, None),
+        };
+        match (scheme, arg) {
+            ("bearer", None) => Ok(HttpAuth::Bearer),
+            ("basic", username) => Ok(HttpAuth::Basic {
+                username: username.map(str::to_string),
+            }),
+            ("header", Some(name)) if !name.is_empty() => Ok(HttpAuth::Header(name.to_string())),
+            ("query", Some(param)) if !param.is_empty() => Ok(HttpAuth::Query(param.to_string())),
+            _ => Err(format!(
+                "Invalid auth {}: expected bearer, basic[:username], header:<name> or query:<param>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for HttpAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpAuth::Bearer => write!(f, "bearer"),
+            HttpAuth::Basic { username: None } => write!(f, "basic"),
+            HttpAuth::Basic {
+                username: Some(username),
+            } => write!(f, "basic:{}", username),
+            HttpAuth::Header(name) => write!(f, "header:{}", name),
+            HttpAuth::Query(param) => write!(f, "query:{}", param),
+        }
+    }
+}
+
+impl TryFrom<String> for HttpAuth {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<HttpAuth> for String {
+    fn from(auth: HttpAuth) -> Self {
+        auth.to_string()
+    }
+}
+
+/// Timeouts, retries and size limits of an `HttpClient`.
+#[derive(Debug, Clone)]
+pub struct HttpLimits {
+    /// Time allowed for a single attempt, including reading the body.
+    pub timeout: Duration,
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the second attempt; doubled for every further attempt.
+    pub initial_backoff: Duration,
+    /// Largest response body accepted, in bytes.
+    pub max_response_bytes: u64,
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_response_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+/// A response whose body has been read in full.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, HttpError> {
+        serde_json::from_slice(&self.body).map_err(|e| HttpError::InvalidBody(e.to_string()))
+    }
+}
+
+/// A request under construction, sent with `HttpClient::send`.
+pub struct HttpRequest {
+    method: Method,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+}
+
+impl HttpRequest {
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        Self {
+            method,
+            path: path.into(),
+            query: vec![],
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    pub fn query(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// A JSON body, with the matching content type.
+    pub fn json(self, body: &serde_json::Value) -> Self {
+        let mut request = self.header("Content-Type", "application/json");
+        request.body = Some(body.to_string().into_bytes());
+        request
+    }
+}
+
+/// Sends requests relative to the base URL of an `api` connection profile,
+/// authenticated with its secret.
+#[derive(Clone)]
+pub struct HttpClient {
+    base_url: Url,
+    /// `base_url` as named in errors, logs and `Debug`, without the secret.
+    shown_base_url: Url,
+    auth: Option<(HttpAuth, String)>,
+    limits: HttpLimits,
+    client: reqwest::Client,
+}
+
+impl fmt::Debug for HttpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpClient")
+            .field("base_url", &self.shown_base_url.as_str())
+            .field("auth", &self.auth.as_ref().map(|(auth, _)| auth))
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+impl HttpClient {
+    /// A client for `base_url` sending `secret` as `auth` says. Without an
+    /// auth scheme the secret, if any, is not sent.
+    pub fn new(
+        base_url: &str,
+        auth: Option<HttpAuth>,
+        secret: Option<String>,
+    ) -> Result<Self, HttpError> {
+        let base_url = parse_base_url(base_url)?;
+        Ok(Self {
+            shown_base_url: base_url.clone(),
+            base_url,
+            auth: auth.zip(secret),
+            limits: HttpLimits::default(),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Name `base_url` in errors and logs instead of the actual base URL,
+    /// which may contain the secret.
+    pub fn with_shown_base_url(mut self, base_url: &str) -> Result<Self, HttpError> {
+        self.shown_base_url = parse_base_url(base_url)?;
+        Ok(self)
+    }
+
+    pub fn with_limits(mut self, limits: HttpLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &HttpLimits {
+        &self.limits
+    }
+
+    /// The base URL as shown in errors.
+    pub fn base_url(&self) -> &str {
+        self.shown_base_url.as_str()
+    }
+
+    pub async fn get(&self, path: &str) -> Result<HttpResponse, HttpError> {
+        self.send(HttpRequest::new(Method::GET, path)).await
+    }
+
+    pub async fn send(&self, request: HttpRequest) -> Result<HttpResponse, HttpError> {
+        let path = request.path.trim_start_matches('/');
+        let url = self
+            .base_url
+            .join(path)
+            .map_err(|_| HttpError::InvalidUrl(request.path.clone()))?;
+        let shown_url = self
+            .shown_base_url
+            .join(path)
+            .map_err(|_| HttpError::InvalidUrl(request.path.clone()))?;
+
+        let max_attempts = self.limits.max_attempts.max(1);
+        let mut backoff = self.limits.initial_backoff;
+        for attempt in 1..=max_attempts {
+            let (outcome, retryable) = match self.attempt(&url, &shown_url, &request).await {
+                Ok(response) => {
+                    let retryable = response.status >= 500 || response.status == 429;
+                    (Ok(response), retryable)
+                }
+                Err(e @ HttpError::Request { .. }) => (Err(e), true),
+                Err(e) => (Err(e), false),
+            };
+            if !retryable || attempt == max_attempts {
+                return outcome;
+            }
+            tracing::debug!("Retrying request to {} (attempt {})", shown_url, attempt);
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        unreachable!("the last attempt returns")
+    }
+
+    async fn attempt(
+        &self,
+        url: &Url,
+        shown_url: &Url,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, HttpError> {
+        // Errors name `shown_url`, which has no query string: query
+        // parameters, including a `query:` secret, are only added by the
+        // builder. The URL reqwest keeps in its errors has them, so it is
+        // dropped.
+        let request_error = |e: reqwest::Error| HttpError::Request {
+            url: shown_url.to_string(),
+            message: e.without_url().to_string(),
+        };
+        let too_large = || HttpError::TooLarge {
+            url: shown_url.to_string(),
+            limit: self.limits.max_response_bytes,
+        };
+
+        let mut builder = self
+            .client
+            .request(request.method.clone(), url.clone())
+            .timeout(self.limits.timeout)
+            .query(&request.query);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+        builder = match &self.auth {
+            None => builder,
+            Some((HttpAuth::Bearer, secret)) => builder.bearer_auth(secret),
+            Some((
+                HttpAuth::Basic {
+                    username: Some(user),
+                },
+                secret,
+            )) => builder.basic_auth(user, Some(secret)),
+            Some((HttpAuth::Basic { username: None }, secret)) => match secret.split_once(':') {
+                Some((user, password)) => builder.basic_auth(user, Some(password)),
+                None => builder.basic_auth(secret, None::<&str>),
+            },
+            Some((HttpAuth::Header(name), secret)) => builder.header(name, secret),
+            Some((HttpAuth::Query(param), secret)) => builder.query(&[(param, secret)]),
+        };
+
+        let mut response = builder.send().await.map_err(request_error)?;
+        let declared = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+        if declared.is_some_and(|len| len > self.limits.max_response_bytes) {
+            return Err(too_large());
+        }
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(request_error)? {
+            if (body.len() + chunk.len()) as u64 > self.limits.max_response_bytes {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+fn parse_base_url(base_url: &str) -> Result<Url, HttpError> {
+    // A trailing slash makes relative paths extend the base path instead of
+    // replacing its last segment.
+    let mut url = Url::parse(base_url).map_err(|_| HttpError::InvalidUrl(base_url.to_string()))?;
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Request, State};
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use std::sync::{Arc, Mutex};
+
+    /// Requests seen by the stand-in server, as (path and query, headers).
+    #[derive(Default)]
+    struct Received {
+        requests: Mutex<Vec<(String, HeaderMap)>>,
+        failures_left: Mutex<u32>,
+    }
+
+    async fn receive(
+        State(received): State<Arc<Received>>,
+        request: Request,
+    ) -> (StatusCode, String) {
+        received
+            .requests
+            .lock()
+            .unwrap()
+            .push((request.uri().to_string(), request.headers().clone()));
+        {
+            let mut failures_left = received.failures_left.lock().unwrap();
+            if *failures_left > 0 {
+                *failures_left -= 1;
+                return (StatusCode::SERVICE_UNAVAILABLE, String::new());
+            }
+        }
+        match request.uri().path() {
+            "/api/v3/pets" => (StatusCode::OK, r#"[{"id": 1, "name": "Rex"}]"#.to_string()),
+            "/api/v3/large" => (StatusCode::OK, "x".repeat(2048)),
+            "/api/v3/slow" => {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                (StatusCode::OK, String::new())
+            }
+            _ => (StatusCode::NOT_FOUND, String::new()),
+        }
+    }
+
+    async fn stand_in(failures: u32) -> (String, Arc<Received>) {
+        let received = Arc::new(Received::default());
+        *received.failures_left.lock().unwrap() = failures;
+        let app = Router::new()
+            .fallback(get(receive))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/v3", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, received)
+    }
+
+    fn fast_limits() -> HttpLimits {
+        HttpLimits {
+            timeout: Duration::from_millis(500),
+            initial_backoff: Duration::from_millis(1),
+            ..HttpLimits::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_auth() {
+        for spec in [
+            "bearer",
+            "basic",
+            "basic:alice",
+            "header:X-API-Key",
+            "query:api_key",
+        ] {
+            assert_eq!(spec.parse::<HttpAuth>().unwrap().to_string(), spec);
+        }
+        assert!("header".parse::<HttpAuth>().is_err());
+        assert!("query:".parse::<HttpAuth>().is_err());
+        assert!("digest".parse::<HttpAuth>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_auth_schemes() {
+        let (url, received) = stand_in(0).await;
+        let cases = [
+            (HttpAuth::Bearer, "s3cret", "authorization", "Bearer s3cret"),
+            (
+                HttpAuth::Basic {
+                    username: Some("alice".to_string()),
+                },
+                "pw",
+                "authorization",
+                "Basic YWxpY2U6cHc=",
+            ),
+            (
+                HttpAuth::Basic { username: None },
+                "alice:pw",
+                "authorization",
+                "Basic YWxpY2U6cHc=",
+            ),
+            (
+                HttpAuth::Header("X-API-Key".to_string()),
+                "k3y",
+                "x-api-key",
+                "k3y",
+            ),
+        ];
+        for (auth, secret, header, expected) in cases {
+            let client = HttpClient::new(&url, Some(auth), Some(secret.to_string())).unwrap();
+            let response = client.get("pets").await.unwrap();
+            assert!(response.is_success());
+            let (_, headers) = received.requests.lock().unwrap().pop().unwrap();
+            assert_eq!(headers[header], expected);
+        }
+
+        let client = HttpClient::new(
+            &url,
+            Some(HttpAuth::Query("api_key".to_string())),
+            Some("k3y".to_string()),
+        )
+        .unwrap();
+        let response = client
+            .send(HttpRequest::new(Method::GET, "/pets").query("status", "sold"))
+            .await
+            .unwrap();
+        let pets: serde_json::Value = response.json().unwrap();
+        assert_eq!(pets[0]["name"], "Rex");
+        let (uri, headers) = received.requests.lock().unwrap().pop().unwrap();
+        assert_eq!(uri, "/api/v3/pets?status=sold&api_key=k3y");
+        assert!(!headers.contains_key("authorization"));
+    }
+
+    #[tokio::test]
+    async fn test_retries_and_limits() {
+        let (url, received) = stand_in(2).await;
+        let client = HttpClient::new(&url, None, None)
+            .unwrap()
+            .with_limits(fast_limits());
+        let response = client.get("pets").await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(received.requests.lock().unwrap().len(), 3);
+
+        let (url, received) = stand_in(3).await;
+        let client = HttpClient::new(&url, None, None)
+            .unwrap()
+            .with_limits(fast_limits());
+        assert_eq!(client.get("pets").await.unwrap().status, 503);
+        assert_eq!(received.requests.lock().unwrap().len(), 3);
+
+        // Client errors are returned, not retried.
+        let response = client.get("missing").await.unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(received.requests.lock().unwrap().len(), 4);
+
+        let client = client.with_limits(HttpLimits {
+            max_response_bytes: 1024,
+            max_attempts: 1,
+            ..fast_limits()
+        });
+        assert!(matches!(
+            client.get("large").await,
+            Err(HttpError::TooLarge { limit: 1024, .. })
+        ));
+        assert!(matches!(
+            client.get("slow").await,
+            Err(HttpError::Request { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_errors_do_not_leak_query_secret() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api/v3", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = HttpClient::new(
+            &url,
+            Some(HttpAuth::Query("api_key".to_string())),
+            Some("k3y".to_string()),
+        )
+        .unwrap()
+        .with_limits(HttpLimits {
+            max_attempts: 1,
+            ..fast_limits()
+        });
+        let error = client
+            .send(HttpRequest::new(Method::GET, "pets").query("status", "sold"))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HttpError::Request { .. }));
+        let message = error.to_string();
+        assert!(message.contains("/api/v3/pets"), "{}", message);
+        assert!(!message.contains("k3y"), "{}", message);
+    }
+}
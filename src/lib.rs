@@ -1,5 +1,16 @@
+pub mod alerting;
 pub mod api;
+pub mod audit;
+pub mod auth;
 pub mod checks;
 pub mod connections;
 pub mod db;
+pub mod environments;
+pub mod http_client;
+pub mod logger;
+pub mod maintenance;
+pub mod manifest;
+pub mod runner;
+pub mod scheduler;
 pub mod secrets;
+pub mod settings;
@@ -1,52 +1,341 @@
 use acme_data_dash::{
+    alerting::{email::EmailChannels, webhook::WebhookChannels, AlertManager, LogNotifier},
     api::{app_router, AppState},
-    checks::{example_check::ExampleCheck, DataCheck, StandardCheckContext},
+    audit::Actor,
+    auth::{self, Authenticator, LocalIdentityBackend},
+    checks::{
+        example_check::ExampleCheck, file::FileCheck, registry::CheckRegistry, StandardCheckContext,
+    },
     connections::ConnectionManager,
     db::Db,
+    logger,
+    manifest::{self, Format, Manifest},
+    runner::{runs::RunManager, CheckRunner},
+    scheduler::Scheduler,
+    secrets::{DbSecretStore, EnvVarSecretStore, SecretStore},
+    settings::{Override, SecretBackend, Settings},
 };
-use std::collections::HashMap;
+use clap::{Parser, Subcommand};
+use std::io::BufRead;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tower_http::services::ServeDir;
 use tracing::info;
 
+/// Flags override the settings file and `DATA_DASH_*` environment variables.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Settings file (TOML). Defaults to `$DATA_DASH_CONFIG`, then
+    /// `data_dash.toml` if it exists.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Address the HTTP server listens on. Non-loopback addresses require `--auth`.
+    #[arg(long)]
+    bind: Option<SocketAddr>,
+
+    /// Require a login session or API token for every API request.
+    #[arg(long)]
+    auth: bool,
+
+    /// Environment whose connection and data source overrides checks use
+    /// unless a run asks for another one.
+    #[arg(long = "env")]
+    environment: Option<String>,
+
+    #[arg(long)]
+    database_url: Option<String>,
+
+    #[arg(long)]
+    log_level: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Cli {
+    fn overrides(&self) -> Vec<Override> {
+        use toml::Value;
+        let mut overrides = Vec::new();
+        if let Some(bind) = self.bind {
+            overrides.push(("server.bind", Value::String(bind.to_string())));
+        }
+        if self.auth {
+            overrides.push(("server.auth", Value::Boolean(true)));
+        }
+        if let Some(environment) = &self.environment {
+            overrides.push(("server.environment", Value::String(environment.clone())));
+        }
+        if let Some(url) = &self.database_url {
+            overrides.push(("database.url", Value::String(url.clone())));
+        }
+        if let Some(level) = &self.log_level {
+            overrides.push(("logging.level", Value::String(level.clone())));
+        }
+        overrides
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a local user, or reset its password. The password is read from stdin.
+    AddUser {
+        username: String,
+        /// Roles granted without scope restrictions; replaces existing roles.
+        #[arg(long = "role")]
+        roles: Vec<String>,
+    },
+    /// Create or upgrade the database schema, then exit.
+    Migrate,
+    /// Export or apply configuration files.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective settings, with credentials masked.
+    Show,
+    /// Print the current connections, data sources, unix groups, schedules
+    /// and check metadata. Secret values are not included.
+    Export {
+        #[arg(long, default_value = "toml", value_parser = ["toml", "yaml"])]
+        format: String,
+        /// Write to this file instead of stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Create and update what the file declares, keeping everything else.
+    Import {
+        file: PathBuf,
+        /// Only print the changes.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Make the configuration match the file, deleting what it does not declare.
+    Apply {
+        file: PathBuf,
+        /// Only print the changes.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+fn register_checks() -> anyhow::Result<CheckRegistry> {
+    let mut checks = CheckRegistry::new();
+    checks.register(Arc::new(ExampleCheck))?;
+    checks.register(Arc::new(
+        FileCheck::new("daily_report_export", "CSV Data Export")
+            .with_description("The daily report export is delivered and not empty")
+            .min_size(1)
+            .max_age(Duration::from_secs(26 * 3600)),
+    ))?;
+    Ok(checks)
+}
+
+/// Replaces the metadata of registered checks by the metadata stored
+/// through configuration files.
+async fn load_check_metadata(db: &Db, checks: &mut CheckRegistry) -> anyhow::Result<()> {
+    for (id, metadata) in db.get_check_metadata().await? {
+        if !checks.set_metadata(&id, serde_json::from_value(metadata)?) {
+            tracing::warn!("Ignoring configured metadata of unknown check {}", id);
+        }
+    }
+    Ok(())
+}
+
+async fn run_config_command(
+    db: &Db,
+    checks: &CheckRegistry,
+    command: ConfigCommand,
+) -> anyhow::Result<()> {
+    let (file, dry_run, prune) = match command {
+        ConfigCommand::Show => unreachable!("handled before connecting to the database"),
+        ConfigCommand::Export { format, output } => {
+            let text = Manifest::export(db, checks)
+                .await?
+                .render(Format::from_name(&format)?)?;
+            match output {
+                Some(path) => std::fs::write(path, text)?,
+                None => print!("{}", text),
+            }
+            return Ok(());
+        }
+        ConfigCommand::Import { file, dry_run } => (file, dry_run, false),
+        ConfigCommand::Apply { file, dry_run } => (file, dry_run, true),
+    };
+
+    let current = Manifest::load(db).await?;
+    let file = Manifest::read(&file)?;
+    let desired = if prune {
+        file
+    } else {
+        current.clone().merge(&file)
+    };
+    desired.validate(db, checks).await?;
+
+    let changes = manifest::plan(&current, &desired);
+    if changes.is_empty() {
+        println!("No changes");
+        return Ok(());
+    }
+    for change in &changes {
+        println!("{}", change);
+    }
+    if dry_run {
+        return Ok(());
+    }
+    let actor = Actor {
+        username: std::env::var("USER").unwrap_or_else(|_| "config".to_string()),
+        ip: None,
+    };
+    manifest::apply(db, &changes, &actor).await?;
+    println!("Applied {} changes", changes.len());
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+    let settings = Settings::load(cli.config.as_deref(), cli.overrides())?;
 
-    info!("Starting Acme Data Dash...");
+    if let Some(Command::Config {
+        command: ConfigCommand::Show,
+    }) = cli.command
+    {
+        print!("{}", settings.to_masked_toml()?);
+        return settings.validate();
+    }
+    settings.validate()?;
+
+    // Initialize logging
+    logger::init(&settings.logging)?;
 
     // 1. Initialize DB
-    let db = Db::new("sqlite:data_dash.db").await?;
+    let db = Db::new(&settings.database.url).await?;
+    if let Some(Command::Migrate) = cli.command {
+        println!("Database is up to date");
+        return Ok(());
+    }
+    let mut checks = register_checks()?;
+
+    if let Some(Command::Config { command }) = cli.command {
+        return run_config_command(&db, &checks, command).await;
+    }
+    if let Some(Command::AddUser { username, roles }) = cli.command {
+        let mut password = String::new();
+        std::io::stdin().lock().read_line(&mut password)?;
+        let password = password.trim_end_matches(['\r', '\n']);
+        anyhow::ensure!(!password.is_empty(), "Password must not be empty");
+        db.save_user(&username, &auth::hash_password(password)?)
+            .await?;
+        if !roles.is_empty() {
+            let global: Vec<_> = roles
+                .into_iter()
+                .map(|role| (role, serde_json::json!({})))
+                .collect();
+            db.set_user_roles(&username, &global).await?;
+        }
+        println!("Saved user {}", username);
+        return Ok(());
+    }
+
+    info!("Starting Acme Data Dash...");
 
     // 2. Initialize Secrets
-    // Using DbSecretStore to allow UI configuration
-    let secret_store = Arc::new(acme_data_dash::secrets::DbSecretStore::new(db.clone()));
+    let secret_store: Arc<dyn SecretStore> = match settings.secret_store.backend {
+        // Secrets can then be managed in the UI
+        SecretBackend::Database => Arc::new(DbSecretStore::new(db.clone())),
+        SecretBackend::Env => Arc::new(EnvVarSecretStore),
+    };
 
     // 3. Initialize Connections
-    let connection_manager = Arc::new(ConnectionManager::new(db.clone(), secret_store));
+    let mut connection_manager = ConnectionManager::new(db.clone(), secret_store.clone());
+    if let Some(environment) = &settings.server.environment {
+        anyhow::ensure!(
+            db.environment_exists(environment).await?,
+            "Unknown environment: {}",
+            environment
+        );
+        info!("Using environment {}", environment);
+        connection_manager = connection_manager.in_environment(environment);
+    }
+    let connection_manager = Arc::new(connection_manager);
     let check_context = Arc::new(StandardCheckContext { connection_manager });
 
-    // 4. Register Checks
-    let mut checks: HashMap<String, Arc<dyn DataCheck>> = HashMap::new();
-    let example = Arc::new(ExampleCheck);
-    checks.insert(example.id().to_string(), example);
+    // 4. Apply configured check metadata
+    load_check_metadata(&db, &mut checks).await?;
+
+    // 5. Build Runner
+    let email = Arc::new(EmailChannels::new(db.clone(), secret_store.clone()));
+    let alerts = Arc::new(
+        AlertManager::new(db.clone(), settings.alerting.rules())
+            .with_notifier(Arc::new(LogNotifier))
+            .with_notifier(Arc::new(WebhookChannels::new(db.clone(), secret_store)))
+            .with_notifier(email.clone()),
+    );
+    let runner = Arc::new(
+        CheckRunner::new(db.clone(), check_context)
+            .with_alerts(alerts)
+            .with_limits(settings.scheduler.limits()),
+    );
+    let runs = Arc::new(RunManager::new(db.clone(), runner.clone()));
+    runs.recover().await?;
+
+    let check_ids = checks.values().map(|c| c.id().to_string()).collect();
+    tokio::spawn(email.run_daily_digest(check_ids, settings.alerting.digest_time()?));
+    if settings.scheduler.enabled {
+        let scheduled = checks.values().cloned().collect();
+        let tick = Duration::from_secs(settings.scheduler.tick_secs);
+        tokio::spawn(
+            Scheduler::new(db.clone(), runner.clone(), scheduled)
+                .with_tick(tick)
+                .run(),
+        );
+    }
 
-    // 5. Build App State
+    // 6. Build App State
+    let auth = if settings.server.auth {
+        if db.get_users().await?.is_empty() {
+            tracing::warn!(
+                "Authentication is enabled but no users exist; create one with `add-user`"
+            );
+        }
+        let backend = Arc::new(LocalIdentityBackend::new(db.clone()));
+        // Off loopback the cookie crosses a network: only send it over HTTPS.
+        let secure_cookie = !settings.server.bind.ip().is_loopback();
+        Some(Arc::new(
+            Authenticator::new(db.clone(), backend).with_secure_cookie(secure_cookie),
+        ))
+    } else {
+        tracing::warn!("Authentication is disabled");
+        None
+    };
     let state = Arc::new(AppState {
         checks,
-        check_context,
+        runner,
+        runs,
         db,
+        auth,
     });
 
-    // 6. Start Server
+    // 7. Start Server
     let api = app_router(state);
-    let app = api.nest_service("/", ServeDir::new("ui/dist"));
+    let app = api.nest_service("/", ServeDir::new(&settings.server.ui_dir));
 
-    let listener = TcpListener::bind("0.0.0.0:3000").await?;
+    let listener = TcpListener::bind(settings.server.bind).await?;
     info!("Listening on {}", listener.local_addr()?);
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,364 @@
+//! Maintenance windows and alert silences.
+//!
+//! A maintenance window covers checks by connection, data source or tag.
+//! Checks still run during a window, but their results are recorded as
+//! `Suppressed` (with the original outcome in the details), which neither
+//! raises alerts nor counts as a failure. A silence only mutes alerts for a
+//! single check until a given time; its results are recorded as usual.
+
+use crate::checks::{CheckResult, CheckStatus, DataCheck};
+use crate::connections::DataSourceBindings;
+use crate::db::{Db, MaintenanceWindowRecord, SilenceRecord};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Longest a recurring maintenance window may last.
+const MAX_WINDOW_SECS: u64 = 366 * 24 * 3600;
+
+/// What a maintenance window applies to. A check is covered when it uses
+/// one of the connections or data sources, or carries one of the tags. An
+/// empty scope covers every check.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaintenanceScope {
+    #[serde(default)]
+    pub connections: Vec<String>,
+    #[serde(default)]
+    pub data_sources: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl MaintenanceScope {
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty() && self.data_sources.is_empty() && self.tags.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WindowSchedule {
+    Once {
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+    },
+    /// Starts at every occurrence of a cron expression (with seconds, UTC,
+    /// e.g. `0 0 2 * * Sun`) and lasts `duration_secs`.
+    Recurring { cron: String, duration_secs: u64 },
+}
+
+impl WindowSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            WindowSchedule::Once { starts_at, ends_at } if ends_at <= starts_at => {
+                Err("Maintenance window must end after it starts".to_string())
+            }
+            WindowSchedule::Once { .. } => Ok(()),
+            WindowSchedule::Recurring {
+                cron,
+                duration_secs,
+            } => {
+                Schedule::from_str(cron).map_err(|e| format!("Invalid cron expression: {}", e))?;
+                if *duration_secs == 0 {
+                    return Err("Recurring maintenance window needs a duration".to_string());
+                }
+                if *duration_secs > MAX_WINDOW_SECS {
+                    return Err(format!(
+                        "Recurring maintenance window may last at most {} seconds",
+                        MAX_WINDOW_SECS
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        match self {
+            WindowSchedule::Once { starts_at, ends_at } => *starts_at <= now && now < *ends_at,
+            WindowSchedule::Recurring {
+                cron,
+                duration_secs,
+            } => {
+                let Ok(schedule) = Schedule::from_str(cron) else {
+                    return false;
+                };
+                // Active if an occurrence started within the last `duration`.
+                // Durations too large for chrono, which `validate` rejects,
+                // never match rather than panic.
+                let Some(duration) = i64::try_from(*duration_secs)
+                    .ok()
+                    .and_then(ChronoDuration::try_seconds)
+                else {
+                    return false;
+                };
+                let Some(since) = now
+                    .checked_sub_signed(duration)
+                    .and_then(|t| t.checked_sub_signed(ChronoDuration::seconds(1)))
+                else {
+                    return false;
+                };
+                schedule.after(&since).next().is_some_and(|start| {
+                    start <= now
+                        && start
+                            .checked_add_signed(duration)
+                            .is_some_and(|end| now < end)
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub name: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub schedule: WindowSchedule,
+    #[serde(default)]
+    pub scope: MaintenanceScope,
+}
+
+impl MaintenanceWindow {
+    /// Whether the window covers `check`.
+    pub fn applies_to(&self, check: &dyn DataCheck, bindings: &DataSourceBindings) -> bool {
+        if self.scope.is_empty() {
+            return true;
+        }
+        let connections = bindings.connections_of(check);
+        let data_sources = bindings.data_sources_of(check);
+
+        self.scope
+            .connections
+            .iter()
+            .any(|name| connections.contains(name))
+            || self
+                .scope
+                .data_sources
+                .iter()
+                .any(|name| data_sources.contains(name))
+            || check
+                .metadata()
+                .tags
+                .iter()
+                .any(|tag| self.scope.tags.contains(tag))
+    }
+
+    /// The result recorded in place of `result` while this window is active.
+    pub fn suppress(&self, result: &CheckResult) -> CheckResult {
+        CheckResult {
+            status: CheckStatus::Suppressed,
+            message: format!("[maintenance: {}] {}", self.name, result.message),
+            details: Some(serde_json::json!({
+                "maintenance_window": self.name,
+                "reason": self.reason,
+                "original_status": result.status,
+                "original_details": result.details,
+            })),
+        }
+    }
+}
+
+impl TryFrom<MaintenanceWindowRecord> for MaintenanceWindow {
+    type Error = String;
+
+    fn try_from(r: MaintenanceWindowRecord) -> Result<Self, Self::Error> {
+        Ok(MaintenanceWindow {
+            id: Some(r.id),
+            name: r.name,
+            reason: r.reason,
+            schedule: serde_json::from_value(r.schedule).map_err(|e| e.to_string())?,
+            scope: serde_json::from_value(r.scope).map_err(|e| e.to_string())?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Silence {
+    pub id: i64,
+    pub check_id: String,
+    pub until: DateTime<Utc>,
+    pub reason: String,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<SilenceRecord> for Silence {
+    fn from(r: SilenceRecord) -> Self {
+        Silence {
+            id: r.id,
+            check_id: r.check_id,
+            until: r.until,
+            reason: r.reason,
+            author: r.author,
+            created_at: r.created_at,
+        }
+    }
+}
+
+/// Looks up the windows and silences that apply to a check.
+#[derive(Clone)]
+pub struct Maintenance {
+    db: Db,
+}
+
+impl Maintenance {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// The first maintenance window covering `check` at `now`, if any.
+    pub async fn active_window(
+        &self,
+        check: &dyn DataCheck,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<MaintenanceWindow>> {
+        let windows: Vec<MaintenanceWindow> = self
+            .db
+            .get_maintenance_windows()
+            .await?
+            .into_iter()
+            .filter_map(|r| {
+                let name = r.name.clone();
+                MaintenanceWindow::try_from(r)
+                    .map_err(|e| tracing::error!("Invalid maintenance window {}: {}", name, e))
+                    .ok()
+            })
+            .filter(|w| w.schedule.is_active(now))
+            .collect();
+        if windows.is_empty() {
+            return Ok(None);
+        }
+
+        let bindings = DataSourceBindings::load(&self.db).await?;
+        Ok(windows.into_iter().find(|w| w.applies_to(check, &bindings)))
+    }
+
+    /// The silence muting alerts for `check_id` at `now`, if any.
+    pub async fn active_silence(
+        &self,
+        check_id: &str,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<Silence>> {
+        Ok(self
+            .db
+            .get_silences(Some(check_id))
+            .await?
+            .into_iter()
+            .map(Silence::from)
+            .find(|s| now < s.until))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::testing::{Behavior, ScriptedCheck};
+    use std::collections::HashMap;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn window(scope: MaintenanceScope) -> MaintenanceWindow {
+        MaintenanceWindow {
+            id: None,
+            name: "upgrade".to_string(),
+            reason: None,
+            schedule: WindowSchedule::Once {
+                starts_at: at("2024-05-01T00:00:00Z"),
+                ends_at: at("2024-05-01T04:00:00Z"),
+            },
+            scope,
+        }
+    }
+
+    #[test]
+    fn test_recurring_window() {
+        let schedule = WindowSchedule::Recurring {
+            cron: "0 0 2 * * Sun".to_string(),
+            duration_secs: 3600,
+        };
+        assert!(schedule.validate().is_ok());
+        // 2024-05-05 is a Sunday.
+        assert!(schedule.is_active(at("2024-05-05T02:00:00Z")));
+        assert!(schedule.is_active(at("2024-05-05T02:59:59Z")));
+        assert!(!schedule.is_active(at("2024-05-05T03:00:00Z")));
+        assert!(!schedule.is_active(at("2024-05-06T02:30:00Z")));
+    }
+
+    #[test]
+    fn test_huge_window_duration() {
+        for duration_secs in [10_000_000_000_000_000, u64::MAX] {
+            let schedule = WindowSchedule::Recurring {
+                cron: "0 0 2 * * Sun".to_string(),
+                duration_secs,
+            };
+            assert!(schedule.validate().is_err());
+            assert!(!schedule.is_active(at("2024-05-05T02:00:00Z")));
+        }
+    }
+
+    #[test]
+    fn test_scope_matching() {
+        let check = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Success))
+            .with_connections(&["warehouse"])
+            .with_tags(&["finance"]);
+        let data_sources =
+            DataSourceBindings::new(HashMap::from([("dw".to_string(), "warehouse".to_string())]));
+        let by_connection = window(MaintenanceScope {
+            connections: vec!["warehouse".to_string()],
+            ..Default::default()
+        });
+        let by_data_source = window(MaintenanceScope {
+            data_sources: vec!["dw".to_string()],
+            ..Default::default()
+        });
+        let by_tag = window(MaintenanceScope {
+            tags: vec!["finance".to_string()],
+            ..Default::default()
+        });
+        let other = window(MaintenanceScope {
+            connections: vec!["oracle".to_string()],
+            tags: vec!["marketing".to_string()],
+            ..Default::default()
+        });
+
+        assert!(by_connection.applies_to(&check, &data_sources));
+        assert!(by_data_source.applies_to(&check, &data_sources));
+        assert!(by_tag.applies_to(&check, &data_sources));
+        assert!(!other.applies_to(&check, &data_sources));
+        assert!(window(MaintenanceScope::default()).applies_to(&check, &data_sources));
+    }
+
+    #[tokio::test]
+    async fn test_active_silence_expires() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let now = Utc::now();
+        db.create_silence(
+            "orders",
+            now + ChronoDuration::hours(1),
+            "vendor delay",
+            "alice",
+        )
+        .await
+        .unwrap();
+        let maintenance = Maintenance::new(db);
+
+        let silence = maintenance.active_silence("orders", now).await.unwrap();
+        assert_eq!(silence.unwrap().author, "alice");
+        assert!(maintenance
+            .active_silence("orders", now + ChronoDuration::hours(2))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(maintenance
+            .active_silence("other", now)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,599 @@
+//! Configuration as code.
+//!
+//! A manifest is a TOML or YAML file declaring connection profiles, data
+//! sources, unix groups, check schedules and check metadata, so the setup
+//! can live in git and be reviewed like code. Secret values are never part
+//! of it; data sources and profiles refer to secrets by key.
+//!
+//! `config export` writes the current configuration as a manifest.
+//! `config import` creates and updates what a manifest declares and leaves
+//! everything else alone; `config apply` also deletes what it does not
+//! declare, so the database ends up matching the file. Both show the
+//! changes first and only print them with `--dry-run`.
+
+use crate::audit::{diff, Actor, AuditAction, AuditEvent, AuditLog, FieldChange, ResourceType};
+use crate::checks::registry::CheckRegistry;
+use crate::checks::CheckMetadata;
+use crate::connections::ConnectionProfile;
+use crate::db::Db;
+use crate::scheduler::CheckSchedule;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "toml" => Ok(Format::Toml),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            other => bail!("Unknown config format: {}", other),
+        }
+    }
+
+    /// The format implied by a file extension; TOML unless it is `.yaml` or
+    /// `.yml`.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => Format::Yaml,
+            _ => Format::Toml,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DataSourceEntry {
+    pub name: String,
+    pub connection_name: String,
+    pub secret_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnixGroupEntry {
+    pub group_name: String,
+    pub file_path: String,
+    pub permissions: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub connections: Vec<ConnectionProfile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub data_sources: Vec<DataSourceEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unix_groups: Vec<UnixGroupEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<CheckSchedule>,
+    /// Metadata by check id, replacing what the check declares in code.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<String, CheckMetadata>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str, format: Format) -> Result<Self> {
+        Ok(match format {
+            Format::Toml => toml::from_str(text)?,
+            Format::Yaml => serde_yaml::from_str(text)?,
+        })
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        Self::parse(&text, Format::from_path(path))
+            .with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn render(&self, format: Format) -> Result<String> {
+        Ok(match format {
+            Format::Toml => toml::to_string(self)?,
+            Format::Yaml => serde_yaml::to_string(self)?,
+        })
+    }
+
+    /// The configuration stored in the database. `checks` holds only the
+    /// metadata set through configuration.
+    pub async fn load(db: &Db) -> Result<Self> {
+        let connections = db
+            .get_connection_profiles()
+            .await?
+            .into_iter()
+            .map(ConnectionProfile::from)
+            .collect();
+        let data_sources = db
+            .get_data_sources()
+            .await?
+            .into_iter()
+            .map(|(name, connection_name, secret_key, _)| DataSourceEntry {
+                name,
+                connection_name,
+                secret_key,
+            })
+            .collect();
+        let mut unix_groups: Vec<_> = db
+            .get_unix_groups()
+            .await?
+            .into_iter()
+            .map(|(group_name, file_path, permissions)| UnixGroupEntry {
+                group_name,
+                file_path,
+                permissions,
+            })
+            .collect();
+        unix_groups.sort_by(|a, b| a.group_name.cmp(&b.group_name));
+        let schedules = db
+            .get_schedules()
+            .await?
+            .into_iter()
+            .map(CheckSchedule::from)
+            .collect();
+        let mut checks = BTreeMap::new();
+        for (id, metadata) in db.get_check_metadata().await? {
+            checks.insert(id, serde_json::from_value(metadata)?);
+        }
+        Ok(Self {
+            connections,
+            data_sources,
+            unix_groups,
+            schedules,
+            checks,
+        })
+    }
+
+    /// The stored configuration plus the metadata every other registered
+    /// check declares in code, for `config export`.
+    pub async fn export(db: &Db, registry: &CheckRegistry) -> Result<Self> {
+        let mut manifest = Self::load(db).await?;
+        for check in registry.values() {
+            manifest
+                .checks
+                .entry(check.id().to_string())
+                .or_insert_with(|| check.metadata());
+        }
+        Ok(manifest)
+    }
+
+    /// `self` with the entries of `other` added, replacing entries of the
+    /// same name.
+    pub fn merge(mut self, other: &Manifest) -> Self {
+        fn upsert<T: Clone>(items: &mut Vec<T>, new: &[T], key: impl Fn(&T) -> &str) {
+            for item in new {
+                match items.iter_mut().find(|i| key(i) == key(item)) {
+                    Some(existing) => *existing = item.clone(),
+                    None => items.push(item.clone()),
+                }
+            }
+        }
+        upsert(&mut self.connections, &other.connections, |c| &c.name);
+        upsert(&mut self.data_sources, &other.data_sources, |d| &d.name);
+        upsert(&mut self.unix_groups, &other.unix_groups, |g| &g.group_name);
+        upsert(&mut self.schedules, &other.schedules, |s| &s.name);
+        self.checks
+            .extend(other.checks.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self
+    }
+
+    /// Checks that the manifest can be stored as the complete configuration:
+    /// names are unique, data sources refer to declared connections and to
+    /// existing secrets, and schedules and metadata refer to registered
+    /// checks and existing environments.
+    pub async fn validate(&self, db: &Db, registry: &CheckRegistry) -> Result<()> {
+        let mut errors = Vec::new();
+        let secrets: HashSet<String> = db.get_secrets().await?.into_iter().collect();
+        let environments: HashSet<String> = db
+            .get_environments()
+            .await?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+
+        duplicates(
+            "connection",
+            self.connections.iter().map(|c| &c.name),
+            &mut errors,
+        );
+        duplicates(
+            "data source",
+            self.data_sources.iter().map(|d| &d.name),
+            &mut errors,
+        );
+        duplicates(
+            "unix group",
+            self.unix_groups.iter().map(|g| &g.group_name),
+            &mut errors,
+        );
+        duplicates(
+            "schedule",
+            self.schedules.iter().map(|s| &s.name),
+            &mut errors,
+        );
+
+        let connections: HashSet<&str> = self.connections.iter().map(|c| c.name.as_str()).collect();
+        for source in &self.data_sources {
+            if !connections.contains(source.connection_name.as_str()) {
+                errors.push(format!(
+                    "Data source {} uses undeclared connection {}",
+                    source.name, source.connection_name
+                ));
+            }
+            if !secrets.contains(&source.secret_key) {
+                errors.push(format!(
+                    "Data source {} uses unknown secret {}",
+                    source.name, source.secret_key
+                ));
+            }
+        }
+        for schedule in &self.schedules {
+            if let Err(e) = schedule.validate() {
+                errors.push(format!("Schedule {}: {}", schedule.name, e));
+            }
+            for id in &schedule.checks {
+                if registry.get(id).is_none() {
+                    errors.push(format!(
+                        "Schedule {} runs unknown check {}",
+                        schedule.name, id
+                    ));
+                }
+            }
+            if let Some(environment) = &schedule.environment {
+                if !environments.contains(environment) {
+                    errors.push(format!(
+                        "Schedule {} uses unknown environment {}",
+                        schedule.name, environment
+                    ));
+                }
+            }
+        }
+        for id in self.checks.keys() {
+            if registry.get(id).is_none() {
+                errors.push(format!("Metadata for unknown check {}", id));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            bail!("Invalid configuration:\n  {}", errors.join("\n  "))
+        }
+    }
+}
+
+fn duplicates<'a>(what: &str, names: impl Iterator<Item = &'a String>, errors: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(name) {
+            errors.push(format!("Duplicate {} {}", what, name));
+        }
+    }
+}
+
+/// One change needed to turn the stored configuration into the desired one.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub action: AuditAction,
+    pub resource_type: ResourceType,
+    pub name: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl Change {
+    pub fn fields(&self) -> Vec<FieldChange> {
+        diff(self.before.as_ref(), self.after.as_ref())
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = match self.action {
+            AuditAction::Create => '+',
+            AuditAction::Delete => '-',
+            _ => '~',
+        };
+        write!(f, "{} {:?} {}", sign, self.resource_type, self.name)?;
+        if self.action == AuditAction::Update {
+            let show =
+                |v: &Option<Value>| v.as_ref().map_or("(none)".to_string(), Value::to_string);
+            for change in self.fields() {
+                write!(
+                    f,
+                    "\n    {}: {} -> {}",
+                    change.field,
+                    show(&change.before),
+                    show(&change.after)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The changes turning `current` into `desired`, creations and updates
+/// first, then deletions, each in an order that keeps references valid.
+pub fn plan(current: &Manifest, desired: &Manifest) -> Vec<Change> {
+    let mut upserts = Vec::new();
+    let mut deletes = Vec::new();
+    let mut section =
+        |resource_type, current: Vec<(String, Value)>, desired: Vec<(String, Value)>| {
+            let before: BTreeMap<_, _> = current.into_iter().collect();
+            let after: BTreeMap<_, _> = desired.into_iter().collect();
+            for (name, value) in &after {
+                let action = match before.get(name) {
+                    None => AuditAction::Create,
+                    Some(old) if old != value => AuditAction::Update,
+                    Some(_) => continue,
+                };
+                upserts.push(Change {
+                    action,
+                    resource_type,
+                    name: name.clone(),
+                    before: before.get(name).cloned(),
+                    after: Some(value.clone()),
+                });
+            }
+            let removed = before.iter().filter(|(name, _)| !after.contains_key(*name));
+            let removed: Vec<_> = removed
+                .map(|(name, value)| Change {
+                    action: AuditAction::Delete,
+                    resource_type,
+                    name: name.clone(),
+                    before: Some(value.clone()),
+                    after: None,
+                })
+                .collect();
+            // Sections are visited in dependency order; deletions run in reverse.
+            deletes.splice(0..0, removed);
+        };
+
+    fn entries<T: Serialize>(items: &[T], key: impl Fn(&T) -> &str) -> Vec<(String, Value)> {
+        items
+            .iter()
+            .map(|i| (key(i).to_string(), serde_json::json!(i)))
+            .collect()
+    }
+    let checks = |m: &Manifest| -> Vec<(String, Value)> {
+        m.checks
+            .iter()
+            .map(|(id, metadata)| (id.clone(), serde_json::json!(metadata)))
+            .collect()
+    };
+
+    section(
+        ResourceType::Connection,
+        entries(&current.connections, |c| &c.name),
+        entries(&desired.connections, |c| &c.name),
+    );
+    section(
+        ResourceType::DataSource,
+        entries(&current.data_sources, |d| &d.name),
+        entries(&desired.data_sources, |d| &d.name),
+    );
+    section(
+        ResourceType::UnixGroup,
+        entries(&current.unix_groups, |g| &g.group_name),
+        entries(&desired.unix_groups, |g| &g.group_name),
+    );
+    section(
+        ResourceType::Schedule,
+        entries(&current.schedules, |s| &s.name),
+        entries(&desired.schedules, |s| &s.name),
+    );
+    section(ResourceType::Check, checks(current), checks(desired));
+
+    upserts.extend(deletes);
+    upserts
+}
+
+/// Applies planned changes to the database, recording each in the audit
+/// log as made by `actor`. The changes and their audit entries are written
+/// in one transaction: if any change fails, none is applied.
+pub async fn apply(db: &Db, changes: &[Change], actor: &Actor) -> Result<()> {
+    db.transaction(|db| async move {
+        let audit = AuditLog::new(db.clone());
+        for change in changes {
+            apply_change(&db, change)
+                .await
+                .with_context(|| format!("Failed to apply `{}`", change))?;
+            let event = AuditEvent::new(change.action, change.resource_type, &change.name)
+                .before(change.before.clone())
+                .after(change.after.clone());
+            audit.record(actor, event).await?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+async fn apply_change(db: &Db, change: &Change) -> Result<()> {
+    let name = change.name.as_str();
+    let Some(after) = change.after.clone() else {
+        return match change.resource_type {
+            ResourceType::Connection => db.delete_connection_profile(name, false).await,
+            ResourceType::DataSource => db.delete_data_source(name).await,
+            ResourceType::UnixGroup => db.delete_unix_group(name).await,
+            ResourceType::Schedule => db.delete_schedule(name).await,
+            ResourceType::Check => db.delete_check_metadata(name).await,
+            other => bail!("{:?} is not managed by config files", other),
+        };
+    };
+    match change.resource_type {
+        ResourceType::Connection => {
+            let p: ConnectionProfile = serde_json::from_value(after)?;
+            db.save_connection_profile(
+                &p.name,
+                &p.driver,
+                &p.connection_string_template,
+                p.connection_type.as_deref(),
+                p.secret_ref.as_deref(),
+                p.auth.as_ref().map(ToString::to_string).as_deref(),
+            )
+            .await
+        }
+        ResourceType::DataSource => {
+            let d: DataSourceEntry = serde_json::from_value(after)?;
+            db.save_data_source(&d.name, &d.connection_name, &d.secret_key)
+                .await
+        }
+        ResourceType::UnixGroup => {
+            let g: UnixGroupEntry = serde_json::from_value(after)?;
+            db.save_unix_group(&g.group_name, &g.file_path, &g.permissions)
+                .await
+        }
+        ResourceType::Schedule => {
+            let s: CheckSchedule = serde_json::from_value(after)?;
+            db.save_schedule(&(&s).into()).await
+        }
+        ResourceType::Check => db.save_check_metadata(name, &after).await,
+        other => bail!("{:?} is not managed by config files", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::CheckStatus;
+    use crate::runner::testing::{Behavior, ScriptedCheck};
+    use std::sync::Arc;
+
+    const MANIFEST: &str = r#"
+        [[connections]]
+        name = "warehouse"
+        driver = "Postgres"
+        connection_string_template = "host=wh password={{PASSWORD}}"
+
+        [[data_sources]]
+        name = "sales"
+        connection_name = "warehouse"
+        secret_key = "wh_password"
+
+        [[schedules]]
+        name = "morning"
+        cron = "0 30 6 * * *"
+        checks = ["orders"]
+
+        [checks.orders]
+        owner = "data-eng"
+        tags = ["finance"]
+    "#;
+
+    async fn setup() -> (Db, CheckRegistry) {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        db.save_secret("wh_password", "hunter2").await.unwrap();
+        db.save_unix_group("etl", "/data/etl", "rw").await.unwrap();
+        let mut registry = CheckRegistry::new();
+        registry
+            .register(Arc::new(ScriptedCheck::new(
+                "orders",
+                Behavior::Return(CheckStatus::Success),
+            )))
+            .unwrap();
+        (db, registry)
+    }
+
+    fn actor() -> Actor {
+        Actor {
+            username: "config".to_string(),
+            ip: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_and_apply() {
+        let (db, registry) = setup().await;
+        let desired = Manifest::parse(MANIFEST, Format::Toml).unwrap();
+        let current = Manifest::load(&db).await.unwrap();
+
+        // Import keeps the unix group the file does not mention.
+        let merged = current.clone().merge(&desired);
+        merged.validate(&db, &registry).await.unwrap();
+        let changes = plan(&current, &merged);
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|c| format!("{:?} {:?} {}", c.action, c.resource_type, c.name))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                "Create Connection warehouse",
+                "Create DataSource sales",
+                "Create Schedule morning",
+                "Create Check orders",
+            ]
+        );
+        apply(&db, &changes, &actor()).await.unwrap();
+
+        let stored = Manifest::load(&db).await.unwrap();
+        assert!(plan(&stored, &merged).is_empty());
+        assert_eq!(stored.checks["orders"].owner.as_deref(), Some("data-eng"));
+
+        // Apply deletes it, and reports field changes.
+        let mut desired = desired;
+        desired.data_sources[0].secret_key = "missing".to_string();
+        assert!(desired.validate(&db, &registry).await.is_err());
+        desired.data_sources[0].secret_key = "wh_password".to_string();
+        desired.connections[0].driver = "Oracle".to_string();
+        let changes = plan(&stored, &desired);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[0].to_string(),
+            "~ Connection warehouse\n    driver: \"Postgres\" -> \"Oracle\""
+        );
+        assert_eq!(changes[1].to_string(), "- UnixGroup etl");
+        apply(&db, &changes, &actor()).await.unwrap();
+        assert!(db.get_unix_groups().await.unwrap().is_empty());
+
+        let audited = AuditLog::new(db.clone())
+            .query(&Default::default())
+            .await
+            .unwrap();
+        assert_eq!(audited.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_apply_is_all_or_nothing() {
+        let (db, _) = setup().await;
+        let desired = Manifest::parse(MANIFEST, Format::Toml).unwrap();
+        let current = Manifest::load(&db).await.unwrap();
+        let mut changes = plan(&current, &desired);
+        // The unix group is deleted last; make it fail.
+        let last = changes.last_mut().unwrap();
+        assert_eq!(last.to_string(), "- UnixGroup etl");
+        last.resource_type = ResourceType::Secret;
+
+        let error = apply(&db, &changes, &actor()).await.unwrap_err();
+        assert!(error.to_string().contains("Failed to apply"), "{}", error);
+        let stored = Manifest::load(&db).await.unwrap();
+        assert!(plan(&current, &stored).is_empty());
+        assert!(AuditLog::new(db.clone())
+            .query(&Default::default())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_export_round_trip() {
+        let (db, registry) = setup().await;
+        let manifest = Manifest::export(&db, &registry).await.unwrap();
+        assert!(manifest.checks.contains_key("orders"));
+
+        for format in [Format::Toml, Format::Yaml] {
+            let text = manifest.render(format).unwrap();
+            let parsed = Manifest::parse(&text, format).unwrap();
+            assert!(plan(&manifest, &parsed).is_empty(), "{}", text);
+        }
+        assert!(Manifest::parse("[[connection]]\nname = \"x\"", Format::Toml).is_err());
+    }
+}
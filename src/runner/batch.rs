@@ -0,0 +1,343 @@
+//! Running many checks at once.
+//!
+//! A batch is executed as a DAG: a check starts once all of its dependencies
+//! in the batch have finished, and is recorded as `Skipped` if any of them did
+//! not pass. Dependencies outside the batch are judged by their latest
+//! recorded status.
+
+use super::{CheckRunner, RunOptions};
+use crate::checks::{CheckResult, CheckStatus, DataCheck};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchEntry {
+    pub check_id: String,
+    pub result: CheckResult,
+}
+
+/// Aggregate outcome of a batch run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub warnings: usize,
+    pub failures: usize,
+    pub timed_out: usize,
+    pub skipped: usize,
+    pub suppressed: usize,
+    pub results: Vec<BatchEntry>,
+}
+
+impl BatchSummary {
+    fn add(&mut self, entry: BatchEntry) {
+        self.total += 1;
+        match entry.result.status {
+            CheckStatus::Success => self.succeeded += 1,
+            CheckStatus::Warning => self.warnings += 1,
+            CheckStatus::Failure => self.failures += 1,
+            CheckStatus::TimedOut => self.timed_out += 1,
+            CheckStatus::Skipped => self.skipped += 1,
+            CheckStatus::Suppressed => self.suppressed += 1,
+        }
+        self.results.push(entry);
+    }
+}
+
+impl CheckRunner {
+    /// Execute checks concurrently in dependency order, subject to the
+    /// runner's concurrency limits, and summarize the outcomes.
+    ///
+    /// `params` are shared by all checks; parameters a check declares with a
+    /// default are filled in when missing.
+    pub async fn run_batch(
+        self: &Arc<Self>,
+        checks: Vec<Arc<dyn DataCheck>>,
+        params: HashMap<String, Value>,
+        options: RunOptions,
+    ) -> BatchSummary {
+        let checks: HashMap<String, Arc<dyn DataCheck>> = checks
+            .into_iter()
+            .map(|c| (c.id().to_string(), c))
+            .collect();
+        let latest = self
+            .db
+            .get_latest_check_statuses()
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Could not load latest statuses for batch: {}", e);
+                HashMap::new()
+            });
+
+        // Number of unfinished in-batch dependencies per check, and the
+        // reverse edges used to release dependents.
+        let mut pending: HashMap<String, usize> = HashMap::new();
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        for (id, check) in &checks {
+            let deps: HashSet<String> = check
+                .dependencies()
+                .into_iter()
+                .filter(|d| checks.contains_key(d))
+                .collect();
+            pending.insert(id.clone(), deps.len());
+            for dep in deps {
+                dependents.entry(dep).or_default().push(id.clone());
+            }
+        }
+
+        let mut failed_upstream: HashMap<String, String> = HashMap::new();
+        let mut tasks = JoinSet::new();
+        let ready: Vec<String> = pending
+            .iter()
+            .filter(|(_, n)| **n == 0)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in ready {
+            pending.remove(&id);
+            let skip = skip_reason(&checks[&id], &checks, &failed_upstream, &latest);
+            self.spawn_node(&mut tasks, checks[&id].clone(), &params, &options, skip);
+        }
+
+        let mut summary = BatchSummary::default();
+        while let Some(joined) = tasks.join_next().await {
+            let entry = match joined {
+                Ok(entry) => entry,
+                Err(e) => {
+                    tracing::error!("Batch task failed: {}", e);
+                    continue;
+                }
+            };
+
+            let passed = entry.result.status.is_passing();
+            for dependent in dependents.get(&entry.check_id).into_iter().flatten() {
+                if !passed {
+                    failed_upstream
+                        .entry(dependent.clone())
+                        .or_insert_with(|| entry.check_id.clone());
+                }
+                let Some(remaining) = pending.get_mut(dependent) else {
+                    continue;
+                };
+                *remaining -= 1;
+                if *remaining == 0 {
+                    pending.remove(dependent);
+                    let check = &checks[dependent];
+                    let skip = skip_reason(check, &checks, &failed_upstream, &latest);
+                    self.spawn_node(&mut tasks, check.clone(), &params, &options, skip);
+                }
+            }
+            summary.add(entry);
+        }
+
+        // Checks still pending were waiting on a task that never reported
+        // back (or on a cycle, if the batch bypassed the registry).
+        for id in pending.into_keys() {
+            let check = &checks[&id];
+            let reason = "Skipped: upstream checks did not complete".to_string();
+            summary.add(BatchEntry {
+                check_id: id,
+                result: self.skip(check.as_ref(), reason, &options).await,
+            });
+        }
+
+        summary.results.sort_by(|a, b| a.check_id.cmp(&b.check_id));
+        summary
+    }
+
+    fn spawn_node(
+        self: &Arc<Self>,
+        tasks: &mut JoinSet<BatchEntry>,
+        check: Arc<dyn DataCheck>,
+        params: &HashMap<String, Value>,
+        options: &RunOptions,
+        skip: Option<String>,
+    ) {
+        let runner = self.clone();
+        let params = with_defaults(check.as_ref(), params);
+        let options = options.clone();
+        tasks.spawn(async move {
+            let result = match skip {
+                Some(reason) => runner.skip(check.as_ref(), reason, &options).await,
+                None => {
+                    let outcome = runner.run(check.as_ref(), &params, &options).await;
+                    super::recorded_result(&outcome)
+                }
+            };
+            BatchEntry {
+                check_id: check.id().to_string(),
+                result,
+            }
+        });
+    }
+}
+
+/// Why a check whose in-batch dependencies have finished should not run.
+fn skip_reason(
+    check: &Arc<dyn DataCheck>,
+    batch: &HashMap<String, Arc<dyn DataCheck>>,
+    failed_upstream: &HashMap<String, String>,
+    latest: &HashMap<String, (String, chrono::DateTime<chrono::Utc>)>,
+) -> Option<String> {
+    if let Some(upstream) = failed_upstream.get(check.id()) {
+        return Some(format!("Skipped: upstream check {} did not pass", upstream));
+    }
+    check
+        .dependencies()
+        .into_iter()
+        .filter(|dep| !batch.contains_key(dep))
+        .find_map(|dep| match latest.get(&dep) {
+            Some((status, _)) if status != "Success" && status != "Warning" => Some(format!(
+                "Skipped: upstream check {} last finished with {}",
+                dep, status
+            )),
+            _ => None,
+        })
+}
+
+fn with_defaults(check: &dyn DataCheck, params: &HashMap<String, Value>) -> HashMap<String, Value> {
+    let mut params = params.clone();
+    for def in check.parameters() {
+        if let Some(default) = def.default {
+            params
+                .entry(def.name)
+                .or_insert_with(|| Value::String(default));
+        }
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Db;
+    use crate::runner::limits::ConcurrencyLimits;
+    use crate::runner::testing::{Behavior, MockContext, ScriptedCheck};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_batch_summary() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let runner = Arc::new(CheckRunner::new(db, Arc::new(MockContext)));
+        let checks: Vec<Arc<dyn DataCheck>> = vec![
+            Arc::new(ScriptedCheck::new(
+                "a",
+                Behavior::Return(CheckStatus::Success),
+            )),
+            Arc::new(ScriptedCheck::new(
+                "b",
+                Behavior::Return(CheckStatus::Warning),
+            )),
+            Arc::new(ScriptedCheck::new(
+                "c",
+                Behavior::Return(CheckStatus::Failure),
+            )),
+            Arc::new(ScriptedCheck::new("d", Behavior::Panic)),
+        ];
+
+        let summary = runner
+            .run_batch(checks, HashMap::new(), RunOptions::default())
+            .await;
+        assert_eq!(summary.total, 4);
+        assert_eq!(summary.succeeded, 1);
+        assert_eq!(summary.warnings, 1);
+        assert_eq!(summary.failures, 2);
+    }
+
+    #[tokio::test]
+    async fn test_per_connection_limit_serializes_checks() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let limits = ConcurrencyLimits {
+            global: 8,
+            per_connection: 1,
+            ..Default::default()
+        };
+        let runner = Arc::new(CheckRunner::new(db, Arc::new(MockContext)).with_limits(limits));
+        let checks: Vec<Arc<dyn DataCheck>> = (0..3)
+            .map(|i| {
+                Arc::new(
+                    ScriptedCheck::new(
+                        &format!("oracle_{}", i),
+                        Behavior::Sleep(Duration::from_millis(50)),
+                    )
+                    .with_connections(&["oracle"]),
+                ) as Arc<dyn DataCheck>
+            })
+            .collect();
+
+        let start = Instant::now();
+        let summary = runner
+            .run_batch(checks, HashMap::new(), RunOptions::default())
+            .await;
+        assert_eq!(summary.succeeded, 3);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn test_failed_upstream_skips_downstream() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let runner = Arc::new(CheckRunner::new(db.clone(), Arc::new(MockContext)));
+        let checks: Vec<Arc<dyn DataCheck>> = vec![
+            Arc::new(ScriptedCheck::new(
+                "raw_load",
+                Behavior::Return(CheckStatus::Failure),
+            )),
+            Arc::new(
+                ScriptedCheck::new("aggregates", Behavior::Return(CheckStatus::Success))
+                    .with_dependencies(&["raw_load"]),
+            ),
+            Arc::new(
+                ScriptedCheck::new("report", Behavior::Return(CheckStatus::Success))
+                    .with_dependencies(&["aggregates"]),
+            ),
+            Arc::new(ScriptedCheck::new(
+                "independent",
+                Behavior::Return(CheckStatus::Success),
+            )),
+        ];
+
+        let summary = runner
+            .run_batch(checks, HashMap::new(), RunOptions::default())
+            .await;
+        assert_eq!(summary.failures, 1);
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(summary.succeeded, 1);
+
+        let report = summary
+            .results
+            .iter()
+            .find(|e| e.check_id == "report")
+            .unwrap();
+        assert!(report.result.message.contains("aggregates"));
+
+        let statuses = db.get_latest_check_statuses().await.unwrap();
+        assert_eq!(statuses["report"].0, "Skipped");
+    }
+
+    #[tokio::test]
+    async fn test_dependencies_run_before_dependents() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let runner = Arc::new(CheckRunner::new(db.clone(), Arc::new(MockContext)));
+        let checks: Vec<Arc<dyn DataCheck>> = vec![
+            Arc::new(
+                ScriptedCheck::new("downstream", Behavior::Return(CheckStatus::Success))
+                    .with_dependencies(&["upstream"]),
+            ),
+            Arc::new(ScriptedCheck::new(
+                "upstream",
+                Behavior::Sleep(Duration::from_millis(30)),
+            )),
+        ];
+
+        let summary = runner
+            .run_batch(checks, HashMap::new(), RunOptions::default())
+            .await;
+        assert_eq!(summary.succeeded, 2);
+
+        let history = db.get_recent_results(10).await.unwrap();
+        let order: Vec<_> = history.iter().rev().map(|r| r.1.as_str()).collect();
+        assert_eq!(order, vec!["upstream", "downstream"]);
+    }
+}
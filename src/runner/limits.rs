@@ -0,0 +1,91 @@
+//! Concurrency limits shared by every execution path.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimits {
+    /// Maximum number of checks executing at once.
+    pub global: usize,
+    /// Maximum number of checks executing at once against one connection.
+    pub per_connection: usize,
+    /// Per-connection limits that replace `per_connection` for the named profiles.
+    pub connection_overrides: HashMap<String, usize>,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            global: 8,
+            per_connection: 2,
+            connection_overrides: HashMap::new(),
+        }
+    }
+}
+
+/// Permits held for the duration of one execution.
+pub struct Permits {
+    _connections: Vec<OwnedSemaphorePermit>,
+    _global: OwnedSemaphorePermit,
+}
+
+pub struct Limiter {
+    limits: ConcurrencyLimits,
+    global: Arc<Semaphore>,
+    connections: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl Limiter {
+    pub fn new(limits: ConcurrencyLimits) -> Self {
+        Self {
+            global: Arc::new(Semaphore::new(limits.global.max(1))),
+            limits,
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait until a check using `connections` may run.
+    ///
+    /// Connection permits are taken in sorted order before the global one, so
+    /// two executions can never wait on each other, and a check blocked on a
+    /// busy connection does not occupy a global slot.
+    pub async fn acquire(&self, connections: &[String]) -> Permits {
+        let mut names: Vec<&String> = connections.iter().collect();
+        names.sort();
+        names.dedup();
+
+        let mut held = Vec::with_capacity(names.len());
+        for name in names {
+            let semaphore = self.connection_semaphore(name);
+            held.push(semaphore.acquire_owned().await.expect("semaphore closed"));
+        }
+        let global = self
+            .global
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore closed");
+
+        Permits {
+            _connections: held,
+            _global: global,
+        }
+    }
+
+    fn connection_semaphore(&self, name: &str) -> Arc<Semaphore> {
+        let mut connections = self.connections.lock().unwrap();
+        connections
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let limit = self
+                    .limits
+                    .connection_overrides
+                    .get(name)
+                    .copied()
+                    .unwrap_or(self.limits.per_connection);
+                Arc::new(Semaphore::new(limit.max(1)))
+            })
+            .clone()
+    }
+}
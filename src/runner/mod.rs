@@ -0,0 +1,463 @@
+//! Check execution.
+//!
+//! `CheckRunner` is the single place where checks are executed and their
+//! results recorded, so that HTTP requests and background runs behave the same.
+
+pub mod batch;
+pub mod limits;
+pub mod runs;
+#[cfg(test)]
+pub(crate) mod testing;
+
+use crate::alerting::AlertManager;
+use crate::checks::{CheckContext, CheckError, CheckResult, CheckStatus, DataCheck};
+use crate::connections::DataSourceBindings;
+use crate::db::{AttemptRecord, Db};
+use crate::maintenance::Maintenance;
+use chrono::Utc;
+use futures::FutureExt;
+use limits::{ConcurrencyLimits, Limiter};
+use serde_json::Value;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Once};
+use std::time::Duration;
+
+/// Per-run execution options.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Overrides `DataCheck::timeout` for this run.
+    pub timeout: Option<Duration>,
+    /// Environment to resolve connections in, instead of the context's.
+    pub environment: Option<String>,
+}
+
+pub struct CheckRunner {
+    db: Db,
+    check_context: Arc<dyn CheckContext>,
+    limiter: Limiter,
+    alerts: Option<Arc<AlertManager>>,
+    maintenance: Maintenance,
+}
+
+impl CheckRunner {
+    pub fn new(db: Db, check_context: Arc<dyn CheckContext>) -> Self {
+        Self {
+            maintenance: Maintenance::new(db.clone()),
+            db,
+            check_context,
+            limiter: Limiter::new(ConcurrencyLimits::default()),
+            alerts: None,
+        }
+    }
+
+    /// Evaluate alert rules for every result this runner records.
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: ConcurrencyLimits) -> Self {
+        self.limiter = Limiter::new(limits);
+        self
+    }
+
+    /// Execute a check and store its result in the history.
+    ///
+    /// Failed attempts are retried according to the check's `RetryPolicy`;
+    /// every attempt is logged, but only the final one is stored as the
+    /// result. Errors are recorded too, so a check that times out or panics
+    /// shows up in the history rather than disappearing. A result produced
+    /// during a maintenance window covering the check is recorded and
+    /// returned as `Suppressed`. A run in an unknown environment fails
+    /// without being recorded.
+    pub async fn run(
+        &self,
+        check: &dyn DataCheck,
+        params: &HashMap<String, Value>,
+        options: &RunOptions,
+    ) -> Result<CheckResult, CheckError> {
+        let context = self.context_for(options).await?;
+        let timeout = options.timeout.unwrap_or_else(|| check.timeout());
+        let policy = check.retry_policy();
+        let connections = match DataSourceBindings::load(&self.db).await {
+            Ok(bindings) => bindings.connections_of(check),
+            Err(e) => {
+                tracing::error!("Failed to load data sources for {}: {}", check.id(), e);
+                check.connections()
+            }
+        };
+        let mut attempts = Vec::new();
+
+        let mut outcome = loop {
+            let attempt = attempts.len() as u32 + 1;
+            let permits = self.limiter.acquire(&connections).await;
+            let started_at = Utc::now();
+            let outcome = execute_guarded(check, context.as_ref(), params, timeout).await;
+            drop(permits);
+
+            let recorded = recorded_result(&outcome);
+            attempts.push(AttemptRecord {
+                attempt: attempt as i64,
+                status: format!("{:?}", recorded.status),
+                message: recorded.message,
+                started_at,
+                finished_at: Utc::now(),
+            });
+
+            match &outcome {
+                Err(e) if policy.should_retry(attempt, e) => {
+                    let delay = policy.backoff(attempt);
+                    tracing::warn!(
+                        "Check {} attempt {} failed ({}), retrying in {:?}",
+                        check.id(),
+                        attempt,
+                        e,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                _ => break outcome,
+            }
+        };
+
+        match self.maintenance.active_window(check, Utc::now()).await {
+            Ok(Some(window)) => outcome = Ok(window.suppress(&recorded_result(&outcome))),
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to load maintenance windows: {}", e),
+        }
+
+        let recorded = recorded_result(&outcome);
+        let result_id = self
+            .record(check.id(), &recorded, context.environment())
+            .await;
+        if let Some(result_id) = result_id {
+            if let Err(e) = self.db.save_attempts(result_id, &attempts).await {
+                tracing::error!("Failed to save attempts for {}: {}", check.id(), e);
+            }
+        }
+        if let Some(alerts) = &self.alerts {
+            if let Err(e) = alerts.process(check, &recorded, result_id).await {
+                tracing::error!("Failed to process alerts for {}: {}", check.id(), e);
+            }
+        }
+
+        outcome
+    }
+
+    /// Record a check as skipped without executing it.
+    pub async fn skip(
+        &self,
+        check: &dyn DataCheck,
+        reason: String,
+        options: &RunOptions,
+    ) -> CheckResult {
+        let result = CheckResult {
+            status: CheckStatus::Skipped,
+            message: reason,
+            details: None,
+        };
+        let environment = options
+            .environment
+            .as_deref()
+            .or_else(|| self.check_context.environment());
+        self.record(check.id(), &result, environment).await;
+        result
+    }
+
+    /// The context a run executes in: the runner's own, or one switched to
+    /// the environment requested for the run.
+    async fn context_for(&self, options: &RunOptions) -> Result<Arc<dyn CheckContext>, CheckError> {
+        let Some(environment) = &options.environment else {
+            return Ok(self.check_context.clone());
+        };
+        match self.db.environment_exists(environment).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err(CheckError::ConfigError(format!(
+                    "Unknown environment: {}",
+                    environment
+                )))
+            }
+            Err(e) => return Err(CheckError::ExecutionError(e.to_string())),
+        }
+        self.check_context
+            .for_environment(environment)
+            .ok_or_else(|| {
+                CheckError::ConfigError("The check context does not support environments".into())
+            })
+    }
+
+    /// Store a result. A successful result also clears the check's
+    /// acknowledgement, since the incident it covered is over.
+    async fn record(
+        &self,
+        check_id: &str,
+        result: &CheckResult,
+        environment: Option<&str>,
+    ) -> Option<i64> {
+        if matches!(result.status, CheckStatus::Success) {
+            if let Err(e) = self.db.clear_acknowledgement(check_id).await {
+                tracing::error!("Failed to clear acknowledgement of {}: {}", check_id, e);
+            }
+        }
+
+        let status_str = format!("{:?}", result.status);
+        match self
+            .db
+            .save_result(
+                check_id,
+                &status_str,
+                &result.message,
+                result.details.as_ref(),
+                environment,
+            )
+            .await
+        {
+            Ok(id) => Some(id),
+            Err(e) => {
+                tracing::error!("Failed to save result for {}: {}", check_id, e);
+                None
+            }
+        }
+    }
+}
+
+fn recorded_result(outcome: &Result<CheckResult, CheckError>) -> CheckResult {
+    match outcome {
+        Ok(result) => result.clone(),
+        Err(e) => CheckResult::from_error(e),
+    }
+}
+
+/// Execute a check with a timeout, converting panics into `CheckError::Panicked`.
+///
+/// The timeout only takes effect at an `.await` point: a check that blocks
+/// the thread cannot be interrupted.
+pub async fn execute_guarded(
+    check: &dyn DataCheck,
+    ctx: &dyn CheckContext,
+    params: &HashMap<String, Value>,
+    timeout: Duration,
+) -> Result<CheckResult, CheckError> {
+    install_panic_hook();
+
+    let execution = AssertUnwindSafe(check.execute(ctx, params)).catch_unwind();
+    match tokio::time::timeout(timeout, execution).await {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(payload)) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic payload".to_string());
+            let backtrace = LAST_BACKTRACE
+                .with(|b| b.borrow_mut().take())
+                .unwrap_or_default();
+            tracing::error!("Check {} panicked: {}", check.id(), message);
+            Err(CheckError::Panicked { message, backtrace })
+        }
+        Err(_) => Err(CheckError::TimedOut(timeout)),
+    }
+}
+
+thread_local! {
+    /// Backtrace of the most recent panic on this thread. `catch_unwind` runs
+    /// on the panicking thread, so it can pick this up right after unwinding.
+    static LAST_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let backtrace = Backtrace::force_capture().to_string();
+            LAST_BACKTRACE.with(|b| *b.borrow_mut() = Some(backtrace));
+            previous(info);
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{Behavior, MockContext, ScriptedCheck};
+    use super::*;
+    use crate::checks::{RetryPolicy, StandardCheckContext};
+    use crate::connections::ConnectionManager;
+    use crate::secrets::DbSecretStore;
+
+    async fn runner() -> (Db, CheckRunner) {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let runner = CheckRunner::new(db.clone(), Arc::new(MockContext));
+        (db, runner)
+    }
+
+    #[tokio::test]
+    async fn test_timeout_is_recorded() {
+        let (db, runner) = runner().await;
+        let check = ScriptedCheck::new("slow", Behavior::Sleep(Duration::from_secs(60)));
+        let options = RunOptions {
+            timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+
+        let outcome = runner.run(&check, &HashMap::new(), &options).await;
+        assert!(matches!(outcome, Err(CheckError::TimedOut(_))));
+
+        let history = db.get_recent_results(1).await.unwrap();
+        assert_eq!(history[0].2, "TimedOut");
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let (db, runner) = runner().await;
+        let check = ScriptedCheck::new(
+            "flaky",
+            Behavior::FailTimes(2, || CheckError::ExecutionError("connection reset".into())),
+        )
+        .with_retry_policy(RetryPolicy::transient(3, Duration::ZERO));
+
+        let outcome = runner
+            .run(&check, &HashMap::new(), &RunOptions::default())
+            .await;
+        assert!(outcome.is_ok());
+
+        let history = db.get_recent_results(10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].2, "Success");
+        let attempts = db.get_attempts(history[0].0).await.unwrap();
+        let statuses: Vec<_> = attempts.iter().map(|a| a.status.as_str()).collect();
+        assert_eq!(statuses, vec!["Failure", "Failure", "Success"]);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error_fails_immediately() {
+        let (db, runner) = runner().await;
+        let check = ScriptedCheck::new(
+            "misconfigured",
+            Behavior::FailTimes(1, || CheckError::ConfigError("missing table".into())),
+        )
+        .with_retry_policy(RetryPolicy::transient(3, Duration::ZERO));
+
+        let outcome = runner
+            .run(&check, &HashMap::new(), &RunOptions::default())
+            .await;
+        assert!(matches!(outcome, Err(CheckError::ConfigError(_))));
+
+        let history = db.get_recent_results(10).await.unwrap();
+        assert_eq!(db.get_attempts(history[0].0).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_panic_is_caught() {
+        let (_db, runner) = runner().await;
+        let check = ScriptedCheck::new("boom", Behavior::Panic);
+
+        let outcome = runner
+            .run(&check, &HashMap::new(), &RunOptions::default())
+            .await;
+        match outcome {
+            Err(CheckError::Panicked { message, .. }) => {
+                assert_eq!(message, "scripted panic in boom")
+            }
+            other => panic!("expected panic error, got {:?}", other),
+        }
+
+        // The runner is still usable afterwards.
+        let check = ScriptedCheck::new("ok", Behavior::Return(CheckStatus::Success));
+        assert!(runner
+            .run(&check, &HashMap::new(), &RunOptions::default())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_environment_is_recorded() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        db.save_environment("staging", None).await.unwrap();
+        let connection_manager = Arc::new(ConnectionManager::new(
+            db.clone(),
+            Arc::new(DbSecretStore::new(db.clone())),
+        ));
+        let runner = CheckRunner::new(
+            db.clone(),
+            Arc::new(StandardCheckContext { connection_manager }),
+        );
+        let check = ScriptedCheck::new("ok", Behavior::Return(CheckStatus::Success));
+        let in_env = |environment: &str| RunOptions {
+            environment: Some(environment.to_string()),
+            ..Default::default()
+        };
+
+        runner
+            .run(&check, &HashMap::new(), &in_env("staging"))
+            .await
+            .unwrap();
+        runner
+            .run(&check, &HashMap::new(), &RunOptions::default())
+            .await
+            .unwrap();
+        let outcome = runner.run(&check, &HashMap::new(), &in_env("prod")).await;
+        assert!(matches!(outcome, Err(CheckError::ConfigError(_))));
+
+        let history = db.get_recent_results(10).await.unwrap();
+        let environments: Vec<_> = history.iter().map(|r| r.4.as_deref()).collect();
+        assert_eq!(environments, vec![None, Some("staging")]);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_window_suppresses_result() {
+        let (db, runner) = runner().await;
+        let now = Utc::now();
+        let schedule = serde_json::json!({
+            "type": "once",
+            "starts_at": now - chrono::Duration::hours(1),
+            "ends_at": now + chrono::Duration::hours(1),
+        });
+        let scope = serde_json::json!({ "connections": ["warehouse"] });
+        db.create_maintenance_window("upgrade", None, &schedule, &scope)
+            .await
+            .unwrap();
+
+        let covered = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Failure))
+            .with_connections(&["warehouse"]);
+        let result = runner
+            .run(&covered, &HashMap::new(), &RunOptions::default())
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Suppressed));
+        assert_eq!(result.details.unwrap()["original_status"], "Failure");
+
+        let other = ScriptedCheck::new("other", Behavior::Return(CheckStatus::Failure));
+        let result = runner
+            .run(&other, &HashMap::new(), &RunOptions::default())
+            .await
+            .unwrap();
+        assert!(matches!(result.status, CheckStatus::Failure));
+    }
+
+    #[tokio::test]
+    async fn test_success_clears_acknowledgement() {
+        let (db, runner) = runner().await;
+        db.acknowledge_check("orders", "alice", Some("INC-123"))
+            .await
+            .unwrap();
+
+        let failing = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Failure));
+        runner
+            .run(&failing, &HashMap::new(), &RunOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(db.get_acknowledgements().await.unwrap().len(), 1);
+
+        let passing = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Success));
+        runner
+            .run(&passing, &HashMap::new(), &RunOptions::default())
+            .await
+            .unwrap();
+        assert!(db.get_acknowledgements().await.unwrap().is_empty());
+    }
+}
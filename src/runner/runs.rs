@@ -0,0 +1,241 @@
+//! Background runs.
+//!
+//! A run is a single check execution detached from the HTTP request that
+//! started it. Its state is persisted in the `runs` table so clients can poll
+//! for the outcome, and the in-flight task can be aborted to cancel it.
+
+use super::{CheckRunner, RunOptions};
+use crate::checks::{CheckResult, DataCheck};
+use crate::db::{Db, RunRecord};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::task::AbortHandle;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RunStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl RunStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            RunStatus::Succeeded | RunStatus::Failed | RunStatus::Cancelled
+        )
+    }
+}
+
+impl FromStr for RunStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Queued" => Ok(RunStatus::Queued),
+            "Running" => Ok(RunStatus::Running),
+            "Succeeded" => Ok(RunStatus::Succeeded),
+            "Failed" => Ok(RunStatus::Failed),
+            "Cancelled" => Ok(RunStatus::Cancelled),
+            other => Err(format!("Unknown run status: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Run {
+    pub id: String,
+    pub check_id: String,
+    pub status: RunStatus,
+    pub params: Option<Value>,
+    pub result: Option<CheckResult>,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+impl TryFrom<RunRecord> for Run {
+    type Error = String;
+
+    fn try_from(r: RunRecord) -> Result<Self, Self::Error> {
+        Ok(Run {
+            id: r.id,
+            check_id: r.check_id,
+            status: r.status.parse()?,
+            params: r.params,
+            result: r.result.and_then(|v| serde_json::from_value(v).ok()),
+            error: r.error,
+            created_at: r.created_at.to_rfc3339(),
+            started_at: r.started_at.map(|t| t.to_rfc3339()),
+            finished_at: r.finished_at.map(|t| t.to_rfc3339()),
+        })
+    }
+}
+
+pub struct RunManager {
+    db: Db,
+    runner: Arc<CheckRunner>,
+    in_flight: Arc<Mutex<HashMap<String, AbortHandle>>>,
+}
+
+impl RunManager {
+    pub fn new(db: Db, runner: Arc<CheckRunner>) -> Self {
+        Self {
+            db,
+            runner,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Queue a check for execution and return the id of the new run.
+    pub async fn submit(
+        &self,
+        check: Arc<dyn DataCheck>,
+        params: HashMap<String, Value>,
+        options: RunOptions,
+    ) -> anyhow::Result<String> {
+        let run_id = Uuid::new_v4().to_string();
+        let params_json = serde_json::to_value(&params)?;
+        self.db
+            .create_run(&run_id, check.id(), &params_json)
+            .await?;
+
+        let db = self.db.clone();
+        let runner = self.runner.clone();
+        let in_flight = self.in_flight.clone();
+        let id = run_id.clone();
+
+        // Hold the lock while spawning so the task cannot deregister itself
+        // before its handle has been stored.
+        let mut handles = self.in_flight.lock().unwrap();
+        let task = tokio::spawn(async move {
+            if let Err(e) = db.mark_run_started(&id).await {
+                tracing::error!("Failed to mark run {} as started: {}", id, e);
+            }
+
+            let outcome = runner.run(check.as_ref(), &params, &options).await;
+            let finished = match outcome {
+                Ok(result) => {
+                    let result = serde_json::to_value(&result).ok();
+                    db.finish_run(
+                        &id,
+                        &format!("{:?}", RunStatus::Succeeded),
+                        result.as_ref(),
+                        None,
+                    )
+                    .await
+                }
+                Err(e) => {
+                    db.finish_run(
+                        &id,
+                        &format!("{:?}", RunStatus::Failed),
+                        None,
+                        Some(&e.to_string()),
+                    )
+                    .await
+                }
+            };
+            if let Err(e) = finished {
+                tracing::error!("Failed to record outcome of run {}: {}", id, e);
+            }
+
+            in_flight.lock().unwrap().remove(&id);
+        });
+        handles.insert(run_id.clone(), task.abort_handle());
+
+        Ok(run_id)
+    }
+
+    pub async fn get(&self, id: &str) -> anyhow::Result<Option<Run>> {
+        match self.db.get_run(id).await? {
+            Some(record) => Ok(Some(Run::try_from(record).map_err(anyhow::Error::msg)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Cancel a queued or running run. Returns `false` if the run has
+    /// already finished.
+    pub async fn cancel(&self, id: &str) -> anyhow::Result<bool> {
+        let handle = self.in_flight.lock().unwrap().remove(id);
+        if let Some(handle) = handle {
+            handle.abort();
+        }
+
+        self.db
+            .finish_run(id, &format!("{:?}", RunStatus::Cancelled), None, None)
+            .await
+    }
+
+    /// Mark runs orphaned by a previous process as failed.
+    pub async fn recover(&self) -> anyhow::Result<()> {
+        let count = self.db.fail_interrupted_runs().await?;
+        if count > 0 {
+            tracing::warn!("Marked {} interrupted runs as failed", count);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::CheckStatus;
+    use crate::runner::testing::{Behavior, MockContext, ScriptedCheck};
+    use std::time::Duration;
+
+    async fn manager() -> RunManager {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        let runner = Arc::new(CheckRunner::new(db.clone(), Arc::new(MockContext)));
+        RunManager::new(db, runner)
+    }
+
+    async fn wait_for_terminal(manager: &RunManager, id: &str) -> Run {
+        for _ in 0..100 {
+            let run = manager.get(id).await.unwrap().unwrap();
+            if run.status.is_terminal() {
+                return run;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("run {} did not finish", id);
+    }
+
+    #[tokio::test]
+    async fn test_submitted_run_succeeds() {
+        let manager = manager().await;
+        let check = ScriptedCheck::new("quick", Behavior::Return(CheckStatus::Success));
+        let id = manager
+            .submit(Arc::new(check), HashMap::new(), RunOptions::default())
+            .await
+            .unwrap();
+
+        let run = wait_for_terminal(&manager, &id).await;
+        assert_eq!(run.status, RunStatus::Succeeded);
+        assert!(matches!(
+            run.result.map(|r| r.status),
+            Some(CheckStatus::Success)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_run() {
+        let manager = manager().await;
+        let check = ScriptedCheck::new("slow", Behavior::Sleep(Duration::from_secs(60)));
+        let id = manager
+            .submit(Arc::new(check), HashMap::new(), RunOptions::default())
+            .await
+            .unwrap();
+
+        assert!(manager.cancel(&id).await.unwrap());
+        let run = manager.get(&id).await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Cancelled);
+        assert!(!manager.cancel(&id).await.unwrap());
+    }
+}
@@ -0,0 +1,154 @@
+//! Test doubles shared by the runner and check tests.
+
+use crate::checks::{
+    CheckContext, CheckError, CheckMetadata, CheckResult, CheckStatus, DataCheck,
+    ParameterDefinition, RetryPolicy,
+};
+use crate::connections::ConnectionError;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+pub struct MockContext;
+
+#[async_trait]
+impl CheckContext for MockContext {
+    async fn get_connection_string(&self, name: &str) -> Result<String, ConnectionError> {
+        Err(ConnectionError::ProfileNotFound(name.to_string()))
+    }
+}
+
+/// A context resolving every connection to the same path, for the file
+/// checks.
+pub struct FileContext(pub String);
+
+#[async_trait]
+impl CheckContext for FileContext {
+    async fn get_connection_string(&self, _name: &str) -> Result<String, ConnectionError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// What a `ScriptedCheck` does when executed.
+pub enum Behavior {
+    Return(CheckStatus),
+    Sleep(Duration),
+    Panic,
+    /// Fail with the error built by the function for the first N
+    /// executions, then succeed.
+    FailTimes(u32, fn() -> CheckError),
+}
+
+pub struct ScriptedCheck {
+    pub id: String,
+    pub behavior: Behavior,
+    pub retry_policy: RetryPolicy,
+    pub connections: Vec<String>,
+    pub data_sources: Vec<String>,
+    pub dependencies: Vec<String>,
+    pub tags: Vec<String>,
+    executions: AtomicU32,
+}
+
+impl ScriptedCheck {
+    pub fn new(id: &str, behavior: Behavior) -> Self {
+        Self {
+            id: id.to_string(),
+            behavior,
+            retry_policy: RetryPolicy::none(),
+            connections: vec![],
+            data_sources: vec![],
+            dependencies: vec![],
+            tags: vec![],
+            executions: AtomicU32::new(0),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    pub fn with_connections(mut self, connections: &[&str]) -> Self {
+        self.connections = connections.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn with_data_sources(mut self, data_sources: &[&str]) -> Self {
+        self.data_sources = data_sources.iter().map(|d| d.to_string()).collect();
+        self
+    }
+
+    pub fn with_dependencies(mut self, dependencies: &[&str]) -> Self {
+        self.dependencies = dependencies.iter().map(|d| d.to_string()).collect();
+        self
+    }
+
+    pub fn with_tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|t| t.to_string()).collect();
+        self
+    }
+}
+
+#[async_trait]
+impl DataCheck for ScriptedCheck {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn description(&self) -> &str {
+        "Scripted test check"
+    }
+
+    fn parameters(&self) -> Vec<ParameterDefinition> {
+        vec![]
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy.clone()
+    }
+
+    fn connections(&self) -> Vec<String> {
+        self.connections.clone()
+    }
+
+    fn data_sources(&self) -> Vec<String> {
+        self.data_sources.clone()
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.dependencies.clone()
+    }
+
+    fn metadata(&self) -> CheckMetadata {
+        CheckMetadata {
+            tags: self.tags.clone(),
+            ..Default::default()
+        }
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &dyn CheckContext,
+        _params: &HashMap<String, Value>,
+    ) -> Result<CheckResult, CheckError> {
+        let execution = self.executions.fetch_add(1, Ordering::SeqCst) + 1;
+        let status = match &self.behavior {
+            Behavior::Return(status) => status.clone(),
+            Behavior::Sleep(duration) => {
+                tokio::time::sleep(*duration).await;
+                CheckStatus::Success
+            }
+            Behavior::Panic => panic!("scripted panic in {}", self.id),
+            Behavior::FailTimes(times, err) if execution <= *times => return Err(err()),
+            Behavior::FailTimes(..) => CheckStatus::Success,
+        };
+        Ok(CheckResult {
+            status,
+            message: "done".to_string(),
+            details: None,
+        })
+    }
+}
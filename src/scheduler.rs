@@ -0,0 +1,235 @@
+//! Running checks on cron schedules.
+//!
+//! A schedule selects checks by id and/or group and runs them as a batch at
+//! every occurrence of its cron expression (with seconds, UTC). Schedules
+//! are read from the database on every tick, so changes apply without a
+//! restart.
+
+use crate::checks::DataCheck;
+use crate::db::{Db, ScheduleRecord};
+use crate::runner::{CheckRunner, RunOptions};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the scheduler looks for due schedules.
+pub const DEFAULT_TICK: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckSchedule {
+    pub name: String,
+    /// Cron expression with seconds, e.g. `0 30 6 * * *` for 06:30 UTC.
+    pub cron: String,
+    /// Ids of the checks to run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<String>,
+    /// Run every check in this group as well.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Environment to run in instead of the server's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl CheckSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        Schedule::from_str(&self.cron).map_err(|e| format!("Invalid cron expression: {}", e))?;
+        if self.checks.is_empty() && self.group.is_none() {
+            return Err("A schedule needs checks or a group to run".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn selects(&self, check: &dyn DataCheck) -> bool {
+        self.checks.iter().any(|id| id == check.id())
+            || matches!(&self.group, Some(group) if check.metadata().groups.contains(group))
+    }
+
+    /// Whether an occurrence falls in `(after, until]`.
+    pub fn is_due(&self, after: DateTime<Utc>, until: DateTime<Utc>) -> bool {
+        let Ok(schedule) = Schedule::from_str(&self.cron) else {
+            return false;
+        };
+        self.enabled && schedule.after(&after).next().is_some_and(|at| at <= until)
+    }
+}
+
+impl From<ScheduleRecord> for CheckSchedule {
+    fn from(r: ScheduleRecord) -> Self {
+        Self {
+            name: r.name,
+            cron: r.cron,
+            checks: r.checks,
+            group: r.check_group,
+            environment: r.environment,
+            enabled: r.enabled,
+        }
+    }
+}
+
+impl From<&CheckSchedule> for ScheduleRecord {
+    fn from(s: &CheckSchedule) -> Self {
+        Self {
+            name: s.name.clone(),
+            cron: s.cron.clone(),
+            checks: s.checks.clone(),
+            check_group: s.group.clone(),
+            environment: s.environment.clone(),
+            enabled: s.enabled,
+        }
+    }
+}
+
+pub struct Scheduler {
+    db: Db,
+    runner: Arc<CheckRunner>,
+    checks: Vec<Arc<dyn DataCheck>>,
+    tick: Duration,
+}
+
+impl Scheduler {
+    pub fn new(db: Db, runner: Arc<CheckRunner>, checks: Vec<Arc<dyn DataCheck>>) -> Self {
+        Self {
+            db,
+            runner,
+            checks,
+            tick: DEFAULT_TICK,
+        }
+    }
+
+    pub fn with_tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    /// Runs due schedules until the task is dropped. Occurrences missed
+    /// while the server was down are not caught up.
+    pub async fn run(self) {
+        let mut last = Utc::now();
+        loop {
+            tokio::time::sleep(self.tick).await;
+            let now = Utc::now();
+            if let Err(e) = self.run_due(last, now).await {
+                tracing::error!("Failed to run scheduled checks: {}", e);
+            }
+            last = now;
+        }
+    }
+
+    /// Starts a batch for every schedule due in `(after, until]` and returns
+    /// their names. Batches run in the background.
+    pub async fn run_due(
+        &self,
+        after: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut started = Vec::new();
+        for schedule in self.db.get_schedules().await? {
+            let schedule = CheckSchedule::from(schedule);
+            if !schedule.is_due(after, until) {
+                continue;
+            }
+            let checks: Vec<_> = self
+                .checks
+                .iter()
+                .filter(|c| schedule.selects(c.as_ref()))
+                .cloned()
+                .collect();
+            if checks.is_empty() {
+                tracing::warn!("Schedule {} matches no registered checks", schedule.name);
+                continue;
+            }
+            tracing::info!(
+                "Schedule {} is running {} checks",
+                schedule.name,
+                checks.len()
+            );
+            let runner = self.runner.clone();
+            let options = RunOptions {
+                environment: schedule.environment.clone(),
+                ..Default::default()
+            };
+            tokio::spawn(async move {
+                runner.run_batch(checks, HashMap::new(), options).await;
+            });
+            started.push(schedule.name);
+        }
+        Ok(started)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::CheckStatus;
+    use crate::runner::testing::{Behavior, MockContext, ScriptedCheck};
+    use chrono::TimeZone;
+
+    fn schedule(cron: &str) -> CheckSchedule {
+        CheckSchedule {
+            name: "morning".to_string(),
+            cron: cron.to_string(),
+            checks: vec!["orders".to_string()],
+            group: None,
+            environment: None,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_is_due() {
+        let daily = schedule("0 30 6 * * *");
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 5, 1, h, m, 0).unwrap();
+        assert!(daily.is_due(at(6, 29), at(6, 30)));
+        assert!(!daily.is_due(at(6, 30), at(6, 31)));
+        assert!(!CheckSchedule {
+            enabled: false,
+            ..daily.clone()
+        }
+        .is_due(at(6, 29), at(6, 30)));
+
+        assert!(schedule("not cron").validate().is_err());
+        assert!(CheckSchedule {
+            checks: vec![],
+            ..daily
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_due() {
+        let db = Db::new("sqlite::memory:").await.unwrap();
+        db.save_schedule(&(&schedule("0 * * * * *")).into())
+            .await
+            .unwrap();
+        let runner = Arc::new(CheckRunner::new(db.clone(), Arc::new(MockContext)));
+        let check = ScriptedCheck::new("orders", Behavior::Return(CheckStatus::Success));
+        let scheduler = Scheduler::new(db.clone(), runner, vec![Arc::new(check)]);
+
+        let now = Utc::now();
+        let started = scheduler
+            .run_due(now - chrono::Duration::minutes(2), now)
+            .await
+            .unwrap();
+        assert_eq!(started, vec!["morning"]);
+
+        for _ in 0..50 {
+            if !db.get_recent_results(1).await.unwrap().is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("scheduled check did not run");
+    }
+}
@@ -0,0 +1,415 @@
+//! Application settings.
+//!
+//! Settings are layered: built-in defaults, then a TOML file (`--config`,
+//! `DATA_DASH_CONFIG`, or `data_dash.toml` in the working directory if it
+//! exists), then `DATA_DASH_<SECTION>__<KEY>` environment variables (e.g.
+//! `DATA_DASH_SERVER__BIND=0.0.0.0:3000`), then command-line flags. Each
+//! layer only replaces the keys it sets. The result is validated before
+//! the server starts.
+
+use crate::alerting::AlertRules;
+use crate::db::Backend;
+use crate::runner::limits::ConcurrencyLimits;
+use anyhow::{bail, Context, Result};
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::{Table, Value};
+use tracing::level_filters::LevelFilter;
+
+/// Prefix of the environment variables read as settings.
+pub const ENV_PREFIX: &str = "DATA_DASH_";
+/// Environment variable naming the settings file.
+pub const CONFIG_ENV: &str = "DATA_DASH_CONFIG";
+/// Settings file read when no other is given, if it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "data_dash.toml";
+
+const MASK: &str = "********";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub secret_store: SecretStoreSettings,
+    pub scheduler: SchedulerSettings,
+    pub logging: LoggingSettings,
+    pub alerting: AlertingSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    /// Non-loopback addresses require `auth`.
+    pub bind: SocketAddr,
+    /// Require a login session or API token for every API request.
+    pub auth: bool,
+    /// Directory of the built UI.
+    pub ui_dir: PathBuf,
+    /// Default environment for check runs.
+    pub environment: Option<String>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([127, 0, 0, 1], 3000)),
+            auth: false,
+            ui_dir: PathBuf::from("ui/dist"),
+            environment: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    /// May contain credentials; masked by `config show`.
+    pub url: String,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        Self {
+            url: "sqlite:data_dash.db".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackend {
+    /// The `secrets` table, managed through the UI and API.
+    #[default]
+    Database,
+    /// Environment variables named by the secret key.
+    Env,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretStoreSettings {
+    pub backend: SecretBackend,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerSettings {
+    /// Run check schedules. Checks can still be run through the API.
+    pub enabled: bool,
+    /// How often due schedules are looked for.
+    pub tick_secs: u64,
+    /// Checks executing at once, across all runs.
+    pub max_concurrent_checks: usize,
+    /// Checks executing at once against one connection.
+    pub max_per_connection: usize,
+}
+
+impl Default for SchedulerSettings {
+    fn default() -> Self {
+        let limits = ConcurrencyLimits::default();
+        Self {
+            enabled: true,
+            tick_secs: crate::scheduler::DEFAULT_TICK.as_secs(),
+            max_concurrent_checks: limits.global,
+            max_per_connection: limits.per_connection,
+        }
+    }
+}
+
+impl SchedulerSettings {
+    pub fn limits(&self) -> ConcurrencyLimits {
+        ConcurrencyLimits {
+            global: self.max_concurrent_checks,
+            per_connection: self.max_per_connection,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSettings {
+    /// `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+    /// Append logs to this file instead of writing them to stderr.
+    pub file: Option<PathBuf>,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file: None,
+        }
+    }
+}
+
+impl LoggingSettings {
+    pub fn level_filter(&self) -> Result<LevelFilter> {
+        LevelFilter::from_str(&self.level)
+            .map_err(|_| anyhow::anyhow!("Unknown log level: {}", self.level))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertingSettings {
+    /// Number of consecutive `Warning` results that raise an alert.
+    pub consecutive_warnings: u32,
+    /// Time of day (UTC, `HH:MM`) at which the email status digest is sent.
+    pub digest_at: String,
+}
+
+impl Default for AlertingSettings {
+    fn default() -> Self {
+        Self {
+            consecutive_warnings: AlertRules::default().consecutive_warnings,
+            digest_at: "08:00".to_string(),
+        }
+    }
+}
+
+impl AlertingSettings {
+    pub fn rules(&self) -> AlertRules {
+        AlertRules {
+            consecutive_warnings: self.consecutive_warnings,
+        }
+    }
+
+    pub fn digest_time(&self) -> Result<NaiveTime> {
+        NaiveTime::parse_from_str(&self.digest_at, "%H:%M")
+            .with_context(|| format!("Invalid digest time {:?}, expected HH:MM", self.digest_at))
+    }
+}
+
+/// A setting given on the command line, as a dotted path and a value.
+pub type Override = (&'static str, Value);
+
+impl Settings {
+    /// Loads the settings from the process environment: the settings file
+    /// given by `config` or `DATA_DASH_CONFIG`, the `DATA_DASH_*` variables,
+    /// then `overrides`.
+    pub fn load(config: Option<&Path>, overrides: Vec<Override>) -> Result<Self> {
+        let file = match config {
+            Some(path) => Some(path.to_path_buf()),
+            None => match std::env::var_os(CONFIG_ENV) {
+                Some(path) => Some(PathBuf::from(path)),
+                None => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|p| p.exists()),
+            },
+        };
+        let file = match file {
+            Some(path) => Some(
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("Could not read settings file {}", path.display()))?,
+            ),
+            None => None,
+        };
+        Self::from_layers(file.as_deref(), std::env::vars(), overrides)
+    }
+
+    /// Layers the settings file contents, environment variables and
+    /// overrides on top of the defaults.
+    pub fn from_layers(
+        file: Option<&str>,
+        env: impl IntoIterator<Item = (String, String)>,
+        overrides: Vec<Override>,
+    ) -> Result<Self> {
+        let Value::Table(mut settings) = Value::try_from(Settings::default())? else {
+            unreachable!("settings serialize to a table");
+        };
+        if let Some(text) = file {
+            let table: Table = toml::from_str(text).context("Invalid settings file")?;
+            merge(&mut settings, table);
+        }
+        for (name, value) in env {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if name == CONFIG_ENV {
+                continue;
+            }
+            let path = key.to_lowercase().replace("__", ".");
+            set(&mut settings, &path, env_value(&value))
+                .with_context(|| format!("Invalid environment variable {}", name))?;
+        }
+        for (path, value) in overrides {
+            set(&mut settings, path, value)?;
+        }
+        Value::Table(settings)
+            .try_into()
+            .context("Invalid settings")
+    }
+
+    /// Reports every invalid setting at once.
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        if let Err(e) = crate::auth::check_bind_address(&self.server.bind, self.server.auth) {
+            errors.push(e.to_string());
+        }
+        if Backend::from_url(&self.database.url).is_err() {
+            errors.push(format!(
+                "Unsupported database URL {}: expected sqlite: or postgres://",
+                mask_url(&self.database.url)
+            ));
+        }
+        if self.scheduler.tick_secs == 0 {
+            errors.push("scheduler.tick_secs must be at least 1".to_string());
+        }
+        if self.scheduler.max_concurrent_checks == 0 || self.scheduler.max_per_connection == 0 {
+            errors.push("scheduler concurrency limits must be at least 1".to_string());
+        }
+        if let Err(e) = self.logging.level_filter() {
+            errors.push(e.to_string());
+        }
+        if let Err(e) = self.alerting.digest_time() {
+            errors.push(e.to_string());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            bail!("Invalid settings:\n  {}", errors.join("\n  "))
+        }
+    }
+
+    /// The settings as TOML, with credentials masked.
+    pub fn to_masked_toml(&self) -> Result<String> {
+        let mut masked = self.clone();
+        masked.database.url = mask_url(&masked.database.url);
+        Ok(toml::to_string(&masked)?)
+    }
+}
+
+/// Replaces the password in the user info of a URL, if any.
+fn mask_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    // Only user info with a password is masked.
+    match rest[..authority_end].rfind('@') {
+        Some(at) => match rest[..at].split_once(':') {
+            Some((user, _)) => format!("{}://{}:{}{}", scheme, user, MASK, &rest[at..]),
+            None => url.to_string(),
+        },
+        None => url.to_string(),
+    }
+}
+
+/// An environment variable as a TOML value: booleans and numbers are typed,
+/// anything else is a string.
+fn env_value(raw: &str) -> Value {
+    if let Ok(b) = raw.parse::<bool>() {
+        Value::Boolean(b)
+    } else if let Ok(i) = raw.parse::<i64>() {
+        Value::Integer(i)
+    } else {
+        Value::String(raw.to_string())
+    }
+}
+
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(layer)) => merge(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Sets a dotted `section.key` path.
+fn set(settings: &mut Table, path: &str, value: Value) -> Result<()> {
+    let Some((section, key)) = path.split_once('.') else {
+        bail!("Unknown setting {}", path);
+    };
+    match settings.get_mut(section) {
+        Some(Value::Table(table)) => {
+            table.insert(key.to_string(), value);
+            Ok(())
+        }
+        _ => bail!("Unknown settings section {}", section),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_layers() {
+        let file = r#"
+            [server]
+            bind = "127.0.0.1:8080"
+            ui_dir = "/srv/ui"
+
+            [logging]
+            level = "debug"
+        "#;
+        let settings = Settings::from_layers(
+            Some(file),
+            env(&[
+                ("DATA_DASH_SERVER__BIND", "127.0.0.1:9090"),
+                ("DATA_DASH_SCHEDULER__TICK_SECS", "5"),
+                ("DATA_DASH_CONFIG", "ignored.toml"),
+                ("PATH", "/usr/bin"),
+            ]),
+            vec![("logging.level", Value::String("warn".into()))],
+        )
+        .unwrap();
+
+        assert_eq!(settings.server.bind.port(), 9090);
+        assert_eq!(settings.server.ui_dir, PathBuf::from("/srv/ui"));
+        assert_eq!(settings.scheduler.tick_secs, 5);
+        assert_eq!(settings.logging.level, "warn");
+        assert_eq!(settings.database.url, "sqlite:data_dash.db");
+        settings.validate().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        assert!(Settings::from_layers(Some("[server]\nport = 1"), env(&[]), vec![]).is_err());
+        assert!(Settings::from_layers(None, env(&[("DATA_DASH_NOPE__X", "1")]), vec![]).is_err());
+
+        let settings = Settings::from_layers(
+            Some("[server]\nbind = \"0.0.0.0:3000\"\n[alerting]\ndigest_at = \"8am\""),
+            env(&[
+                ("DATA_DASH_LOGGING__LEVEL", "loud"),
+                ("DATA_DASH_DATABASE__URL", "mysql://dash@db/dash"),
+            ]),
+            vec![],
+        )
+        .unwrap();
+        let message = settings.validate().unwrap_err().to_string();
+        assert!(message.contains("Unsupported database URL"), "{}", message);
+        assert!(message.contains("without authentication"), "{}", message);
+        assert!(message.contains("Unknown log level"), "{}", message);
+        assert!(message.contains("Invalid digest time"), "{}", message);
+    }
+
+    #[test]
+    fn test_masked() {
+        assert_eq!(
+            mask_url("postgres://dash:hunter2@db:5432/dash"),
+            "postgres://dash:********@db:5432/dash"
+        );
+        assert_eq!(mask_url("sqlite:data_dash.db"), "sqlite:data_dash.db");
+        assert_eq!(mask_url("postgres://app@db/x"), "postgres://app@db/x");
+
+        let mut settings = Settings::default();
+        settings.database.url = "postgres://dash:hunter2@db/dash".to_string();
+        let shown = settings.to_masked_toml().unwrap();
+        assert!(!shown.contains("hunter2"));
+        assert!(shown.contains("[scheduler]"));
+        settings.validate().unwrap();
+    }
+}